@@ -13,6 +13,35 @@ pub fn singleton(args: TokenStream, input: TokenStream) -> TokenStream {
 
         impl ::dlrf::DLRFSingleton for #input_struct_ident {
             const DLRF_NAME: &'static str = #dlrf_name;
+
+            fn static_slot() -> &'static ::dlrf::SingletonSlot {
+                static SLOT: ::dlrf::SingletonSlot = ::dlrf::SingletonSlot::new();
+                &SLOT
+            }
+        }
+
+        impl #input_struct_ident {
+            /// Returns the singleton's instance, or None if it has not been
+            /// constructed or its static could not be resolved.
+            ///
+            /// # Safety
+            /// A static resolver must be installed with
+            /// `dlrf::set_static_resolver` and no mutable references to the
+            /// singleton may exist.
+            pub unsafe fn instance() -> Option<&'static Self> {
+                ::dlrf::instance_ptr::<Self>().map(|instance| unsafe { instance.as_ref() })
+            }
+
+            /// Returns the singleton's instance mutably, or None if it has not
+            /// been constructed or its static could not be resolved.
+            ///
+            /// # Safety
+            /// A static resolver must be installed with
+            /// `dlrf::set_static_resolver` and no other references to the
+            /// singleton may exist.
+            pub unsafe fn instance_mut() -> Option<&'static mut Self> {
+                ::dlrf::instance_ptr::<Self>().map(|mut instance| unsafe { instance.as_mut() })
+            }
         }
     })
 }
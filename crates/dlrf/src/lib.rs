@@ -1,6 +1,150 @@
+use std::collections::HashMap;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, OnceLock, PoisonError};
+
 /// Used to denote DLRF reflection names for singletons.
 pub trait DLRFSingleton {
     const DLRF_NAME: &'static str;
+
+    /// Per-type cache for the address of the singleton's static. The
+    /// `singleton` macro gives every type its own static slot, manual
+    /// implementations fall back to a slot shared by all types with the same
+    /// name.
+    fn static_slot() -> &'static SingletonSlot {
+        shared_slot(Self::DLRF_NAME)
+    }
+}
+
+fn shared_slot(name: &'static str) -> &'static SingletonSlot {
+    static SLOTS: Mutex<Option<HashMap<&'static str, &'static SingletonSlot>>> = Mutex::new(None);
+
+    SLOTS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get_or_insert_with(HashMap::new)
+        .entry(name)
+        .or_insert_with(|| Box::leak(Box::new(SingletonSlot::new())))
+}
+
+/// Finds the address of a singleton's static by its DLRF name.
+pub type StaticResolver = fn(&str) -> Option<usize>;
+
+static STATIC_RESOLVER: OnceLock<StaticResolver> = OnceLock::new();
+
+/// Installs the function used to find singleton statics that have not been
+/// cached in their slot yet. Only the first resolver that gets installed is
+/// used.
+pub fn set_static_resolver(resolver: StaticResolver) {
+    let _ = STATIC_RESOLVER.set(resolver);
+}
+
+/// Address of the static holding the singleton's instance pointer. Only the
+/// first lookup goes through the installed resolver, the address is cached
+/// in the type's slot afterwards. Returns None if there is no resolver or the
+/// resolver could not find the static.
+pub fn resolve_static<T: DLRFSingleton>() -> Option<usize> {
+    if let Some(address) = T::static_slot().get() {
+        return Some(address);
+    }
+
+    let address = STATIC_RESOLVER.get()?(T::DLRF_NAME)?;
+    T::static_slot().set(address);
+    Some(address)
+}
+
+/// Reads the singleton's instance pointer from its static. Returns None if
+/// the static could not be resolved or the singleton has not been
+/// constructed.
+pub fn instance_ptr<T: DLRFSingleton>() -> Option<NonNull<T>> {
+    let address = resolve_static::<T>()?;
+
+    // SAFETY: resolved addresses point at the static holding the pointer.
+    NonNull::new(unsafe { *(address as *const *mut T) })
+}
+
+/// Caches the address of the static holding a singleton's instance pointer
+/// once it has been resolved.
+pub struct SingletonSlot(AtomicUsize);
+
+impl SingletonSlot {
+    pub const fn new() -> Self {
+        Self(AtomicUsize::new(0))
+    }
+
+    pub fn get(&self) -> Option<usize> {
+        match self.0.load(Ordering::Relaxed) {
+            0 => None,
+            address => Some(address),
+        }
+    }
+
+    pub fn set(&self, address: usize) {
+        self.0.store(address, Ordering::Relaxed);
+    }
+}
+
+impl Default for SingletonSlot {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 pub use dlrf_derive::singleton;
+
+#[cfg(test)]
+mod test {
+    use std::ptr::NonNull;
+
+    use super::{instance_ptr, resolve_static, set_static_resolver, DLRFSingleton};
+
+    struct Resolved(u32);
+
+    impl DLRFSingleton for Resolved {
+        const DLRF_NAME: &'static str = "Resolved";
+    }
+
+    struct Unconstructed;
+
+    impl DLRFSingleton for Unconstructed {
+        const DLRF_NAME: &'static str = "Unconstructed";
+    }
+
+    struct Unknown;
+
+    impl DLRFSingleton for Unknown {
+        const DLRF_NAME: &'static str = "Unknown";
+    }
+
+    static mut RESOLVED_INSTANCE: Resolved = Resolved(42);
+    static mut RESOLVED_STATIC: *mut Resolved = std::ptr::null_mut();
+    static UNCONSTRUCTED_STATIC: usize = 0;
+
+    #[test]
+    fn resolves_and_caches_statics() {
+        unsafe { RESOLVED_STATIC = &raw mut RESOLVED_INSTANCE };
+
+        set_static_resolver(|name| match name {
+            "Resolved" => Some(&raw const RESOLVED_STATIC as usize),
+            "Unconstructed" => Some(&UNCONSTRUCTED_STATIC as *const usize as usize),
+            _ => None,
+        });
+
+        let address = resolve_static::<Resolved>().unwrap();
+        assert_eq!(Resolved::static_slot().get(), Some(address));
+        // Manual implementations share their slot by name.
+        assert!(std::ptr::eq(
+            Resolved::static_slot(),
+            super::shared_slot("Resolved")
+        ));
+
+        let instance: NonNull<Resolved> = instance_ptr::<Resolved>().unwrap();
+        assert_eq!(unsafe { instance.as_ref() }.0, 42);
+
+        assert!(resolve_static::<Unconstructed>().is_some());
+        assert!(instance_ptr::<Unconstructed>().is_none());
+
+        assert!(resolve_static::<Unknown>().is_none());
+        assert_eq!(Unknown::static_slot().get(), None);
+    }
+}
@@ -0,0 +1,393 @@
+//! Hands work from arbitrary threads over to game tasks.
+//!
+//! Most of the game's structures can only be touched safely from within its
+//! own tasks. The dispatcher lets other threads (steam callbacks, sockets,
+//! file watchers, ...) queue closures that are ran from a recurring task in the
+//! requested task group instead.
+//!
+//! A panicking closure is logged and doesn't stop the other queued closures
+//! from running.
+//!
+//! ```ignore
+//! let cs_task = wait_for_instance::<CSTaskImp>(Duration::from_secs(30))?;
+//! let dispatcher = GameThreadDispatcher::new(cs_task, [CSTaskGroupIndex::FrameBegin]);
+//!
+//! std::thread::spawn(move || {
+//!     let handle = dispatcher
+//!         .submit_with_result(CSTaskGroupIndex::FrameBegin, |_| {
+//!             let world_chr_man = unsafe { get_instance::<WorldChrMan>() }.unwrap()?;
+//!             Some(world_chr_man.main_player.as_ref()?.chr_ins.field_ins_handle)
+//!         })
+//!         .unwrap();
+//!
+//!     tracing::info!("Main player: {:?}", handle.recv());
+//! });
+//! ```
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+
+use eldenring::cs::{CSTaskGroupIndex, CSTaskImp};
+use eldenring::fd4::FD4TaskData;
+use thiserror::Error;
+
+use crate::task::CSTaskImpExt;
+
+#[derive(Error, Debug)]
+pub enum DispatchError {
+    #[error("Dispatcher has no queue for task group {0:?}.")]
+    UnregisteredGroup(CSTaskGroupIndex),
+}
+
+type JobFn = Box<dyn FnOnce(&FD4TaskData) + Send>;
+
+struct Job {
+    f: JobFn,
+    next: *mut Job,
+}
+
+/// Lock-free multi-producer queue. Producers push onto an intrusive stack and
+/// the consumer takes the entire stack at once, so there's no ABA to worry
+/// about.
+struct JobQueue {
+    group: CSTaskGroupIndex,
+    head: AtomicPtr<Job>,
+}
+
+// SAFETY: jobs are Send and only reachable through the atomic head.
+unsafe impl Send for JobQueue {}
+unsafe impl Sync for JobQueue {}
+
+impl JobQueue {
+    fn push(&self, f: JobFn) {
+        let job = Box::into_raw(Box::new(Job {
+            f,
+            next: ptr::null_mut(),
+        }));
+
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            unsafe { (*job).next = head };
+            match self
+                .head
+                .compare_exchange_weak(head, job, Ordering::Release, Ordering::Relaxed)
+            {
+                Ok(_) => break,
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    /// Takes all queued jobs in submission order.
+    fn take(&self) -> Vec<JobFn> {
+        let mut job = self.head.swap(ptr::null_mut(), Ordering::Acquire);
+
+        let mut jobs = Vec::new();
+        while !job.is_null() {
+            let boxed = unsafe { Box::from_raw(job) };
+            job = boxed.next;
+            jobs.push(boxed.f);
+        }
+
+        jobs.reverse();
+        jobs
+    }
+
+    /// Runs all queued jobs. Panics are caught per job so a single failing
+    /// job doesn't take the others down with it.
+    fn run(&self, data: &FD4TaskData) {
+        for job in self.take() {
+            if let Err(e) = catch_unwind(AssertUnwindSafe(|| job(data))) {
+                let message = e
+                    .downcast_ref::<&str>()
+                    .copied()
+                    .or_else(|| e.downcast_ref::<String>().map(String::as_str))
+                    .unwrap_or("unknown panic");
+
+                tracing::error!("Dispatched job in {:?} panicked: {message}", self.group);
+            }
+        }
+    }
+}
+
+impl Drop for JobQueue {
+    fn drop(&mut self) {
+        drop(self.take());
+    }
+}
+
+/// Queues closures from any thread to be ran from game tasks. The dispatcher
+/// is cheap to clone and can be moved to other threads freely.
+#[derive(Clone)]
+pub struct GameThreadDispatcher {
+    queues: Arc<[JobQueue]>,
+}
+
+impl GameThreadDispatcher {
+    /// Creates a dispatcher that can run closures in the specified task
+    /// groups. This registers a recurring task for every group that drains its
+    /// queue once per frame, the tasks stay registered for as long as the game
+    /// runs.
+    ///
+    /// The tasks hold on to the queues, so dropping every clone of the
+    /// dispatcher doesn't drop the jobs still queued. They are ran the next
+    /// time their task group runs.
+    pub fn new(cs_task: &CSTaskImp, groups: impl IntoIterator<Item = CSTaskGroupIndex>) -> Self {
+        let dispatcher = Self::with_queues(groups);
+
+        for (index, queue) in dispatcher.queues.iter().enumerate() {
+            let queues = dispatcher.queues.clone();
+            cs_task.run_recurring(
+                move |data: &FD4TaskData| queues[index].run(data),
+                queue.group,
+            );
+        }
+
+        dispatcher
+    }
+
+    /// Creates a queue for every distinct group without registering the tasks
+    /// that drain them. Queued jobs are dropped along with the last clone of
+    /// the dispatcher.
+    fn with_queues(groups: impl IntoIterator<Item = CSTaskGroupIndex>) -> Self {
+        let mut queues: Vec<JobQueue> = Vec::new();
+        for group in groups {
+            if !queues.iter().any(|q| q.group as u32 == group as u32) {
+                queues.push(JobQueue {
+                    group,
+                    head: AtomicPtr::new(ptr::null_mut()),
+                });
+            }
+        }
+
+        Self {
+            queues: queues.into(),
+        }
+    }
+
+    /// Queues a closure to be ran during the next execution of the task group.
+    pub fn submit<F>(&self, group: CSTaskGroupIndex, f: F) -> Result<(), DispatchError>
+    where
+        F: FnOnce(&FD4TaskData) + Send + 'static,
+    {
+        self.queue(group)?.push(Box::new(f));
+        Ok(())
+    }
+
+    /// Queues a closure to be ran during the next execution of the task group
+    /// and returns a receiver for the closure's result.
+    pub fn submit_with_result<F, R>(
+        &self,
+        group: CSTaskGroupIndex,
+        f: F,
+    ) -> Result<Oneshot<R>, DispatchError>
+    where
+        F: FnOnce(&FD4TaskData) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let (sender, receiver) = mpsc::sync_channel(1);
+        self.queue(group)?.push(Box::new(move |data: &FD4TaskData| {
+            // The receiver might have been dropped already, which is fine.
+            let _ = sender.send(f(data));
+        }));
+
+        Ok(Oneshot { receiver })
+    }
+
+    fn queue(&self, group: CSTaskGroupIndex) -> Result<&JobQueue, DispatchError> {
+        self.queues
+            .iter()
+            .find(|q| q.group as u32 == group as u32)
+            .ok_or(DispatchError::UnregisteredGroup(group))
+    }
+}
+
+/// Receives the result of a closure submitted with
+/// [`GameThreadDispatcher::submit_with_result`].
+pub struct Oneshot<R> {
+    receiver: mpsc::Receiver<R>,
+}
+
+impl<R> Oneshot<R> {
+    /// Blocks until the closure has ran. Never call this from within a game
+    /// task since it can end up waiting on the task group it is blocking, use
+    /// [`Oneshot::try_recv`] there instead.
+    pub fn recv(self) -> Result<R, mpsc::RecvError> {
+        self.receiver.recv()
+    }
+
+    /// Like [`Oneshot::recv`] but gives up after the timeout.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<R, mpsc::RecvTimeoutError> {
+        self.receiver.recv_timeout(timeout)
+    }
+
+    /// Returns the result if the closure has ran already.
+    pub fn try_recv(&self) -> Result<R, mpsc::TryRecvError> {
+        self.receiver.try_recv()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::mpsc::{RecvError, TryRecvError};
+    use std::sync::{Arc, Mutex};
+
+    use eldenring::cs::CSTaskGroupIndex;
+    use eldenring::fd4::FD4TaskData;
+
+    use super::{DispatchError, GameThreadDispatcher};
+
+    fn task_data() -> FD4TaskData {
+        unsafe { std::mem::zeroed() }
+    }
+
+    /// Runs the jobs like the group's task would during its next execution.
+    fn run(dispatcher: &GameThreadDispatcher, group: CSTaskGroupIndex) {
+        dispatcher.queue(group).unwrap().run(&task_data());
+    }
+
+    #[test]
+    fn runs_jobs_in_submission_order() {
+        let dispatcher = GameThreadDispatcher::with_queues([CSTaskGroupIndex::FrameBegin]);
+        let log = Arc::new(Mutex::new(Vec::new()));
+
+        for i in 0..5 {
+            let log = log.clone();
+            dispatcher
+                .submit(CSTaskGroupIndex::FrameBegin, move |_| {
+                    log.lock().unwrap().push(i)
+                })
+                .unwrap();
+        }
+        assert!(log.lock().unwrap().is_empty());
+
+        run(&dispatcher, CSTaskGroupIndex::FrameBegin);
+        assert_eq!(*log.lock().unwrap(), [0, 1, 2, 3, 4]);
+
+        // Jobs only run once.
+        run(&dispatcher, CSTaskGroupIndex::FrameBegin);
+        assert_eq!(log.lock().unwrap().len(), 5);
+    }
+
+    #[test]
+    fn keeps_order_per_thread_under_concurrent_submits() {
+        const THREADS: usize = 8;
+        const JOBS: usize = 500;
+
+        let dispatcher = GameThreadDispatcher::with_queues([CSTaskGroupIndex::FrameBegin]);
+        let log = Arc::new(Mutex::new(Vec::new()));
+
+        std::thread::scope(|scope| {
+            for thread in 0..THREADS {
+                let dispatcher = dispatcher.clone();
+                let log = log.clone();
+                scope.spawn(move || {
+                    for job in 0..JOBS {
+                        let log = log.clone();
+                        dispatcher
+                            .submit(CSTaskGroupIndex::FrameBegin, move |_| {
+                                log.lock().unwrap().push((thread, job))
+                            })
+                            .unwrap();
+                    }
+                });
+            }
+
+            // Drain while the producers are still submitting.
+            for _ in 0..10 {
+                run(&dispatcher, CSTaskGroupIndex::FrameBegin);
+                std::thread::yield_now();
+            }
+        });
+        run(&dispatcher, CSTaskGroupIndex::FrameBegin);
+
+        let log = log.lock().unwrap();
+        assert_eq!(log.len(), THREADS * JOBS);
+        for thread in 0..THREADS {
+            let jobs = log
+                .iter()
+                .filter(|(t, _)| *t == thread)
+                .map(|(_, job)| *job)
+                .collect::<Vec<_>>();
+            assert_eq!(jobs, (0..JOBS).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn queues_are_per_group() {
+        let dispatcher = GameThreadDispatcher::with_queues([
+            CSTaskGroupIndex::FrameBegin,
+            CSTaskGroupIndex::SystemStep,
+            CSTaskGroupIndex::FrameBegin,
+        ]);
+        assert_eq!(dispatcher.queues.len(), 2);
+
+        let frame_begin = dispatcher
+            .submit_with_result(CSTaskGroupIndex::FrameBegin, |_| "frame begin")
+            .unwrap();
+        let system_step = dispatcher
+            .submit_with_result(CSTaskGroupIndex::SystemStep, |_| "system step")
+            .unwrap();
+
+        run(&dispatcher, CSTaskGroupIndex::SystemStep);
+        assert_eq!(frame_begin.try_recv(), Err(TryRecvError::Empty));
+        assert_eq!(system_step.recv(), Ok("system step"));
+
+        assert!(matches!(
+            dispatcher.submit(CSTaskGroupIndex::SteamThread0, |_| {}),
+            Err(DispatchError::UnregisteredGroup(
+                CSTaskGroupIndex::SteamThread0
+            ))
+        ));
+    }
+
+    #[test]
+    fn returns_results_through_the_oneshot() {
+        let dispatcher = GameThreadDispatcher::with_queues([CSTaskGroupIndex::FrameBegin]);
+        let result = dispatcher
+            .submit_with_result(CSTaskGroupIndex::FrameBegin, |_| 6 * 7)
+            .unwrap();
+        assert_eq!(result.try_recv(), Err(TryRecvError::Empty));
+
+        // Results of jobs whose receiver is gone are discarded.
+        drop(
+            dispatcher
+                .submit_with_result(CSTaskGroupIndex::FrameBegin, |_| 0)
+                .unwrap(),
+        );
+
+        run(&dispatcher, CSTaskGroupIndex::FrameBegin);
+        assert_eq!(result.recv(), Ok(42));
+    }
+
+    #[test]
+    fn panicking_jobs_drop_their_result() {
+        let dispatcher = GameThreadDispatcher::with_queues([CSTaskGroupIndex::FrameBegin]);
+        let result = dispatcher
+            .submit_with_result(CSTaskGroupIndex::FrameBegin, |_| -> u32 {
+                panic!("job failed")
+            })
+            .unwrap();
+        let next = dispatcher
+            .submit_with_result(CSTaskGroupIndex::FrameBegin, |_| 1)
+            .unwrap();
+
+        run(&dispatcher, CSTaskGroupIndex::FrameBegin);
+        assert_eq!(result.recv(), Err(RecvError));
+        // Jobs queued after the panicking one still run.
+        assert_eq!(next.recv(), Ok(1));
+    }
+
+    #[test]
+    fn dropping_the_dispatcher_drops_queued_jobs() {
+        let dispatcher = GameThreadDispatcher::with_queues([CSTaskGroupIndex::FrameBegin]);
+        let result = dispatcher
+            .submit_with_result(CSTaskGroupIndex::FrameBegin, |_| 1)
+            .unwrap();
+
+        drop(dispatcher);
+        assert_eq!(result.recv(), Err(RecvError));
+    }
+}
@@ -0,0 +1,491 @@
+//! Small async executor driven by the game's task runtime.
+//!
+//! Spawned futures ("scripts") are polled from recurring tasks registered to
+//! the task groups they're waiting on. There's no real wakeup mechanism, every
+//! pending script is simply polled again the next time its task group runs.
+//! Scripts can move between task groups with [`next_frame`] so they can be
+//! ordered against specific engine phases.
+//!
+//! ```ignore
+//! let cs_task = wait_for_instance::<CSTaskImp>(Duration::from_secs(30))?;
+//! executor::spawn(cs_task, CSTaskGroupIndex::FrameBegin, async {
+//!     sleep_game_time(2.0).await;
+//!     next_frame(CSTaskGroupIndex::ChrIns_PostPhysics).await;
+//!     // Do something after the physics have been processed.
+//! })
+//! .detach();
+//! ```
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::pin::Pin;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use dlrf::DLRFSingleton;
+use eldenring::cs::{CSTaskGroupIndex, CSTaskImp};
+use eldenring::fd4::FD4TaskData;
+
+use crate::singleton::{wait_for_instance_async, WaitForInstance};
+use crate::task::CSTaskImpExt;
+
+type ScriptFuture = Pin<Box<dyn Future<Output = ()>>>;
+
+struct Script {
+    future: Mutex<Option<ScriptFuture>>,
+    /// Task group the script wants to be polled from next.
+    group: AtomicU32,
+    cancelled: AtomicBool,
+    finished: AtomicBool,
+}
+
+// SAFETY: scripts are only ever polled from one task at a time. Futures
+// spawned through the safe API are Send anyways.
+unsafe impl Send for Script {}
+unsafe impl Sync for Script {}
+
+#[derive(Default)]
+struct ExecutorState {
+    cs_task: Option<NonNull<CSTaskImp>>,
+    /// Task groups that have a driver task registered.
+    drivers: HashSet<u32>,
+    /// Scripts waiting to be polled by the task group.
+    queues: HashMap<u32, Vec<Arc<Script>>>,
+    /// Amount of times each task group's driver has ran.
+    frames: HashMap<u32, u64>,
+}
+
+// SAFETY: the CSTaskImp is a singleton that lives for the duration of the game.
+unsafe impl Send for ExecutorState {}
+
+static EXECUTOR: LazyLock<Mutex<ExecutorState>> = LazyLock::new(Default::default);
+
+#[derive(Clone, Copy)]
+struct PollContext {
+    group: u32,
+    delta_time: f32,
+    script: *const Script,
+}
+
+thread_local! {
+    static POLL_CONTEXT: Cell<Option<PollContext>> = const { Cell::new(None) };
+}
+
+fn poll_context() -> PollContext {
+    POLL_CONTEXT
+        .get()
+        .expect("Future must be awaited from a script spawned on the executor")
+}
+
+/// Handle to a spawned script. Dropping the handle cancels the script.
+#[must_use = "dropping the handle cancels the script"]
+pub struct ScriptHandle {
+    script: Option<Arc<Script>>,
+}
+
+impl ScriptHandle {
+    /// Whether or not the script has ran to completion.
+    pub fn is_finished(&self) -> bool {
+        self.script
+            .as_ref()
+            .is_some_and(|s| s.finished.load(Ordering::Relaxed))
+    }
+
+    /// Drops the handle without cancelling the script.
+    pub fn detach(mut self) {
+        self.script.take();
+    }
+}
+
+impl Drop for ScriptHandle {
+    fn drop(&mut self) {
+        if let Some(script) = self.script.as_ref() {
+            script.cancelled.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Spawns a script that is first polled during the next execution of the
+/// task group.
+pub fn spawn<F: Future<Output = ()> + Send + 'static>(
+    cs_task: &CSTaskImp,
+    group: CSTaskGroupIndex,
+    future: F,
+) -> ScriptHandle {
+    unsafe { spawn_unchecked(cs_task, group, future) }
+}
+
+/// Spawns a script that isn't Send, ex: one holding on to a singleton
+/// reference across awaits.
+///
+/// # Safety
+/// The task groups the script gets polled from might run on different task
+/// runner threads. Caller must ensure the future holds no state that can't be
+/// moved between threads.
+pub unsafe fn spawn_unchecked<F: Future<Output = ()> + 'static>(
+    cs_task: &CSTaskImp,
+    group: CSTaskGroupIndex,
+    future: F,
+) -> ScriptHandle {
+    let script = Arc::new(Script {
+        future: Mutex::new(Some(Box::pin(future))),
+        group: AtomicU32::new(group as u32),
+        cancelled: AtomicBool::new(false),
+        finished: AtomicBool::new(false),
+    });
+
+    let mut state = EXECUTOR.lock().unwrap();
+    state.cs_task = Some(NonNull::from(cs_task));
+    enqueue(&mut state, script.clone());
+
+    ScriptHandle {
+        script: Some(script),
+    }
+}
+
+/// Queues the script on the task group it wants to be polled from next,
+/// registering a driver task for that group if there isn't one yet.
+fn enqueue(state: &mut ExecutorState, script: Arc<Script>) {
+    let group = script.group.load(Ordering::Relaxed);
+
+    if state.drivers.insert(group) {
+        let cs_task = unsafe {
+            state
+                .cs_task
+                .expect("Executor has no CSTaskImp to register drivers with")
+                .as_ref()
+        };
+
+        // SAFETY: group always comes from a CSTaskGroupIndex.
+        let group_index: CSTaskGroupIndex = unsafe { std::mem::transmute(group) };
+        cs_task.run_recurring(move |data: &FD4TaskData| drive(group, data), group_index);
+    }
+
+    state.queues.entry(group).or_default().push(script);
+}
+
+/// Polls all scripts queued on the task group and requeues the ones that are
+/// still pending.
+fn drive(group: u32, data: &FD4TaskData) {
+    let pending = poll_group(group, data.delta_time.time);
+    if pending.is_empty() {
+        return;
+    }
+
+    let mut state = EXECUTOR.lock().unwrap();
+    for script in pending {
+        enqueue(&mut state, script);
+    }
+}
+
+/// Polls all scripts queued on the task group, returning the ones that are
+/// still pending.
+fn poll_group(group: u32, delta_time: f32) -> Vec<Arc<Script>> {
+    let scripts = {
+        let mut state = EXECUTOR.lock().unwrap();
+        *state.frames.entry(group).or_default() += 1;
+        std::mem::take(state.queues.entry(group).or_default())
+    };
+
+    let mut cx = Context::from_waker(Waker::noop());
+    let mut pending = Vec::with_capacity(scripts.len());
+    for script in scripts {
+        if script.cancelled.load(Ordering::Relaxed) {
+            continue;
+        }
+
+        let mut future = script.future.lock().unwrap();
+        let Some(inner) = future.as_mut() else {
+            continue;
+        };
+
+        POLL_CONTEXT.set(Some(PollContext {
+            group,
+            delta_time,
+            script: Arc::as_ptr(&script),
+        }));
+        let poll = inner.as_mut().poll(&mut cx);
+        POLL_CONTEXT.set(None);
+
+        if poll.is_ready() {
+            future.take();
+            script.finished.store(true, Ordering::Relaxed);
+        } else {
+            drop(future);
+            pending.push(script);
+        }
+    }
+
+    pending
+}
+
+fn group_frame(group: u32) -> u64 {
+    EXECUTOR
+        .lock()
+        .unwrap()
+        .frames
+        .get(&group)
+        .copied()
+        .unwrap_or_default()
+}
+
+/// Resumes the script the next time the task group runs. Awaiting a task group
+/// that comes later in the frame resumes the script later in the same frame.
+pub fn next_frame(group: CSTaskGroupIndex) -> NextFrame {
+    NextFrame {
+        group: group as u32,
+        frame: None,
+    }
+}
+
+pub struct NextFrame {
+    group: u32,
+    frame: Option<u64>,
+}
+
+impl Future for NextFrame {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+        let context = poll_context();
+        let current = group_frame(self.group);
+
+        match self.frame {
+            Some(frame) if context.group == self.group && current > frame => Poll::Ready(()),
+            Some(_) => Poll::Pending,
+            None => {
+                self.frame = Some(current);
+                unsafe { &*context.script }
+                    .group
+                    .store(self.group, Ordering::Relaxed);
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Resumes the script after the specified amount of game time has passed. Game
+/// time only advances while the task group the script is polled from runs.
+pub fn sleep_game_time(seconds: f32) -> SleepGameTime {
+    SleepGameTime {
+        remaining: seconds,
+        started: false,
+    }
+}
+
+pub struct SleepGameTime {
+    remaining: f32,
+    started: bool,
+}
+
+impl Future for SleepGameTime {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+        let context = poll_context();
+
+        // Time spent in the frame the sleep got started in does not count.
+        if self.started {
+            self.remaining -= context.delta_time;
+        }
+        self.started = true;
+
+        if self.remaining <= 0.0 {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// Resumes the script once the condition is true. The condition is checked
+/// every time the script gets polled.
+pub fn wait_until<F: FnMut() -> bool + Unpin>(condition: F) -> WaitUntil<F> {
+    WaitUntil { condition }
+}
+
+pub struct WaitUntil<F> {
+    condition: F,
+}
+
+impl<F: FnMut() -> bool + Unpin> Future for WaitUntil<F> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if (self.condition)() {
+            Poll::Ready(())
+        } else {
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+}
+
+/// Resumes the script once the singleton has been instantiated.
+///
+/// # Safety
+/// Same as [`crate::singleton::get_instance`].
+pub unsafe fn wait_for_singleton<T: DLRFSingleton + 'static>() -> WaitForInstance<T> {
+    wait_for_instance_async::<T>()
+}
+
+#[cfg(test)]
+mod test {
+    use std::future::Future;
+    use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use super::{
+        next_frame, poll_group, sleep_game_time, wait_until, Script, ScriptHandle, EXECUTOR,
+    };
+    use eldenring::cs::CSTaskGroupIndex;
+
+    /// Queues a script without registering a driver task for its group.
+    /// Every test uses its own groups since the executor state is shared.
+    fn spawn(group: CSTaskGroupIndex, future: impl Future<Output = ()> + 'static) -> ScriptHandle {
+        let script = Arc::new(Script {
+            future: Mutex::new(Some(Box::pin(future))),
+            group: AtomicU32::new(group as u32),
+            cancelled: AtomicBool::new(false),
+            finished: AtomicBool::new(false),
+        });
+
+        EXECUTOR
+            .lock()
+            .unwrap()
+            .queues
+            .entry(group as u32)
+            .or_default()
+            .push(script.clone());
+
+        ScriptHandle {
+            script: Some(script),
+        }
+    }
+
+    /// Runs a single frame of the task group, like its driver task would.
+    fn run(group: CSTaskGroupIndex, delta_time: f32) {
+        let pending = poll_group(group as u32, delta_time);
+
+        let mut state = EXECUTOR.lock().unwrap();
+        for script in pending {
+            let group = script.group.load(Ordering::Relaxed);
+            state.queues.entry(group).or_default().push(script);
+        }
+    }
+
+    fn queued(group: CSTaskGroupIndex) -> usize {
+        EXECUTOR
+            .lock()
+            .unwrap()
+            .queues
+            .get(&(group as u32))
+            .map(Vec::len)
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn ready_script_finishes() {
+        let group = CSTaskGroupIndex::FrameBegin;
+        let handle = spawn(group, async {});
+
+        assert!(!handle.is_finished());
+        run(group, 0.016);
+        assert!(handle.is_finished());
+        assert_eq!(queued(group), 0);
+    }
+
+    #[test]
+    fn wait_until_polls_every_frame() {
+        let group = CSTaskGroupIndex::SteamThread0;
+        let polls = Arc::new(AtomicU32::new(0));
+
+        let handle = spawn(group, {
+            let polls = polls.clone();
+            async move {
+                wait_until(move || polls.fetch_add(1, Ordering::Relaxed) == 2).await;
+            }
+        });
+
+        run(group, 0.016);
+        run(group, 0.016);
+        assert!(!handle.is_finished());
+        run(group, 0.016);
+        assert!(handle.is_finished());
+        assert_eq!(polls.load(Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn next_frame_moves_between_groups() {
+        let from = CSTaskGroupIndex::SteamThread1;
+        let to = CSTaskGroupIndex::SteamThread2;
+        let resumed = Arc::new(AtomicU32::new(0));
+
+        let handle = spawn(from, {
+            let resumed = resumed.clone();
+            async move {
+                next_frame(to).await;
+                resumed.fetch_add(1, Ordering::Relaxed);
+                // Awaiting the current group waits for its next run.
+                next_frame(to).await;
+                resumed.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+
+        run(from, 0.016);
+        assert_eq!(queued(from), 0);
+        assert_eq!(queued(to), 1);
+
+        run(to, 0.016);
+        assert_eq!(resumed.load(Ordering::Relaxed), 1);
+        assert!(!handle.is_finished());
+
+        run(to, 0.016);
+        assert_eq!(resumed.load(Ordering::Relaxed), 2);
+        assert!(handle.is_finished());
+    }
+
+    #[test]
+    fn sleep_counts_game_time() {
+        let group = CSTaskGroupIndex::SteamThread3;
+        let handle = spawn(group, async {
+            sleep_game_time(0.5).await;
+        });
+
+        // Time spent in the starting frame doesn't count.
+        run(group, 1.0);
+        assert!(!handle.is_finished());
+        run(group, 0.25);
+        run(group, 0.0);
+        assert!(!handle.is_finished());
+        run(group, 0.25);
+        assert!(handle.is_finished());
+    }
+
+    #[test]
+    fn dropping_the_handle_cancels() {
+        let group = CSTaskGroupIndex::SteamThread4;
+        let polls = Arc::new(AtomicU32::new(0));
+        let condition = {
+            let polls = polls.clone();
+            move || {
+                polls.fetch_add(1, Ordering::Relaxed);
+                false
+            }
+        };
+
+        let handle = spawn(group, wait_until(condition.clone()));
+        run(group, 0.016);
+        drop(handle);
+        run(group, 0.016);
+        assert_eq!(polls.load(Ordering::Relaxed), 1);
+        assert_eq!(queued(group), 0);
+
+        spawn(group, wait_until(condition)).detach();
+        run(group, 0.016);
+        run(group, 0.016);
+        assert_eq!(polls.load(Ordering::Relaxed), 3);
+    }
+}
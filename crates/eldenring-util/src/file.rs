@@ -0,0 +1,402 @@
+//! Inspecting and unloading the game's file caps.
+//!
+//! Loading isn't wrapped. It's not known what `CSFileImpVmt::add_file_cap`
+//! does with the name and the file cap it's handed, and there's no way to
+//! create a file cap of the right type for a resource yet.
+//!
+//! ```ignore
+//! let file_imp = unsafe { get_instance::<CSFileImp>() }.unwrap().unwrap();
+//! for file_cap in file_imp.file_caps() {
+//!     tracing::info!("{} {:?}", file_cap.name, file_cap.state);
+//! }
+//!
+//! // From a mod's own thread, unloading is progressed by the game's tasks.
+//! file_imp.unload("data0:/menu/hi/01_common.tpf")?;
+//! unsafe { wait_for_unload("data0:/menu/hi/01_common.tpf", Duration::from_secs(10)) }?;
+//! ```
+use std::ptr::NonNull;
+use std::time::{Duration, Instant};
+
+use eldenring::cs::{
+    CSFileImp, CSFileImpVmt, CSFileRepository, CSFileRepositoryMutex, UntypedFileCap,
+};
+use eldenring::dlkr::DLAllocatorRef;
+use eldenring::fd4::{FD4FileCapState, HashStringError, OwnedFD4BasicHashString};
+use thiserror::Error;
+
+use crate::singleton::get_instance;
+
+#[derive(Error, Debug)]
+pub enum FileError {
+    #[error("No file cap named {0} is loaded.")]
+    NotLoaded(String),
+    #[error("Timed out waiting for {0} to load.")]
+    Timeout(String),
+    #[error("Timed out waiting for {0} to unload.")]
+    UnloadTimeout(String),
+    #[error("CSFileImp has no allocator.")]
+    NoAllocator,
+    #[error("Could not create the file cap name. {0}")]
+    Name(#[from] HashStringError),
+}
+
+/// Snapshot of a loaded file cap.
+#[derive(Debug, Clone)]
+pub struct FileCapInfo {
+    pub name: String,
+    pub state: FD4FileCapState,
+    pub reference_count: u32,
+}
+
+impl From<&UntypedFileCap> for FileCapInfo {
+    fn from(file_cap: &UntypedFileCap) -> Self {
+        Self {
+            name: file_cap.file_cap.res_cap.name.to_string(),
+            state: file_cap.file_cap.load_state,
+            reference_count: file_cap.file_cap.res_cap.reference_count,
+        }
+    }
+}
+
+pub trait CSFileImpExt {
+    /// Lists all file caps in the file repository.
+    fn file_caps(&self) -> Vec<FileCapInfo>;
+
+    /// Looks up a file cap by its name, ex: "data0:/regulation.bin".
+    fn file_cap(&self, name: &str) -> Option<FileCapInfo>;
+
+    /// Load state of the file cap with the specified name.
+    fn file_cap_state(&self, name: &str) -> Option<FD4FileCapState> {
+        self.file_cap(name).map(|f| f.state)
+    }
+
+    /// Unloads the file cap with the specified name through
+    /// `CSFileImpVmt::unload_file_cap_by_name`. Some resources do not survive
+    /// being unloaded while in use so be careful with what you unload. The
+    /// file cap leaves the repository once the game is done unloading it, see
+    /// [`wait_for_unload`] for waiting on that.
+    fn unload(&mut self, name: &str) -> Result<(), FileError>;
+}
+
+impl CSFileImpExt for CSFileImp {
+    fn file_caps(&self) -> Vec<FileCapInfo> {
+        let _lock = RepositoryLock::new(&self.file_repository_1);
+        list_file_caps(&self.file_repository_1)
+    }
+
+    fn file_cap(&self, name: &str) -> Option<FileCapInfo> {
+        let _lock = RepositoryLock::new(&self.file_repository_1);
+        let file_cap = find_file_cap(&self.file_repository_1, name)?;
+        Some(FileCapInfo::from(unsafe { file_cap.as_ref() }))
+    }
+
+    fn unload(&mut self, name: &str) -> Result<(), FileError> {
+        // Only checked up front, the game looks the file cap up again. The
+        // repository isn't locked during the call so the game's unload can't
+        // end up waiting on a thread that needs one of the mutexes.
+        if self.file_cap(name).is_none() {
+            return Err(FileError::NotLoaded(name.to_string()));
+        }
+
+        let allocator = self
+            .get_runtime_class()
+            .allocator1
+            .ok_or(FileError::NoAllocator)?;
+        // Passed by reference and freed once the call returns.
+        let name =
+            OwnedFD4BasicHashString::new_in(name, unsafe { DLAllocatorRef::new(allocator) })?;
+
+        self.unload_file_cap_by_name(&name);
+        Ok(())
+    }
+}
+
+/// Holds all of the file repository's mutexes while the holders are read.
+/// Which mutex guards which holder hasn't been worked out, the mutexes might
+/// as well belong to the five file load queues, so all of them are held. No
+/// game code is called while holding them.
+struct RepositoryLock {
+    mutexes: [*mut CSFileRepositoryMutex; 5],
+}
+
+impl RepositoryLock {
+    fn new(repository: &CSFileRepository) -> Self {
+        let mutexes = repository.mutexes.each_ref().map(|m| m.as_ptr());
+        // Always locked in the same order.
+        for mutex in mutexes {
+            unsafe { (*mutex).mutex.lock() };
+        }
+
+        Self { mutexes }
+    }
+}
+
+impl Drop for RepositoryLock {
+    fn drop(&mut self) {
+        for mutex in self.mutexes.into_iter().rev() {
+            unsafe { (*mutex).mutex.unlock() };
+        }
+    }
+}
+
+/// Lists the file caps of both holders. The repository must be locked.
+fn list_file_caps(repository: &CSFileRepository) -> Vec<FileCapInfo> {
+    repository
+        .res_rep
+        .res_cap_holder
+        .entries()
+        .chain(repository.holder2.entries())
+        .map(FileCapInfo::from)
+        .collect()
+}
+
+/// Looks up a file cap in both holders. The repository must be locked.
+fn find_file_cap(repository: &CSFileRepository, name: &str) -> Option<NonNull<UntypedFileCap>> {
+    repository
+        .res_rep
+        .res_cap_holder
+        .get(name)
+        .or_else(|| repository.holder2.get(name))
+        .map(NonNull::from)
+}
+
+/// Polls the file repository until `done` returns true. Do not call this from
+/// a game task as loading and unloading is progressed by the game's tasks.
+unsafe fn poll_file_imp(
+    timeout: Duration,
+    mut done: impl FnMut(Option<&CSFileImp>) -> bool,
+) -> bool {
+    let start = Instant::now();
+
+    loop {
+        let file_imp = get_instance::<CSFileImp>().ok().flatten();
+        if done(file_imp.as_deref()) {
+            return true;
+        }
+
+        if start.elapsed() >= timeout {
+            return false;
+        }
+
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/// Blocks until the file cap with the specified name has been loaded. Do not
+/// call this from a game task as loading is progressed by the game's tasks, use
+/// [`crate::executor::wait_until`] with [`CSFileImpExt::file_cap_state`] there
+/// instead.
+///
+/// # Safety
+/// Same as [`get_instance`].
+pub unsafe fn wait_for_file_cap(name: &str, timeout: Duration) -> Result<(), FileError> {
+    let loaded = poll_file_imp(timeout, |file_imp| {
+        file_imp.and_then(|f| f.file_cap_state(name)) == Some(FD4FileCapState::Ready)
+    });
+
+    if !loaded {
+        return Err(FileError::Timeout(name.to_string()));
+    }
+
+    Ok(())
+}
+
+/// Blocks until the file cap with the specified name has left the file
+/// repository, ex: after [`CSFileImpExt::unload`]. Do not call this from a
+/// game task as unloading is progressed by the game's tasks.
+///
+/// # Safety
+/// Same as [`get_instance`].
+pub unsafe fn wait_for_unload(name: &str, timeout: Duration) -> Result<(), FileError> {
+    let unloaded = poll_file_imp(timeout, |file_imp| {
+        file_imp.is_some_and(|f| f.file_cap(name).is_none())
+    });
+
+    if !unloaded {
+        return Err(FileError::UnloadTimeout(name.to_string()));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::mem::size_of;
+    use std::ptr::NonNull;
+
+    use eldenring::cs::{CSFileRepository, UntypedFileCap};
+    use eldenring::dlkr::mock::MockAllocator;
+    use eldenring::fd4::{FD4BasicHashString, FD4FileCapState, FD4ResCap, FD4ResCapHolder};
+
+    use super::{find_file_cap, list_file_caps};
+
+    const RES_CAP_SIZE: usize = size_of::<FD4ResCap<UntypedFileCap>>();
+
+    /// Mimics the layout of [`UntypedFileCap`].
+    #[repr(C)]
+    struct TestFileCap {
+        vftable: usize,
+        name: FD4BasicHashString,
+        owning_repository: usize,
+        next_item: Option<NonNull<TestFileCap>>,
+        reference_count: u32,
+        unk5c: [u8; RES_CAP_SIZE - 0x5c],
+        load_process: usize,
+        load_task: usize,
+        load_state: FD4FileCapState,
+        unk89: [u8; 7],
+    }
+
+    /// Mimics the layout of [`FD4ResCapHolder`].
+    #[repr(C)]
+    struct TestHolder {
+        vftable: usize,
+        allocator: usize,
+        owning_repository: usize,
+        unk18: u32,
+        bucket_count: u32,
+        buckets: NonNull<Option<NonNull<TestFileCap>>>,
+    }
+
+    /// Mimics the layout of [`CSFileRepository`].
+    #[repr(C)]
+    struct TestRepository {
+        res_cap: [u8; RES_CAP_SIZE],
+        res_cap_holder: TestHolder,
+        holder2: TestHolder,
+        rest: [u8; size_of::<CSFileRepository>() - RES_CAP_SIZE - 2 * size_of::<TestHolder>()],
+    }
+
+    /// Links the file caps into buckets the same way the game does, new
+    /// entries become the head of their bucket.
+    fn build_holder(
+        file_caps: &[(&str, FD4FileCapState)],
+        allocator: &mut MockAllocator,
+    ) -> TestHolder {
+        const BUCKET_COUNT: u32 = 7;
+        let buckets: &mut [Option<NonNull<TestFileCap>>] =
+            Box::leak(vec![None; BUCKET_COUNT as usize].into_boxed_slice());
+
+        for (name, load_state) in file_caps {
+            let bucket = (FD4BasicHashString::hash_str(name) % BUCKET_COUNT) as usize;
+            let units = name.encode_utf16().collect::<Vec<_>>();
+            let name = FD4BasicHashString::with_vftable_in(&units, allocator.handle(), 0);
+
+            let file_cap = Box::leak(Box::new(TestFileCap {
+                vftable: 0,
+                name,
+                owning_repository: 0,
+                next_item: buckets[bucket],
+                reference_count: 1,
+                unk5c: [0; RES_CAP_SIZE - 0x5c],
+                load_process: 0,
+                load_task: 0,
+                load_state: *load_state,
+                unk89: [0; 7],
+            }));
+            buckets[bucket] = Some(NonNull::from(file_cap));
+        }
+
+        TestHolder {
+            vftable: 0,
+            allocator: 0,
+            owning_repository: 0,
+            unk18: 0,
+            bucket_count: BUCKET_COUNT,
+            buckets: NonNull::from(&mut buckets[0]),
+        }
+    }
+
+    /// Builds a repository without mutexes, the lookups are tested without
+    /// the lock.
+    fn build_repository(primary: TestHolder, secondary: TestHolder) -> &'static CSFileRepository {
+        let repository = Box::leak(Box::new(TestRepository {
+            res_cap: [0; RES_CAP_SIZE],
+            res_cap_holder: primary,
+            holder2: secondary,
+            rest: [0; size_of::<CSFileRepository>() - RES_CAP_SIZE - 2 * size_of::<TestHolder>()],
+        }));
+
+        unsafe { &*(repository as *mut TestRepository as *const CSFileRepository) }
+    }
+
+    #[test]
+    fn proper_sizes() {
+        assert_eq!(size_of::<TestFileCap>(), size_of::<UntypedFileCap>());
+        assert_eq!(
+            size_of::<TestHolder>(),
+            size_of::<FD4ResCapHolder<UntypedFileCap>>()
+        );
+        assert_eq!(size_of::<TestRepository>(), size_of::<CSFileRepository>());
+    }
+
+    #[test]
+    fn lists_file_caps_from_both_holders() {
+        let mut allocator = MockAllocator::new();
+        let repository = build_repository(
+            build_holder(
+                &[
+                    ("data0:/regulation.bin", FD4FileCapState::Ready),
+                    ("data0:/map/m60/m60_42_36_00.msb", FD4FileCapState::Queued),
+                ],
+                &mut allocator,
+            ),
+            build_holder(
+                &[("data0:/menu/hi/01_common.tpf", FD4FileCapState::Processing)],
+                &mut allocator,
+            ),
+        );
+
+        let mut file_caps = list_file_caps(repository)
+            .into_iter()
+            .map(|f| (f.name, f.state, f.reference_count))
+            .collect::<Vec<_>>();
+        file_caps.sort_by(|a, b| a.0.cmp(&b.0));
+
+        assert_eq!(
+            file_caps,
+            [
+                (
+                    "data0:/map/m60/m60_42_36_00.msb".to_string(),
+                    FD4FileCapState::Queued,
+                    1
+                ),
+                (
+                    "data0:/menu/hi/01_common.tpf".to_string(),
+                    FD4FileCapState::Processing,
+                    1
+                ),
+                (
+                    "data0:/regulation.bin".to_string(),
+                    FD4FileCapState::Ready,
+                    1
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_file_caps_in_both_holders() {
+        let mut allocator = MockAllocator::new();
+        let repository = build_repository(
+            build_holder(
+                &[("data0:/regulation.bin", FD4FileCapState::Ready)],
+                &mut allocator,
+            ),
+            build_holder(
+                &[("data0:/menu/hi/01_common.tpf", FD4FileCapState::Queued)],
+                &mut allocator,
+            ),
+        );
+
+        let state = |name| {
+            find_file_cap(repository, name).map(|f| unsafe { f.as_ref() }.file_cap.load_state)
+        };
+        assert_eq!(state("DATA0:/REGULATION.BIN"), Some(FD4FileCapState::Ready));
+        assert_eq!(
+            state("data0:/menu/hi/01_common.tpf"),
+            Some(FD4FileCapState::Queued)
+        );
+        assert_eq!(state("data0:/missing.bin"), None);
+    }
+}
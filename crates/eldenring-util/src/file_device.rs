@@ -0,0 +1,1074 @@
+//! Serving files to the game from rust through a custom file device.
+//!
+//! A [VirtualFileDevice] answers the game's file requests for paths below its prefix using a
+//! [FileSource]. Paths the source does not know about are answered with a null operator.
+//!
+//! Registering devices with [DLFileDeviceManager] isn't exposed yet. How the manager handles a
+//! null operator, and whether it destroys or enumerates its devices, hasn't been checked against
+//! the game, so a registered device could stop the game's own devices from being reached.
+//!
+//! ```ignore
+//! let device = VirtualFileDevice::new("data0:/", DirectorySource::new("mods/my-mod"));
+//!
+//! // Paths starting with "mymod:" resolve to "data0:/mods/mymod".
+//! file_device_manager.map_virtual_root("mymod:", "data0:/mods/mymod")?;
+//! ```
+use std::alloc::{GlobalAlloc, Layout};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Cursor, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+use std::ptr::NonNull;
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use eldenring::dlio::{
+    AdapterFileOperator, AdapterFileWrite, DLFileDeviceBase, DLFileDeviceManager, DLFileDeviceVmt,
+    DLFileOperatorBase, DLFileOperatorContainer,
+};
+use eldenring::dlkr::{DLAllocatorBase, DLAllocatorRef, DLPlainLightMutex};
+use eldenring::dltx::DLString;
+use thiserror::Error;
+use vtable_rs::VPtr;
+use zip::ZipArchive;
+
+#[derive(Error, Debug)]
+pub enum FileDeviceError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Zip error: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("Virtual root list has no allocator.")]
+    NoAllocator,
+}
+
+/// Stream handed to the game for a single opened file.
+pub trait FileStream: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> FileStream for T {}
+
+/// File opened by a [FileSource].
+pub struct SourceFile {
+    pub stream: Box<dyn FileStream>,
+    /// Reported to the game as the last access and modify time.
+    pub modified: Option<SystemTime>,
+}
+
+impl SourceFile {
+    pub fn new(stream: impl FileStream + 'static) -> Self {
+        Self {
+            stream: Box::new(stream),
+            modified: None,
+        }
+    }
+}
+
+/// Stream handed to the game for a file it's allowed to write to.
+pub trait WritableFileStream: FileStream + AdapterFileWrite {}
+
+impl<T: FileStream + AdapterFileWrite> WritableFileStream for T {}
+
+/// File opened with write access by a [FileSource].
+pub struct WritableSourceFile {
+    pub stream: Box<dyn WritableFileStream>,
+    /// Reported to the game as the last access and modify time.
+    pub modified: Option<SystemTime>,
+}
+
+impl WritableSourceFile {
+    pub fn new(stream: impl WritableFileStream + 'static) -> Self {
+        Self {
+            stream: Box::new(stream),
+            modified: None,
+        }
+    }
+}
+
+/// Provides the files served by a [VirtualFileDevice].
+pub trait FileSource: Send + Sync + 'static {
+    /// Opens the file at `path`, relative to the prefix of the device. Paths are lowercase and
+    /// use forward slashes. Returning None leaves the request to the game's other devices.
+    fn open(&self, path: &str) -> Option<SourceFile>;
+
+    /// Opens the file at `path` with write access, takes priority over [FileSource::open].
+    /// The file might not exist yet, in which case the first write creates it and
+    /// opening it for reading fails with [eldenring::dlio::DLIOResult::NotFound].
+    fn open_writable(&self, _path: &str) -> Option<WritableSourceFile> {
+        None
+    }
+}
+
+/// Serves files from a directory on disk.
+pub struct DirectorySource {
+    root: PathBuf,
+    writable: bool,
+}
+
+impl DirectorySource {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            writable: false,
+        }
+    }
+
+    /// Lets the game write to, create, rename and delete files below the root. Pointing the
+    /// source at a directory other than the game's own redirects the game's writes there.
+    pub fn writable(mut self) -> Self {
+        self.writable = true;
+        self
+    }
+
+    /// Resolves a path to a location below the root, rejecting paths that escape it.
+    fn resolve(&self, path: &str) -> Option<PathBuf> {
+        let relative = Path::new(path);
+        relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+            .then(|| self.root.join(relative))
+    }
+}
+
+impl FileSource for DirectorySource {
+    fn open(&self, path: &str) -> Option<SourceFile> {
+        let file = File::open(self.resolve(path)?).ok()?;
+        let metadata = file.metadata().ok()?;
+        if !metadata.is_file() {
+            return None;
+        }
+
+        Some(SourceFile {
+            modified: metadata.modified().ok(),
+            stream: Box::new(file),
+        })
+    }
+
+    fn open_writable(&self, path: &str) -> Option<WritableSourceFile> {
+        if !self.writable {
+            return None;
+        }
+
+        let path = self.resolve(path)?;
+        if path.is_dir() {
+            return None;
+        }
+
+        Some(WritableSourceFile {
+            modified: fs::metadata(&path).and_then(|m| m.modified()).ok(),
+            stream: Box::new(HostFile::new(path)),
+        })
+    }
+}
+
+/// File on disk that is only created once the game writes to it.
+///
+/// The OS handle is released when the game closes the file so it can be renamed or deleted
+/// while the operator is still alive.
+pub struct HostFile {
+    path: PathBuf,
+    file: Option<File>,
+    position: u64,
+}
+
+impl HostFile {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            file: None,
+            position: 0,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Opens the file if needed, returns None if it doesn't exist and `create` is false.
+    fn handle(&mut self, create: bool) -> std::io::Result<Option<&mut File>> {
+        if self.file.is_none() {
+            if create {
+                if let Some(parent) = self.path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+            }
+
+            let file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(create)
+                .truncate(false)
+                .open(&self.path);
+            let mut file = match file {
+                Ok(file) => file,
+                Err(e) if e.kind() == ErrorKind::NotFound && !create => return Ok(None),
+                Err(e) => return Err(e),
+            };
+
+            file.seek(SeekFrom::Start(self.position))?;
+            self.file = Some(file);
+        }
+
+        Ok(self.file.as_mut())
+    }
+}
+
+impl Read for HostFile {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let Some(file) = self.handle(false)? else {
+            return Ok(0);
+        };
+
+        let read = file.read(buf)?;
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl Write for HostFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let file = self.handle(true)?.unwrap();
+        let written = file.write(buf)?;
+        self.position += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self.file.as_mut() {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Seek for HostFile {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        if let Some(file) = self.handle(false)? {
+            self.position = file.seek(pos)?;
+            return Ok(self.position);
+        }
+
+        // Missing files behave like empty ones until written to.
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+            SeekFrom::End(offset) => u64::try_from(offset).ok(),
+        };
+
+        self.position = position.ok_or(ErrorKind::InvalidInput)?;
+        Ok(self.position)
+    }
+}
+
+impl AdapterFileWrite for HostFile {
+    fn set_len(&mut self, length: u64) -> std::io::Result<()> {
+        self.handle(true)?.unwrap().set_len(length)
+    }
+
+    fn close(&mut self) -> std::io::Result<()> {
+        self.flush()?;
+        self.file = None;
+        Ok(())
+    }
+
+    fn exists(&self) -> bool {
+        self.path.is_file()
+    }
+
+    fn delete(&mut self) -> std::io::Result<()> {
+        self.file = None;
+        match self.path.is_dir() {
+            true => fs::remove_dir(&self.path),
+            false => fs::remove_file(&self.path),
+        }
+    }
+
+    /// Renames the file within its directory, only the file name of `path` is used.
+    fn rename(&mut self, path: &str) -> std::io::Result<()> {
+        let name = path
+            .rsplit(['/', '\\'])
+            .next()
+            .filter(|name| !matches!(*name, "" | "." | ".."))
+            .ok_or(ErrorKind::InvalidInput)?;
+        let target = self.path.with_file_name(name);
+
+        self.close()?;
+        fs::rename(&self.path, &target)?;
+        self.path = target;
+        Ok(())
+    }
+
+    fn create_directory(&mut self) -> std::io::Result<()> {
+        fs::create_dir_all(&self.path)
+    }
+}
+
+/// Serves files from in-memory buffers that can be swapped out at runtime.
+#[derive(Default)]
+pub struct MemorySource {
+    files: RwLock<HashMap<String, Arc<[u8]>>>,
+}
+
+impl MemorySource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds or replaces the file at `path`. Operators that are already open keep serving the
+    /// old contents.
+    pub fn insert(&self, path: &str, contents: impl Into<Arc<[u8]>>) {
+        self.files
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(normalize_path(path), contents.into());
+    }
+
+    pub fn remove(&self, path: &str) -> Option<Arc<[u8]>> {
+        self.files
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&normalize_path(path))
+    }
+}
+
+impl FileSource for MemorySource {
+    fn open(&self, path: &str) -> Option<SourceFile> {
+        // Inserts and removes can't leave the map half-updated, so a poisoned lock is still
+        // safe to use. Panicking here would unwind into the game.
+        let files = self.files.read().unwrap_or_else(PoisonError::into_inner);
+        let contents = files.get(path)?.clone();
+        Some(SourceFile::new(Cursor::new(contents)))
+    }
+}
+
+/// Serves files from a zip archive. Entries are decompressed into memory when opened.
+pub struct ZipSource {
+    archive: Mutex<ZipArchive<Box<dyn FileStream>>>,
+    /// Maps normalized paths to the entry names inside the archive.
+    entries: HashMap<String, String>,
+}
+
+impl ZipSource {
+    const MAX_RESERVATION: usize = 0x400000;
+
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, FileDeviceError> {
+        Self::from_reader(File::open(path)?)
+    }
+
+    pub fn from_reader(reader: impl FileStream + 'static) -> Result<Self, FileDeviceError> {
+        let archive = ZipArchive::new(Box::new(reader) as Box<dyn FileStream>)?;
+        let entries = archive
+            .file_names()
+            .filter(|name| !name.ends_with('/'))
+            .map(|name| (normalize_path(name), name.to_string()))
+            .collect();
+
+        Ok(Self {
+            archive: Mutex::new(archive),
+            entries,
+        })
+    }
+}
+
+impl FileSource for ZipSource {
+    fn open(&self, path: &str) -> Option<SourceFile> {
+        let name = self.entries.get(path)?;
+        // A panic while reading an entry doesn't corrupt the archive, which only holds the
+        // reader and the central directory.
+        let mut archive = self.archive.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut entry = archive.by_name(name).ok()?;
+
+        // The size comes from the archive, don't trust it with more than a few MiB up front.
+        let mut contents = Vec::with_capacity((entry.size() as usize).min(Self::MAX_RESERVATION));
+        if let Err(e) = entry.read_to_end(&mut contents) {
+            tracing::error!("Could not decompress {name}: {e}");
+            return None;
+        }
+
+        Some(SourceFile::new(Cursor::new(contents)))
+    }
+}
+
+/// File device that hands out [AdapterFileOperator]s for the files of a [FileSource].
+///
+/// Mirrors the layout of [DLFileDeviceBase] so it can be placed in
+/// [DLFileDeviceManager::devices].
+#[repr(C)]
+pub struct VirtualFileDevice<S: FileSource> {
+    vftable: VPtr<dyn DLFileDeviceVmt, Self>,
+    unk8: bool,
+    ref_count: u32,
+    mutex: DLPlainLightMutex,
+    prefix: String,
+    source: S,
+}
+
+impl<S: FileSource> VirtualFileDevice<S> {
+    /// Creates a device serving the paths below `prefix` (ex: "data0:/") from `source`.
+    pub fn new(prefix: &str, source: S) -> Self {
+        Self::with_mutex(prefix, source, Default::default())
+    }
+
+    fn with_mutex(prefix: &str, source: S, mutex: DLPlainLightMutex) -> Self {
+        Self {
+            vftable: Default::default(),
+            unk8: false,
+            ref_count: 1,
+            mutex,
+            prefix: normalize_path(prefix),
+            source,
+        }
+    }
+
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    pub fn source(&self) -> &S {
+        &self.source
+    }
+
+    pub fn as_base(&self) -> &DLFileDeviceBase {
+        unsafe { &*(self as *const Self).cast::<DLFileDeviceBase>() }
+    }
+
+    /// Strips the device prefix from a path requested by the game.
+    fn relative_path(&self, path: &str) -> Option<String> {
+        normalize_path(path)
+            .strip_prefix(&self.prefix)
+            .map(|p| p.trim_start_matches('/').to_string())
+    }
+}
+
+// How the game treats these slots hasn't been checked against its own devices, which is why
+// registering devices isn't exposed. Paths the device doesn't serve get a null operator and the
+// device has no enumerator.
+impl<S: FileSource> DLFileDeviceVmt for VirtualFileDevice<S> {
+    /// The device is owned by Rust, the game doesn't get to destroy it.
+    extern "C" fn destructor(&mut self) {}
+
+    extern "C" fn get_file_operator(
+        &mut self,
+        path_dlstring: &DLString,
+        _path_u16: *const u16,
+        operator_container: &mut DLFileOperatorContainer,
+        allocator: &mut DLAllocatorBase,
+        _is_temp_file: bool,
+    ) -> *const DLFileOperatorBase {
+        let Some(path) = self.relative_path(&path_dlstring.to_string()) else {
+            return std::ptr::null();
+        };
+
+        if let Some(file) = self.source.open_writable(&path) {
+            tracing::debug!("Serving {path_dlstring} from {} (writable)", self.prefix);
+
+            let operator = AdapterFileOperator::new_writable(
+                allocator,
+                path_dlstring,
+                operator_container,
+                self.as_base(),
+                file.stream,
+            );
+            return allocate_operator(allocator, operator, file.modified);
+        }
+
+        if let Some(file) = self.source.open(&path) {
+            tracing::debug!("Serving {path_dlstring} from {}", self.prefix);
+
+            let operator = AdapterFileOperator::new(
+                allocator,
+                path_dlstring,
+                operator_container,
+                self.as_base(),
+                file.stream,
+            );
+            return allocate_operator(allocator, operator, file.modified);
+        }
+
+        std::ptr::null()
+    }
+
+    extern "C" fn file_enumerator(&self) -> *const u8 {
+        std::ptr::null()
+    }
+}
+
+pub trait DLFileDeviceManagerExt {
+    /// Adds a virtual root, making paths starting with `root` (ex: "mymod:") resolve to
+    /// `mount`.
+    fn map_virtual_root(&mut self, root: &str, mount: &str) -> Result<(), FileDeviceError>;
+}
+
+impl DLFileDeviceManagerExt for DLFileDeviceManager {
+    fn map_virtual_root(&mut self, root: &str, mount: &str) -> Result<(), FileDeviceError> {
+        let allocator = self
+            .virtual_roots
+            .allocator()
+            .ok_or(FileDeviceError::NoAllocator)?;
+
+        self.mutex.lock();
+        push_virtual_root(self, root, mount, allocator);
+        self.mutex.unlock();
+
+        Ok(())
+    }
+}
+
+/// Adds a virtual root to the end of the list. The manager's mutex must be held.
+fn push_virtual_root(
+    manager: &mut DLFileDeviceManager,
+    root: &str,
+    mount: &str,
+    allocator: DLAllocatorRef,
+) {
+    manager.virtual_roots.push([
+        DLString::new_in(root, allocator),
+        DLString::new_in(mount, allocator),
+    ]);
+}
+
+/// Moves the operator into memory from the game's allocator, the game releases the operator
+/// through the same allocator.
+fn allocate_operator<R: Read + Seek + 'static>(
+    allocator: &mut DLAllocatorBase,
+    mut operator: AdapterFileOperator<R>,
+    modified: Option<SystemTime>,
+) -> *const DLFileOperatorBase {
+    if let Some(modified) = modified {
+        operator = operator.with_modify_time(to_filetime(modified));
+    }
+
+    let allocator = unsafe { DLAllocatorRef::new(NonNull::from(allocator)) };
+    let allocation =
+        unsafe { allocator.alloc(Layout::for_value(&operator)) }.cast::<AdapterFileOperator<R>>();
+    if allocation.is_null() {
+        tracing::error!(
+            "Could not allocate file operator for {}",
+            operator.base.path
+        );
+        return std::ptr::null();
+    }
+
+    unsafe { allocation.write(operator) };
+    allocation as *const DLFileOperatorBase
+}
+
+fn normalize_path(path: &str) -> String {
+    path.replace('\\', "/")
+        .trim_start_matches('/')
+        .to_ascii_lowercase()
+}
+
+/// Converts to a FILETIME, 100ns intervals since 1601-01-01.
+fn to_filetime(time: SystemTime) -> u64 {
+    const UNIX_EPOCH_AS_FILETIME: u64 = 116_444_736_000_000_000;
+
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    UNIX_EPOCH_AS_FILETIME + (since_epoch.as_nanos() / 100) as u64
+}
+
+#[cfg(test)]
+mod test {
+    use std::alloc::{GlobalAlloc, Layout};
+    use std::fs;
+    use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+    use std::mem::{size_of, ManuallyDrop};
+    use std::path::PathBuf;
+    use std::ptr::NonNull;
+    use std::time::UNIX_EPOCH;
+
+    use eldenring::dlio::{
+        AdapterFileOperator, AdapterFileWrite, DLFileDeviceManager, DLFileDeviceVmt,
+        DLFileOperatorContainer, OpenFileMode,
+    };
+    use eldenring::dlkr::mock::MockAllocator;
+    use eldenring::dlkr::{DLAllocatorRef, DLPlainLightMutex};
+    use eldenring::dltx::DLString;
+    use zip::write::SimpleFileOptions;
+    use zip::{CompressionMethod, ZipWriter};
+
+    use super::{
+        allocate_operator, normalize_path, push_virtual_root, DLFileDeviceManagerExt,
+        DirectorySource, FileDeviceError, FileSource, FileStream, HostFile, MemorySource,
+        VirtualFileDevice, ZipSource,
+    };
+
+    const READ: OpenFileMode = OpenFileMode(0x1);
+
+    /// Mimics the layout of [`eldenring::Vector`].
+    #[repr(C)]
+    struct TestVector {
+        allocator: Option<DLAllocatorRef>,
+        begin: usize,
+        end: usize,
+        capacity: usize,
+    }
+
+    impl TestVector {
+        fn new(allocator: DLAllocatorRef) -> Self {
+            Self {
+                allocator: Some(allocator),
+                begin: 0,
+                end: 0,
+                capacity: 0,
+            }
+        }
+    }
+
+    /// Mimics the layout of [`DLFileDeviceManager`].
+    #[repr(C)]
+    struct TestDeviceManager {
+        devices: TestVector,
+        service_providers: TestVector,
+        msvc_file_device: usize,
+        virtual_roots: TestVector,
+        bnd3_files: TestVector,
+        bnd4_files: TestVector,
+        bnd3_service_provider: usize,
+        bnd4_service_provider: usize,
+        // Never dropped since tearing down the mutex calls into Windows.
+        mutex: ManuallyDrop<DLPlainLightMutex>,
+    }
+
+    impl TestDeviceManager {
+        fn new(allocator: DLAllocatorRef) -> Self {
+            Self {
+                devices: TestVector::new(allocator),
+                service_providers: TestVector::new(allocator),
+                msvc_file_device: 0,
+                virtual_roots: TestVector::new(allocator),
+                bnd3_files: TestVector::new(allocator),
+                bnd4_files: TestVector::new(allocator),
+                bnd3_service_provider: 0,
+                bnd4_service_provider: 0,
+                mutex: ManuallyDrop::new(uninitialized_mutex()),
+            }
+        }
+
+        fn as_manager(&mut self) -> &mut DLFileDeviceManager {
+            unsafe { &mut *(self as *mut Self).cast::<DLFileDeviceManager>() }
+        }
+    }
+
+    /// Mutex that was never initialized, locking it calls into Windows.
+    fn uninitialized_mutex() -> DLPlainLightMutex {
+        DLPlainLightMutex {
+            vftable: Default::default(),
+            critical_section: Default::default(),
+        }
+    }
+
+    /// Device that is never dropped since tearing down its mutex calls into Windows.
+    fn device<S: FileSource>(prefix: &str, source: S) -> ManuallyDrop<VirtualFileDevice<S>> {
+        ManuallyDrop::new(VirtualFileDevice::with_mutex(
+            prefix,
+            source,
+            uninitialized_mutex(),
+        ))
+    }
+
+    /// Mimics the layout of [`DLFileOperatorContainer`].
+    #[repr(C)]
+    #[derive(Default)]
+    struct TestOperatorContainer {
+        allocator: usize,
+        read_file_operator: usize,
+        write_file_operator: usize,
+        flags: u32,
+    }
+
+    impl TestOperatorContainer {
+        fn as_container(&mut self) -> &mut DLFileOperatorContainer {
+            unsafe { &mut *(self as *mut Self).cast::<DLFileOperatorContainer>() }
+        }
+    }
+
+    type SourceOperator = AdapterFileOperator<Box<dyn FileStream>>;
+
+    /// Opens and reads the operator through its vftable like the game does.
+    fn read_operator<R: Read + Seek>(operator: &mut AdapterFileOperator<R>) -> Vec<u8> {
+        assert!((operator.base.vftable.open)(operator, READ));
+
+        let length = (operator.base.vftable.file_size)(operator);
+        let mut output = vec![0; length];
+        let read = unsafe { (operator.base.vftable.read)(operator, output.as_mut_ptr(), length) };
+        output.truncate(read.try_into().unwrap());
+        output
+    }
+
+    /// Destroys the operator and releases its memory the way the game does.
+    fn release_operator<R: Read + Seek>(
+        mut operator: NonNull<AdapterFileOperator<R>>,
+        allocator: DLAllocatorRef,
+    ) {
+        unsafe {
+            let destructor = operator.as_ref().base.vftable.destructor;
+            destructor(operator.as_mut());
+            allocator.dealloc(
+                operator.as_ptr().cast(),
+                Layout::new::<AdapterFileOperator<R>>(),
+            );
+        }
+    }
+
+    /// Empty directory below the temp dir that is removed on drop.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("eldenring-util-{name}-{}", std::process::id()));
+            let _ = fs::remove_dir_all(&path);
+            fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn read(source: &impl FileSource, path: &str) -> Option<Vec<u8>> {
+        let mut file = source.open(path)?;
+        let mut contents = Vec::new();
+        file.stream.read_to_end(&mut contents).unwrap();
+        Some(contents)
+    }
+
+    #[test]
+    fn normalizes_paths() {
+        assert_eq!(
+            normalize_path("\\Parts\\AM_M_1000.partsbnd.dcx"),
+            "parts/am_m_1000.partsbnd.dcx"
+        );
+        assert_eq!(
+            normalize_path("data0:/Regulation.bin"),
+            "data0:/regulation.bin"
+        );
+        assert_eq!(normalize_path("//map/m10"), "map/m10");
+        assert_eq!(normalize_path(""), "");
+    }
+
+    #[test]
+    fn directory_source_rejects_traversal() {
+        let source = DirectorySource::new("mods/my-mod");
+
+        assert_eq!(
+            source.resolve("parts/am_m_1000.partsbnd.dcx"),
+            Some(PathBuf::from("mods/my-mod/parts/am_m_1000.partsbnd.dcx"))
+        );
+        assert_eq!(source.resolve("../regulation.bin"), None);
+        assert_eq!(source.resolve("parts/../../regulation.bin"), None);
+        assert_eq!(source.resolve("./regulation.bin"), None);
+        assert_eq!(source.resolve("/etc/passwd"), None);
+    }
+
+    #[test]
+    fn directory_source_opens_files() {
+        let root = TempDir::new("directory-source");
+        fs::create_dir_all(root.0.join("parts")).unwrap();
+        fs::write(root.0.join("parts/a.bin"), b"contents").unwrap();
+
+        let source = DirectorySource::new(&root.0);
+        assert_eq!(read(&source, "parts/a.bin").unwrap(), b"contents");
+        assert!(source.open("parts/a.bin").unwrap().modified.is_some());
+
+        assert!(source.open("parts").is_none());
+        assert!(source.open("parts/missing.bin").is_none());
+
+        // Only writable sources hand out writable files.
+        assert!(source.open_writable("parts/a.bin").is_none());
+        let source = source.writable();
+        assert!(source.open_writable("parts/a.bin").is_some());
+        assert!(source.open_writable("parts/new.bin").is_some());
+        assert!(source.open_writable("parts").is_none());
+        assert!(source.open_writable("../a.bin").is_none());
+    }
+
+    #[test]
+    fn memory_source_serves_inserted_files() {
+        let source = MemorySource::new();
+        source.insert("Parts\\A.bin", b"first".to_vec());
+        assert_eq!(read(&source, "parts/a.bin").unwrap(), b"first");
+        assert!(read(&source, "parts/b.bin").is_none());
+
+        // Open files keep serving the contents they were opened with.
+        let mut file = source.open("parts/a.bin").unwrap();
+        source.insert("parts/a.bin", b"second".to_vec());
+        let mut contents = Vec::new();
+        file.stream.read_to_end(&mut contents).unwrap();
+        assert_eq!(contents, b"first");
+        assert_eq!(read(&source, "parts/a.bin").unwrap(), b"second");
+
+        assert_eq!(&*source.remove("PARTS/A.BIN").unwrap(), b"second");
+        assert!(read(&source, "parts/a.bin").is_none());
+    }
+
+    #[test]
+    fn memory_source_survives_poisoning() {
+        let source = MemorySource::new();
+        source.insert("a.bin", b"a".to_vec());
+
+        std::thread::scope(|scope| {
+            let poisoned = scope.spawn(|| {
+                let _files = source.files.write().unwrap();
+                panic!("poisoning the lock");
+            });
+            assert!(poisoned.join().is_err());
+        });
+        assert!(source.files.is_poisoned());
+
+        assert_eq!(read(&source, "a.bin").unwrap(), b"a");
+        source.insert("b.bin", b"b".to_vec());
+        assert!(source.remove("a.bin").is_some());
+        assert_eq!(read(&source, "b.bin").unwrap(), b"b");
+    }
+
+    #[test]
+    fn host_file_is_created_on_write() {
+        let root = TempDir::new("host-file-create");
+        let path = root.0.join("save/ER0000.sl2");
+        let mut file = HostFile::new(&path);
+
+        // Reading and closing a missing file doesn't create it.
+        assert!(!file.exists());
+        let mut contents = Vec::new();
+        assert_eq!(file.read_to_end(&mut contents).unwrap(), 0);
+        file.flush().unwrap();
+        file.close().unwrap();
+        assert!(!path.exists());
+
+        file.write_all(b"save").unwrap();
+        assert!(file.exists());
+        file.close().unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"save");
+
+        // Reopens lazily and keeps the position across closes.
+        file.write_all(b"data").unwrap();
+        file.close().unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"savedata");
+        file.seek(SeekFrom::Start(4)).unwrap();
+        file.set_len(4).unwrap();
+        file.close().unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"save");
+    }
+
+    #[test]
+    fn host_file_seeks_while_missing() {
+        let root = TempDir::new("host-file-seek");
+        let path = root.0.join("ER0000.sl2");
+        let mut file = HostFile::new(&path);
+
+        assert_eq!(file.seek(SeekFrom::Start(4)).unwrap(), 4);
+        assert_eq!(file.seek(SeekFrom::Current(2)).unwrap(), 6);
+        assert_eq!(file.seek(SeekFrom::Current(-6)).unwrap(), 0);
+        assert!(file.seek(SeekFrom::Current(-1)).is_err());
+        assert_eq!(file.seek(SeekFrom::End(0)).unwrap(), 0);
+        assert!(file.seek(SeekFrom::End(-1)).is_err());
+        assert!(!path.exists());
+
+        // Writing past the start leaves a gap like a real file would.
+        file.seek(SeekFrom::Start(2)).unwrap();
+        file.write_all(b"ab").unwrap();
+        file.close().unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"\0\0ab");
+    }
+
+    #[test]
+    fn host_file_renames_within_directory() {
+        let root = TempDir::new("host-file-rename");
+        let mut file = HostFile::new(root.0.join("ER0000.sl2"));
+        file.write_all(b"save").unwrap();
+
+        file.rename("savedata:/ER0000.sl2.bak").unwrap();
+        assert_eq!(file.path(), root.0.join("ER0000.sl2.bak"));
+        assert!(!root.0.join("ER0000.sl2").exists());
+        assert_eq!(fs::read(root.0.join("ER0000.sl2.bak")).unwrap(), b"save");
+
+        // Only the file name is used, so paths can't move the file elsewhere.
+        file.rename("..\\..\\escaped.sl2").unwrap();
+        assert_eq!(file.path(), root.0.join("escaped.sl2"));
+        assert!(file.rename("..").is_err());
+        assert!(file.rename("savedata:/.").is_err());
+        assert!(file.rename("savedata:/").is_err());
+        assert_eq!(file.path(), root.0.join("escaped.sl2"));
+    }
+
+    #[test]
+    fn host_file_deletes() {
+        let root = TempDir::new("host-file-delete");
+        let path = root.0.join("ER0000.sl2");
+        let mut file = HostFile::new(&path);
+
+        assert!(file.delete().is_err());
+        file.write_all(b"save").unwrap();
+        file.delete().unwrap();
+        assert!(!file.exists());
+        assert!(!path.exists());
+
+        let mut directory = HostFile::new(root.0.join("backup"));
+        directory.create_directory().unwrap();
+        assert!(!directory.exists());
+        assert!(directory.path().is_dir());
+        directory.delete().unwrap();
+        assert!(!directory.path().exists());
+    }
+
+    fn build_zip(entries: &[(&str, &[u8], CompressionMethod)]) -> Cursor<Vec<u8>> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .add_directory("Parts/", SimpleFileOptions::default())
+            .unwrap();
+
+        for (name, contents, method) in entries {
+            let options = SimpleFileOptions::default().compression_method(*method);
+            writer.start_file(*name, options).unwrap();
+            writer.write_all(contents).unwrap();
+        }
+
+        let mut archive = writer.finish().unwrap();
+        archive.set_position(0);
+        archive
+    }
+
+    #[test]
+    fn zip_source_serves_entries() {
+        let archive = build_zip(&[
+            ("Parts/A.bin", b"stored", CompressionMethod::Stored),
+            ("Parts\\B.bin", &[7; 0x1000], CompressionMethod::Deflated),
+        ]);
+        let source = ZipSource::from_reader(archive).unwrap();
+
+        assert_eq!(read(&source, "parts/a.bin").unwrap(), b"stored");
+        assert_eq!(read(&source, "parts/b.bin").unwrap(), [7; 0x1000]);
+        assert!(read(&source, "parts").is_none());
+        assert!(read(&source, "parts/").is_none());
+        assert!(read(&source, "parts/c.bin").is_none());
+    }
+
+    #[test]
+    fn zip_source_rejects_garbage() {
+        assert!(ZipSource::from_reader(Cursor::new(b"not a zip".to_vec())).is_err());
+    }
+
+    #[test]
+    fn proper_sizes() {
+        assert_eq!(
+            size_of::<TestDeviceManager>(),
+            size_of::<DLFileDeviceManager>()
+        );
+        assert_eq!(
+            size_of::<TestOperatorContainer>(),
+            size_of::<DLFileOperatorContainer>()
+        );
+    }
+
+    #[test]
+    fn device_serves_prefixed_paths() {
+        let mut allocator = MockAllocator::new();
+        let handle = allocator.handle();
+        let mut container = TestOperatorContainer::default();
+
+        let source = MemorySource::new();
+        source.insert("menu/01_common.txt", b"hello".as_slice());
+        let mut device = device("data0:/", source);
+
+        let mut get_file_operator = |path: &str| {
+            let mut path = DLString::new_in(path, handle);
+            let operator = device.get_file_operator(
+                &path,
+                path.inner.as_ptr(),
+                container.as_container(),
+                unsafe { handle.as_ptr().as_mut() },
+                false,
+            );
+            unsafe { path.free() };
+            NonNull::new(operator.cast_mut().cast::<SourceOperator>())
+        };
+
+        assert!(get_file_operator("data0:/menu/missing.txt").is_none());
+        assert!(get_file_operator("data1:/menu/01_common.txt").is_none());
+
+        let mut operator = get_file_operator("DATA0:\\menu\\01_common.txt").unwrap();
+        assert_eq!(allocator.live_allocations(), 2);
+        assert_eq!(
+            unsafe { operator.as_ref() }.base.owning_file_device,
+            NonNull::from(device.as_base())
+        );
+        assert_eq!(read_operator(unsafe { operator.as_mut() }), b"hello");
+
+        release_operator(operator, handle);
+        assert_eq!(allocator.live_allocations(), 0);
+    }
+
+    #[test]
+    fn operators_live_in_the_game_allocator() {
+        let mut allocator = MockAllocator::new();
+        let handle = allocator.handle();
+        let mut container = TestOperatorContainer::default();
+        let device = device("data0:/", MemorySource::new());
+
+        let mut path = DLString::new_in("data0:/regulation.bin", handle);
+        let operator = AdapterFileOperator::new(
+            unsafe { handle.as_ptr().as_ref() },
+            &path,
+            container.as_container(),
+            device.as_base(),
+            Cursor::new(vec![1, 2, 3]),
+        );
+        unsafe { path.free() };
+
+        let operator = allocate_operator(
+            unsafe { handle.as_ptr().as_mut() },
+            operator,
+            Some(UNIX_EPOCH),
+        );
+        let mut operator = NonNull::new(
+            operator
+                .cast_mut()
+                .cast::<AdapterFileOperator<Cursor<Vec<u8>>>>(),
+        )
+        .unwrap();
+        // The operator itself and its copy of the path.
+        assert_eq!(allocator.live_allocations(), 2);
+        assert_eq!(read_operator(unsafe { operator.as_mut() }), [1, 2, 3]);
+
+        release_operator(operator, handle);
+        assert_eq!(allocator.live_allocations(), 0);
+    }
+
+    #[test]
+    fn virtual_roots_are_mapped() {
+        let mut allocator = MockAllocator::new();
+        let mut manager = TestDeviceManager::new(allocator.handle());
+        let manager = manager.as_manager();
+
+        push_virtual_root(manager, "mymod:", "data0:/mods/mymod", allocator.handle());
+
+        let roots = manager
+            .virtual_roots
+            .items()
+            .iter()
+            .map(|[root, mount]| (root.to_string(), mount.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            roots,
+            [("mymod:".to_string(), "data0:/mods/mymod".to_string())]
+        );
+    }
+
+    #[test]
+    fn virtual_roots_need_an_allocator() {
+        let mut allocator = MockAllocator::new();
+        let mut manager = TestDeviceManager::new(allocator.handle());
+        manager.virtual_roots.allocator = None;
+
+        assert!(matches!(
+            manager
+                .as_manager()
+                .map_virtual_root("mymod:", "data0:/mods/mymod"),
+            Err(FileDeviceError::NoAllocator)
+        ));
+    }
+}
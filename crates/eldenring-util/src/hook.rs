@@ -0,0 +1,434 @@
+//! Typed function hooks on the game's memory image.
+//!
+//! Hooks are always installed through a per-target chain. The actual detour
+//! jumps to a small thunk which in turn jumps to the most recently installed
+//! hook. Every hook's original points at the hook that was installed before
+//! it, the oldest one ends up calling the trampoline. This allows several hooks
+//! to be installed onto the same target without them stepping on each other.
+//!
+//! ```ignore
+//! type DrawLineFn = extern "C" fn(*const CSEzDraw, *const FVector4, *const FVector4);
+//!
+//! static DRAW_LINE_HOOK: OnceLock<Hook<DrawLineFn>> = OnceLock::new();
+//!
+//! extern "C" fn draw_line(ez_draw: *const CSEzDraw, from: *const FVector4, to: *const FVector4) {
+//!     DRAW_LINE_HOOK.get().unwrap().original()(ez_draw, from, to)
+//! }
+//!
+//! let hook = unsafe { Hook::new(HookTarget::Va(draw_line_va), draw_line as DrawLineFn) }?;
+//! DRAW_LINE_HOOK.get_or_init(|| hook).enable();
+//! ```
+//!
+//! Installing a hook leaves arxan's code restoration routines alone, so hooks
+//! on code guarded by them may get reverted. Finding the routines that guard a
+//! specific function relies on patterns that haven't been verified against a
+//! game build. Mods hooking guarded code have to neuter the routines
+//! themselves, ex: through [`crate::arxan::disable_code_restoration`].
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::marker::PhantomData;
+use std::mem::ManuallyDrop;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+
+use pelite::pattern::Atom;
+use pelite::pe64::{Pe, Rva};
+use retour::{Function, RawDetour};
+use thiserror::Error;
+use windows::Win32::System::Memory::{
+    VirtualAlloc, VirtualProtect, MEM_COMMIT, MEM_RESERVE, PAGE_EXECUTE_READWRITE,
+    PAGE_PROTECTION_FLAGS,
+};
+
+use crate::program::Program;
+
+#[derive(Error, Debug)]
+pub enum HookError {
+    #[error("Could not find the pattern for the hook target.")]
+    PatternNotFound,
+    #[error("Could not translate RVA {0:#x} to VA.")]
+    InvalidRva(Rva),
+    #[error("Could not change memory protection. {0}")]
+    Protection(windows::core::Error),
+    #[error("Could not allocate hook thunk.")]
+    ThunkAllocation,
+    #[error("Could not find RTTI class {0}.")]
+    ClassNotFound(String),
+    #[error("Could not determine the vftable of the object.")]
+    InvalidVftable,
+    #[error("Vftable index {index} is out of range for a vftable of {len} entries.")]
+    IndexOutOfRange { index: u32, len: usize },
+    #[error("Could not create detour. {0}")]
+    Detour(#[from] retour::Error),
+}
+
+/// Describes the function that should be hooked.
+#[derive(Clone, Copy)]
+pub enum HookTarget {
+    /// Function at an RVA, usually one of the mapped RVAs.
+    Rva(Rva),
+    /// Function at an absolute address.
+    Va(u64),
+    /// Function at the specified capture of a pattern. Index 0 is the start
+    /// of the match.
+    Pattern {
+        pattern: &'static [Atom],
+        capture: usize,
+    },
+}
+
+impl HookTarget {
+    fn resolve(&self, program: &Program) -> Result<u64, HookError> {
+        match self {
+            HookTarget::Rva(rva) => program
+                .rva_to_va(*rva)
+                .map_err(|_| HookError::InvalidRva(*rva)),
+            HookTarget::Va(va) => Ok(*va),
+            HookTarget::Pattern { pattern, capture } => {
+                let mut captures = vec![Rva::default(); capture + 1];
+                if !program.scanner().finds_code(pattern, &mut captures) {
+                    return Err(HookError::PatternNotFound);
+                }
+
+                program
+                    .rva_to_va(captures[*capture])
+                    .map_err(|_| HookError::InvalidRva(captures[*capture]))
+            }
+        }
+    }
+}
+
+/// A single hook in a chain.
+struct HookEntry {
+    detour: usize,
+    /// Function the hook should call to continue the chain.
+    original: AtomicUsize,
+    enabled: AtomicBool,
+}
+
+/// All hooks installed onto a single target.
+struct HookChain {
+    /// Never dropped as that would free the trampoline while other threads
+    /// might still be executing it.
+    detour: ManuallyDrop<RawDetour>,
+    thunk: &'static Thunk,
+    /// Installed hooks, oldest first.
+    entries: Vec<Arc<HookEntry>>,
+}
+
+impl HookChain {
+    /// Rewires the originals of all entries and points the thunk at the most
+    /// recently installed enabled hook.
+    fn relink(&self) {
+        let trampoline = self.detour.trampoline() as *const () as usize;
+        let next = link(trampoline, &self.entries);
+        self.thunk.target.store(next as u64, Ordering::Release);
+    }
+}
+
+/// Points the original of every entry, oldest first, at the enabled entry
+/// installed before it or the trampoline and returns the function the chain
+/// should be entered through. Disabled hooks keep pointing at the next enabled
+/// hook so calls still going through them end up in the right place.
+fn link(trampoline: usize, entries: &[Arc<HookEntry>]) -> usize {
+    let mut next = trampoline;
+    for entry in entries.iter() {
+        entry.original.store(next, Ordering::Release);
+        if entry.enabled.load(Ordering::Acquire) {
+            next = entry.detour;
+        }
+    }
+
+    next
+}
+
+/// Absolute jump to a swappable target.
+#[repr(C, align(16))]
+struct Thunk {
+    // JMP [RIP + 2]
+    jmp: [u8; 8],
+    target: AtomicU64,
+}
+
+impl Thunk {
+    /// Allocates a thunk in executable memory. Thunks are never freed since a
+    /// thread might still be executing it after the hook has been removed.
+    fn allocate(target: usize) -> Result<&'static Self, HookError> {
+        let thunk = unsafe {
+            VirtualAlloc(
+                None,
+                size_of::<Thunk>(),
+                MEM_COMMIT | MEM_RESERVE,
+                PAGE_EXECUTE_READWRITE,
+            )
+        } as *mut Thunk;
+
+        if thunk.is_null() {
+            return Err(HookError::ThunkAllocation);
+        }
+
+        unsafe {
+            thunk.write(Thunk {
+                jmp: [0xff, 0x25, 0x02, 0x00, 0x00, 0x00, 0xcc, 0xcc],
+                target: AtomicU64::new(target as u64),
+            });
+
+            Ok(&*thunk)
+        }
+    }
+}
+
+static HOOK_CHAINS: LazyLock<Mutex<HashMap<u64, HookChain>>> = LazyLock::new(Default::default);
+
+/// A typed hook on a function in the game's memory image. The hook is removed
+/// from its chain when dropped. Once the last hook on a target is dropped the
+/// detour is disabled, but its trampoline is leaked since another thread might
+/// still be executing it through [`Hook::original`].
+pub struct Hook<F: Function> {
+    target: u64,
+    entry: Arc<HookEntry>,
+    _marker: PhantomData<F>,
+}
+
+impl<F: Function> Hook<F> {
+    /// Installs a hook onto the target. The hook starts out disabled, call
+    /// [`Hook::enable`] once the hook is stored somewhere the detour can
+    /// reach it.
+    ///
+    /// # Safety
+    /// Caller must ensure that:
+    ///  - The target is a function with the same signature and calling convention as F.
+    ///  - The detour does not unwind.
+    pub unsafe fn new(target: HookTarget, detour: F) -> Result<Self, HookError> {
+        let program = Program::current();
+        let target = target.resolve(&program)?;
+
+        let entry = Arc::new(HookEntry {
+            detour: detour.to_ptr() as usize,
+            original: AtomicUsize::new(0),
+            enabled: AtomicBool::new(false),
+        });
+
+        let mut chains = HOOK_CHAINS.lock().unwrap();
+        let chain = match chains.entry(target) {
+            std::collections::hash_map::Entry::Occupied(e) => e.into_mut(),
+            std::collections::hash_map::Entry::Vacant(e) => {
+                // The thunk starts out pointing at the target itself, it
+                // gets pointed at the trampoline by the relink below before
+                // the detour gets enabled.
+                let thunk = Thunk::allocate(target as usize)?;
+                let detour =
+                    RawDetour::new(target as *const (), thunk as *const Thunk as *const ())?;
+
+                let chain = HookChain {
+                    detour: ManuallyDrop::new(detour),
+                    thunk,
+                    entries: Vec::new(),
+                };
+                chain.relink();
+                chain.detour.enable()?;
+                e.insert(chain)
+            }
+        };
+
+        chain.entries.push(entry.clone());
+        chain.relink();
+
+        tracing::debug!("Installed hook on {target:#x}");
+
+        Ok(Self {
+            target,
+            entry,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Makes calls to the target go through the detour.
+    pub fn enable(&self) {
+        self.set_enabled(true);
+    }
+
+    /// Makes calls to the target skip the detour.
+    pub fn disable(&self) {
+        self.set_enabled(false);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.entry.enabled.load(Ordering::Acquire)
+    }
+
+    /// Returns the function to call in order to continue the chain. This is
+    /// either the next hook or the trampoline to the original function.
+    pub fn original(&self) -> F {
+        unsafe { F::from_ptr(self.entry.original.load(Ordering::Acquire) as *const ()) }
+    }
+
+    fn set_enabled(&self, enabled: bool) {
+        let chains = HOOK_CHAINS.lock().unwrap();
+        self.entry.enabled.store(enabled, Ordering::Release);
+        if let Some(chain) = chains.get(&self.target) {
+            chain.relink();
+        }
+    }
+}
+
+impl<F: Function> Drop for Hook<F> {
+    fn drop(&mut self) {
+        let mut chains = HOOK_CHAINS.lock().unwrap();
+        let Some(chain) = chains.get_mut(&self.target) else {
+            return;
+        };
+
+        chain.entries.retain(|e| !Arc::ptr_eq(e, &self.entry));
+        chain.relink();
+
+        if chain.entries.is_empty() {
+            if let Err(e) = unsafe { chain.detour.disable() } {
+                tracing::debug!("Could not disable detour on {:#x}: {e}", self.target);
+            }
+            chains.remove(&self.target);
+        }
+
+        tracing::debug!("Removed hook from {:#x}", self.target);
+    }
+}
+
+/// Temporarily makes a region writable for the duration of the closure.
+///
+/// # Safety
+/// Caller must ensure that the region is mapped.
+pub(crate) unsafe fn with_write_access<R>(
+    address: *const c_void,
+    size: usize,
+    f: impl FnOnce() -> R,
+) -> Result<R, HookError> {
+    let mut old_protection = PAGE_PROTECTION_FLAGS::default();
+    VirtualProtect(address, size, PAGE_EXECUTE_READWRITE, &mut old_protection)
+        .map_err(HookError::Protection)?;
+
+    let result = f();
+
+    VirtualProtect(address, size, old_protection, &mut old_protection)
+        .map_err(HookError::Protection)?;
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use super::{link, HookEntry};
+
+    type TargetFn = extern "C" fn(u32) -> u32;
+
+    thread_local! {
+        /// Chain of the current test, every test runs on its own thread.
+        static ENTRIES: RefCell<Vec<Arc<HookEntry>>> = const { RefCell::new(Vec::new()) };
+    }
+
+    /// Stands in for the trampoline to the original function.
+    extern "C" fn trampoline(value: u32) -> u32 {
+        value
+    }
+
+    fn original_of(detour: TargetFn) -> TargetFn {
+        let original = ENTRIES.with_borrow(|entries| {
+            entries
+                .iter()
+                .find(|e| e.detour == detour as usize)
+                .expect("detour not in chain")
+                .original
+                .load(Ordering::Acquire)
+        });
+        unsafe { std::mem::transmute::<usize, TargetFn>(original) }
+    }
+
+    // Every hook appends its digit so the result spells out the call order.
+    extern "C" fn hook_1(value: u32) -> u32 {
+        original_of(hook_1)(value * 10 + 1)
+    }
+
+    extern "C" fn hook_2(value: u32) -> u32 {
+        original_of(hook_2)(value * 10 + 2)
+    }
+
+    extern "C" fn hook_3(value: u32) -> u32 {
+        original_of(hook_3)(value * 10 + 3)
+    }
+
+    fn install(detour: TargetFn) -> Arc<HookEntry> {
+        let entry = Arc::new(HookEntry {
+            detour: detour as usize,
+            original: AtomicUsize::new(0),
+            enabled: AtomicBool::new(true),
+        });
+        ENTRIES.with_borrow_mut(|entries| entries.push(entry.clone()));
+        entry
+    }
+
+    fn uninstall(entry: &Arc<HookEntry>) {
+        ENTRIES.with_borrow_mut(|entries| entries.retain(|e| !Arc::ptr_eq(e, entry)));
+    }
+
+    /// Relinks the chain and calls into it the way the thunk would.
+    fn call() -> u32 {
+        let entry = ENTRIES.with_borrow(|entries| link(trampoline as TargetFn as usize, entries));
+        let entry = unsafe { std::mem::transmute::<usize, TargetFn>(entry) };
+        entry(0)
+    }
+
+    #[test]
+    fn empty_chain_calls_the_original() {
+        assert_eq!(call(), 0);
+    }
+
+    #[test]
+    fn newest_hook_runs_first() {
+        install(hook_1);
+        assert_eq!(call(), 1);
+
+        install(hook_2);
+        install(hook_3);
+        assert_eq!(call(), 321);
+    }
+
+    #[test]
+    fn disabled_hooks_are_skipped() {
+        let first = install(hook_1);
+        let second = install(hook_2);
+        let third = install(hook_3);
+
+        second.enabled.store(false, Ordering::Release);
+        assert_eq!(call(), 31);
+
+        // Calls already inside the disabled hook continue with the next one.
+        assert_eq!(original_of(hook_2)(2), 21);
+
+        third.enabled.store(false, Ordering::Release);
+        first.enabled.store(false, Ordering::Release);
+        assert_eq!(call(), 0);
+
+        second.enabled.store(true, Ordering::Release);
+        assert_eq!(call(), 2);
+    }
+
+    #[test]
+    fn unhooking_keeps_the_remaining_order() {
+        let first = install(hook_1);
+        let second = install(hook_2);
+        install(hook_3);
+
+        uninstall(&second);
+        assert_eq!(call(), 31);
+
+        uninstall(&first);
+        assert_eq!(call(), 3);
+
+        // Hooks installed after an unhook still go to the front.
+        install(hook_1);
+        assert_eq!(call(), 13);
+    }
+}
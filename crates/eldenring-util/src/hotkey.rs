@@ -0,0 +1,658 @@
+//! Edge-triggered hotkeys bound to named actions.
+//!
+//! The manager is meant to be updated once per frame from a game task, after
+//! which the transitions for that frame can be queried by action name.
+//!
+//! ```toml
+//! hold_threshold_ms = 500
+//! double_tap_window_ms = 300
+//!
+//! [bindings]
+//! toggle_hud = "F1"
+//! quick_warp = "Ctrl+Shift+F5"
+//! ```
+//!
+//! ```ignore
+//! let mut hotkeys = HotkeyManager::load("mod_hotkeys.toml")?;
+//! cs_task.run_recurring(
+//!     move |_: &FD4TaskData| {
+//!         hotkeys.update();
+//!
+//!         if hotkeys.pressed("toggle_hud") {
+//!             // ...
+//!         }
+//!     },
+//!     CSTaskGroupIndex::FrameBegin,
+//! );
+//! ```
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use eldenring::cs::CSWindowImp;
+use serde::Deserialize;
+use thiserror::Error;
+use windows::Win32::UI::Input::KeyboardAndMouse::{
+    GetAsyncKeyState, VK_BACK, VK_CONTROL, VK_DELETE, VK_DOWN, VK_END, VK_ESCAPE, VK_HOME,
+    VK_INSERT, VK_LBUTTON, VK_LEFT, VK_MBUTTON, VK_MENU, VK_NEXT, VK_PRIOR, VK_RBUTTON, VK_RETURN,
+    VK_RIGHT, VK_SHIFT, VK_SPACE, VK_TAB, VK_UP, VK_XBUTTON1, VK_XBUTTON2,
+};
+use windows::Win32::UI::WindowsAndMessaging::GetForegroundWindow;
+
+use crate::singleton::install_static_resolver;
+
+#[derive(Error, Debug)]
+pub enum HotkeyError {
+    #[error("Unknown key {0}.")]
+    UnknownKey(String),
+    #[error("Binding {0} does not contain a non-modifier key.")]
+    MissingKey(String),
+    #[error("Binding {0} contains more than one non-modifier key.")]
+    MultipleKeys(String),
+    #[error("Could not read hotkey config. {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not parse hotkey config. {0}")]
+    Config(#[from] toml::de::Error),
+}
+
+const MODIFIER_CTRL: u8 = 1 << 0;
+const MODIFIER_SHIFT: u8 = 1 << 1;
+const MODIFIER_ALT: u8 = 1 << 2;
+
+/// A key combined with the modifiers that need to be held along with it, ex:
+/// `Ctrl+Shift+F5`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyChord {
+    /// Virtual key code of the main key.
+    pub key: i32,
+    modifiers: u8,
+}
+
+impl KeyChord {
+    pub const fn new(key: i32) -> Self {
+        Self { key, modifiers: 0 }
+    }
+
+    pub const fn ctrl(mut self) -> Self {
+        self.modifiers |= MODIFIER_CTRL;
+        self
+    }
+
+    pub const fn shift(mut self) -> Self {
+        self.modifiers |= MODIFIER_SHIFT;
+        self
+    }
+
+    pub const fn alt(mut self) -> Self {
+        self.modifiers |= MODIFIER_ALT;
+        self
+    }
+
+    /// Whether or not the chord is currently held. The held modifiers must
+    /// match exactly so that `Ctrl+F5` doesn't trigger on `Ctrl+Shift+F5`.
+    fn is_down(&self, held_modifiers: u8) -> bool {
+        // Don't count the main key as a modifier if it is one.
+        let held_modifiers = held_modifiers & !modifier_for_key(self.key);
+
+        is_key_down(self.key) && held_modifiers == self.modifiers
+    }
+}
+
+impl FromStr for KeyChord {
+    type Err = HotkeyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut modifiers = 0;
+        let mut key = None;
+
+        for part in s.split('+').map(str::trim) {
+            match part.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => modifiers |= MODIFIER_CTRL,
+                "shift" => modifiers |= MODIFIER_SHIFT,
+                "alt" => modifiers |= MODIFIER_ALT,
+                name => {
+                    let virtual_key = virtual_key_by_name(name)
+                        .ok_or_else(|| HotkeyError::UnknownKey(part.to_string()))?;
+                    if key.replace(virtual_key).is_some() {
+                        return Err(HotkeyError::MultipleKeys(s.to_string()));
+                    }
+                }
+            }
+        }
+
+        let key = key.ok_or_else(|| HotkeyError::MissingKey(s.to_string()))?;
+        Ok(Self { key, modifiers })
+    }
+}
+
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers & MODIFIER_CTRL != 0 {
+            write!(f, "Ctrl+")?;
+        }
+        if self.modifiers & MODIFIER_SHIFT != 0 {
+            write!(f, "Shift+")?;
+        }
+        if self.modifiers & MODIFIER_ALT != 0 {
+            write!(f, "Alt+")?;
+        }
+
+        match virtual_key_name(self.key) {
+            Some(name) => write!(f, "{name}"),
+            None => write!(f, "{:#x}", self.key),
+        }
+    }
+}
+
+/// Hotkey config as read from a TOML file.
+#[derive(Debug, Clone, Deserialize)]
+pub struct HotkeyConfig {
+    /// How long a chord needs to be held before it counts as held.
+    #[serde(default = "default_hold_threshold_ms")]
+    pub hold_threshold_ms: u64,
+    /// Max time between two presses for them to count as a double tap.
+    #[serde(default = "default_double_tap_window_ms")]
+    pub double_tap_window_ms: u64,
+    /// Maps action names to chords, ex: `quick_warp = "Ctrl+Shift+F5"`.
+    #[serde(default)]
+    pub bindings: HashMap<String, String>,
+}
+
+fn default_hold_threshold_ms() -> u64 {
+    500
+}
+
+fn default_double_tap_window_ms() -> u64 {
+    300
+}
+
+impl Default for HotkeyConfig {
+    fn default() -> Self {
+        Self {
+            hold_threshold_ms: default_hold_threshold_ms(),
+            double_tap_window_ms: default_double_tap_window_ms(),
+            bindings: Default::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HotkeyEvent {
+    /// The chord went down this frame.
+    Pressed,
+    /// The chord went up this frame.
+    Released,
+    /// The chord has been down for longer than the hold threshold. Fires once
+    /// per press.
+    Held,
+    /// The chord went down this frame for the second time within the double
+    /// tap window.
+    DoubleTap,
+}
+
+#[derive(Default)]
+struct BindingState {
+    down: bool,
+    held: bool,
+    pressed_at: Option<Instant>,
+    last_press: Option<Instant>,
+}
+
+struct Binding {
+    action: String,
+    chord: KeyChord,
+    state: BindingState,
+}
+
+/// Tracks the transitions of a set of bound chords. Input is ignored while the
+/// game window isn't focused.
+pub struct HotkeyManager {
+    bindings: Vec<Binding>,
+    events: Vec<(usize, HotkeyEvent)>,
+    hold_threshold: Duration,
+    double_tap_window: Duration,
+}
+
+impl Default for HotkeyManager {
+    fn default() -> Self {
+        Self {
+            bindings: Vec::new(),
+            events: Vec::new(),
+            hold_threshold: Duration::from_millis(default_hold_threshold_ms()),
+            double_tap_window: Duration::from_millis(default_double_tap_window_ms()),
+        }
+    }
+}
+
+impl HotkeyManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_config(config: &HotkeyConfig) -> Result<Self, HotkeyError> {
+        let mut manager = Self {
+            hold_threshold: Duration::from_millis(config.hold_threshold_ms),
+            double_tap_window: Duration::from_millis(config.double_tap_window_ms),
+            ..Default::default()
+        };
+
+        for (action, chord) in config.bindings.iter() {
+            manager.bind(action, chord.parse()?);
+        }
+
+        Ok(manager)
+    }
+
+    pub fn from_toml(contents: &str) -> Result<Self, HotkeyError> {
+        Self::from_config(&toml::from_str(contents)?)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, HotkeyError> {
+        Self::from_toml(&std::fs::read_to_string(path)?)
+    }
+
+    /// Binds a chord to an action. An action can have several chords bound to
+    /// it.
+    pub fn bind(&mut self, action: &str, chord: KeyChord) {
+        self.bindings.push(Binding {
+            action: action.to_string(),
+            chord,
+            state: Default::default(),
+        });
+    }
+
+    /// Removes all chords bound to an action.
+    pub fn unbind(&mut self, action: &str) {
+        self.bindings.retain(|b| b.action != action);
+        self.events.clear();
+    }
+
+    /// Polls the keyboard and determines the transitions since the last
+    /// update. Call this once per frame.
+    pub fn update(&mut self) {
+        let focused = is_game_focused();
+        let held_modifiers = held_modifiers();
+
+        self.step(Instant::now(), |chord| {
+            focused && chord.is_down(held_modifiers)
+        });
+    }
+
+    /// Determines the transitions given which chords are down at `now`.
+    fn step(&mut self, now: Instant, is_down: impl Fn(&KeyChord) -> bool) {
+        self.events.clear();
+
+        for (index, binding) in self.bindings.iter_mut().enumerate() {
+            let state = &mut binding.state;
+            let down = is_down(&binding.chord);
+
+            match (state.down, down) {
+                (false, true) => {
+                    self.events.push((index, HotkeyEvent::Pressed));
+                    if state
+                        .last_press
+                        .is_some_and(|p| now.duration_since(p) <= self.double_tap_window)
+                    {
+                        self.events.push((index, HotkeyEvent::DoubleTap));
+                        // Don't let a third press count as another double tap.
+                        state.last_press = None;
+                    } else {
+                        state.last_press = Some(now);
+                    }
+
+                    state.pressed_at = Some(now);
+                    state.held = false;
+                }
+                (true, false) => {
+                    self.events.push((index, HotkeyEvent::Released));
+                    state.pressed_at = None;
+                }
+                (true, true) => {
+                    if !state.held
+                        && state
+                            .pressed_at
+                            .is_some_and(|p| now.duration_since(p) >= self.hold_threshold)
+                    {
+                        self.events.push((index, HotkeyEvent::Held));
+                        state.held = true;
+                    }
+                }
+                (false, false) => {}
+            }
+
+            state.down = down;
+        }
+    }
+
+    /// All transitions detected during the last update.
+    pub fn events(&self) -> impl Iterator<Item = (&str, HotkeyEvent)> {
+        self.events
+            .iter()
+            .map(|(index, event)| (self.bindings[*index].action.as_str(), *event))
+    }
+
+    /// Checks if the action had the specified transition during the last update.
+    pub fn triggered(&self, action: &str, event: HotkeyEvent) -> bool {
+        self.events().any(|(a, e)| a == action && e == event)
+    }
+
+    pub fn pressed(&self, action: &str) -> bool {
+        self.triggered(action, HotkeyEvent::Pressed)
+    }
+
+    pub fn released(&self, action: &str) -> bool {
+        self.triggered(action, HotkeyEvent::Released)
+    }
+
+    pub fn held(&self, action: &str) -> bool {
+        self.triggered(action, HotkeyEvent::Held)
+    }
+
+    pub fn double_tapped(&self, action: &str) -> bool {
+        self.triggered(action, HotkeyEvent::DoubleTap)
+    }
+
+    /// Whether or not any of the action's chords is currently down.
+    pub fn is_down(&self, action: &str) -> bool {
+        self.bindings
+            .iter()
+            .any(|b| b.action == action && b.state.down)
+    }
+}
+
+/// Checks if the game's window is the foreground window.
+fn is_game_focused() -> bool {
+    install_static_resolver();
+    // Shared access is enough to read the window handle.
+    let Some(window) = (unsafe { CSWindowImp::instance() }) else {
+        return false;
+    };
+
+    unsafe { GetForegroundWindow() }.0 == window.window_handle
+}
+
+fn is_key_down(key: i32) -> bool {
+    (unsafe { GetAsyncKeyState(key) }) < 0
+}
+
+fn held_modifiers() -> u8 {
+    let mut modifiers = 0;
+    if is_key_down(VK_CONTROL.0 as i32) {
+        modifiers |= MODIFIER_CTRL;
+    }
+    if is_key_down(VK_SHIFT.0 as i32) {
+        modifiers |= MODIFIER_SHIFT;
+    }
+    if is_key_down(VK_MENU.0 as i32) {
+        modifiers |= MODIFIER_ALT;
+    }
+    modifiers
+}
+
+fn modifier_for_key(key: i32) -> u8 {
+    match key {
+        k if k == VK_CONTROL.0 as i32 => MODIFIER_CTRL,
+        k if k == VK_SHIFT.0 as i32 => MODIFIER_SHIFT,
+        k if k == VK_MENU.0 as i32 => MODIFIER_ALT,
+        _ => 0,
+    }
+}
+
+/// Named keys other than letters, digits, function keys and numpad keys.
+const NAMED_KEYS: &[(&str, u16)] = &[
+    ("Space", VK_SPACE.0),
+    ("Enter", VK_RETURN.0),
+    ("Escape", VK_ESCAPE.0),
+    ("Tab", VK_TAB.0),
+    ("Backspace", VK_BACK.0),
+    ("Insert", VK_INSERT.0),
+    ("Delete", VK_DELETE.0),
+    ("Home", VK_HOME.0),
+    ("End", VK_END.0),
+    ("PageUp", VK_PRIOR.0),
+    ("PageDown", VK_NEXT.0),
+    ("Up", VK_UP.0),
+    ("Down", VK_DOWN.0),
+    ("Left", VK_LEFT.0),
+    ("Right", VK_RIGHT.0),
+    ("Mouse1", VK_LBUTTON.0),
+    ("Mouse2", VK_RBUTTON.0),
+    ("Mouse3", VK_MBUTTON.0),
+    ("Mouse4", VK_XBUTTON1.0),
+    ("Mouse5", VK_XBUTTON2.0),
+];
+
+/// Resolves a key name (case insensitive) to a virtual key code. Besides the
+/// names above this accepts `A`-`Z`, `0`-`9`, `F1`-`F24`, `Numpad0`-`Numpad9`
+/// and raw virtual key codes like `0xC0`.
+fn virtual_key_by_name(name: &str) -> Option<i32> {
+    let name = name.to_ascii_lowercase();
+
+    if let Some((_, key)) = NAMED_KEYS
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(&name))
+    {
+        return Some(*key as i32);
+    }
+
+    if name == "esc" {
+        return Some(VK_ESCAPE.0 as i32);
+    }
+
+    if let [c] = name.as_bytes() {
+        if c.is_ascii_alphanumeric() {
+            return Some(c.to_ascii_uppercase() as i32);
+        }
+    }
+
+    if let Some(index) = name.strip_prefix('f').and_then(|n| n.parse::<i32>().ok()) {
+        return (1..=24).contains(&index).then_some(0x70 + index - 1);
+    }
+
+    if let Some(index) = name
+        .strip_prefix("numpad")
+        .and_then(|n| n.parse::<i32>().ok())
+    {
+        return (0..=9).contains(&index).then_some(0x60 + index);
+    }
+
+    if let Some(hex) = name.strip_prefix("0x") {
+        return i32::from_str_radix(hex, 16).ok();
+    }
+
+    None
+}
+
+fn virtual_key_name(key: i32) -> Option<String> {
+    if let Some((name, _)) = NAMED_KEYS.iter().find(|(_, k)| *k as i32 == key) {
+        return Some(name.to_string());
+    }
+
+    match key {
+        0x30..=0x39 | 0x41..=0x5a => Some((key as u8 as char).to_string()),
+        0x60..=0x69 => Some(format!("Numpad{}", key - 0x60)),
+        0x70..=0x87 => Some(format!("F{}", key - 0x70 + 1)),
+        _ if key == VK_CONTROL.0 as i32 => Some("Ctrl".to_string()),
+        _ if key == VK_SHIFT.0 as i32 => Some("Shift".to_string()),
+        _ if key == VK_MENU.0 as i32 => Some("Alt".to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use super::{HotkeyError, HotkeyEvent, HotkeyManager, KeyChord};
+
+    const F5: i32 = 0x74;
+
+    #[test]
+    fn parse_chords() {
+        assert_eq!("F5".parse::<KeyChord>().unwrap(), KeyChord::new(F5));
+        assert_eq!(
+            "ctrl + SHIFT + f5".parse::<KeyChord>().unwrap(),
+            KeyChord::new(F5).ctrl().shift()
+        );
+        assert_eq!(
+            "Alt+Numpad3".parse::<KeyChord>().unwrap(),
+            KeyChord::new(0x63).alt()
+        );
+        assert_eq!("0xC0".parse::<KeyChord>().unwrap(), KeyChord::new(0xc0));
+        assert_eq!("Esc".parse::<KeyChord>().unwrap(), KeyChord::new(0x1b));
+
+        assert!(matches!(
+            "Ctrl+F25".parse::<KeyChord>(),
+            Err(HotkeyError::UnknownKey(key)) if key == "F25"
+        ));
+        assert!(matches!(
+            "Ctrl+Shift".parse::<KeyChord>(),
+            Err(HotkeyError::MissingKey(_))
+        ));
+        assert!(matches!(
+            "Ctrl+A+B".parse::<KeyChord>(),
+            Err(HotkeyError::MultipleKeys(_))
+        ));
+    }
+
+    #[test]
+    fn chord_round_trip() {
+        for chord in [
+            "F1",
+            "Ctrl+Shift+F5",
+            "Alt+Numpad0",
+            "Ctrl+Alt+Delete",
+            "Shift+Mouse4",
+            "A",
+            "7",
+            "F24",
+            "0xc0",
+        ] {
+            let parsed = chord.parse::<KeyChord>().unwrap();
+            assert_eq!(parsed.to_string(), chord);
+            assert_eq!(parsed.to_string().parse::<KeyChord>().unwrap(), parsed);
+        }
+    }
+
+    #[test]
+    fn parse_config() {
+        let manager = HotkeyManager::from_toml(
+            r#"
+            hold_threshold_ms = 250
+
+            [bindings]
+            toggle_hud = "F1"
+            quick_warp = "Ctrl+Shift+F5"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(manager.hold_threshold, Duration::from_millis(250));
+        assert_eq!(manager.double_tap_window, Duration::from_millis(300));
+
+        let mut bindings = manager
+            .bindings
+            .iter()
+            .map(|b| (b.action.as_str(), b.chord))
+            .collect::<Vec<_>>();
+        bindings.sort_by_key(|(action, _)| *action);
+        assert_eq!(
+            bindings,
+            [
+                ("quick_warp", KeyChord::new(F5).ctrl().shift()),
+                ("toggle_hud", KeyChord::new(0x70)),
+            ]
+        );
+
+        assert!(matches!(
+            HotkeyManager::from_toml("[bindings]\nbroken = \"Ctrl+Nope\""),
+            Err(HotkeyError::UnknownKey(_))
+        ));
+        assert!(matches!(
+            HotkeyManager::from_toml("hold_threshold_ms = \"soon\""),
+            Err(HotkeyError::Config(_))
+        ));
+    }
+
+    fn events(manager: &HotkeyManager) -> Vec<HotkeyEvent> {
+        manager.events().map(|(_, event)| event).collect()
+    }
+
+    #[test]
+    fn press_hold_release() {
+        let mut manager = HotkeyManager::new();
+        manager.bind("warp", KeyChord::new(F5));
+        let start = Instant::now();
+
+        manager.step(start, |_| true);
+        assert_eq!(events(&manager), [HotkeyEvent::Pressed]);
+        assert!(manager.pressed("warp"));
+        assert!(manager.is_down("warp"));
+
+        manager.step(start + Duration::from_millis(100), |_| true);
+        assert_eq!(events(&manager), []);
+
+        manager.step(start + Duration::from_millis(500), |_| true);
+        assert_eq!(events(&manager), [HotkeyEvent::Held]);
+        assert!(manager.held("warp"));
+
+        // Held only fires once per press.
+        manager.step(start + Duration::from_millis(900), |_| true);
+        assert_eq!(events(&manager), []);
+
+        manager.step(start + Duration::from_millis(1000), |_| false);
+        assert_eq!(events(&manager), [HotkeyEvent::Released]);
+        assert!(manager.released("warp"));
+        assert!(!manager.is_down("warp"));
+    }
+
+    #[test]
+    fn double_tap() {
+        let mut manager = HotkeyManager::new();
+        manager.bind("warp", KeyChord::new(F5));
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+
+        manager.step(at(0), |_| true);
+        manager.step(at(50), |_| false);
+        manager.step(at(200), |_| true);
+        assert_eq!(
+            events(&manager),
+            [HotkeyEvent::Pressed, HotkeyEvent::DoubleTap]
+        );
+        assert!(manager.double_tapped("warp"));
+
+        // A third press right after doesn't count as another double tap.
+        manager.step(at(250), |_| false);
+        manager.step(at(300), |_| true);
+        assert_eq!(events(&manager), [HotkeyEvent::Pressed]);
+
+        // Presses further apart than the window are separate presses.
+        manager.step(at(350), |_| false);
+        manager.step(at(1000), |_| true);
+        assert_eq!(events(&manager), [HotkeyEvent::Pressed]);
+    }
+
+    #[test]
+    fn only_bound_actions_trigger() {
+        let mut manager = HotkeyManager::new();
+        manager.bind("warp", KeyChord::new(F5));
+        manager.bind("hud", KeyChord::new(0x70));
+        manager.bind("hud", KeyChord::new(0x71));
+        let now = Instant::now();
+
+        manager.step(now, |chord| chord.key == 0x71);
+        assert!(manager.pressed("hud"));
+        assert!(!manager.pressed("warp"));
+
+        manager.unbind("hud");
+        assert!(!manager.pressed("hud"));
+        manager.step(now, |_| true);
+        assert_eq!(
+            manager.events().collect::<Vec<_>>(),
+            [("warp", HotkeyEvent::Pressed)]
+        );
+    }
+}
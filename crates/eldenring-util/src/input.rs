@@ -11,6 +11,9 @@ const DEBOUNCE_TIMEOUT: Duration = Duration::from_millis(250);
 type DebounceMap = collections::HashMap<i32, time::Instant>;
 static DEBOUNCE_MAP: sync::LazyLock<Mutex<DebounceMap>> = sync::LazyLock::new(Default::default);
 
+/// Checks if the key is down, returning true at most once every 250ms per key.
+/// See [`crate::hotkey::HotkeyManager`] for edge-triggered hotkeys and
+/// [`crate::pad::PadInput`] for controller input.
 pub fn is_key_pressed(key: i32) -> bool {
     if unsafe { KeyboardAndMouse::GetKeyState(key) } < 0 {
         let now = std::time::Instant::now();
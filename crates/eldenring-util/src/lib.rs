@@ -3,20 +3,30 @@ pub mod bullet_manager;
 pub mod camera;
 pub mod character_debug_flags;
 pub mod character_type_properties;
+pub mod dispatcher;
 pub mod ez_draw;
 pub mod ez_state;
+pub mod executor;
 pub mod fade;
+pub mod file;
+pub mod file_device;
 pub mod gaitem;
 pub mod geometry;
 pub mod havok;
+pub mod hook;
+pub mod hotkey;
 pub mod input;
+pub mod pad;
 pub mod program;
 pub mod rtti;
+pub mod runtime_class;
 pub mod singleton;
 pub mod steam;
 pub mod system;
 pub mod task;
 pub mod team_relation;
+pub mod version;
+pub mod vmt_hook;
 pub mod world_area_time;
 pub mod world_chr_man;
 
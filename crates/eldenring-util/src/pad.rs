@@ -0,0 +1,337 @@
+//! Reading the player's input in terms of the game's actions.
+//!
+//! The game merges the input of the pad, keyboard and mouse into virtual
+//! keys, which are what its actions are bound to. The IDs of the virtual keys
+//! are the row IDs of the `DEFAULT_KEY_ASSIGN` param. Sticks and triggers are
+//! analog virtual keys, one per axis.
+//!
+//! The row IDs for common actions like attacking or rolling aren't part of
+//! this crate as they haven't been checked against every game version. A
+//! [`PadActionMap`] names them instead, usually loaded from a config file
+//! holding an `[actions]` table that maps action names like `attack` or
+//! `use_item` to `DEFAULT_KEY_ASSIGN` row IDs.
+//!
+//! Input is only read. The pad manager's layout hasn't been confirmed against
+//! a game build, so writing through it could corrupt the game's memory.
+//!
+//! [`PadInput`] is meant to be updated once per frame from a task in
+//! `CSTaskGroupIndex::PadStep`. Tasks registered by mods run after the game's
+//! own pad task in that group, so the input has been polled by then.
+//!
+//! ```ignore
+//! let actions = PadActionMap::load("mod_actions.toml")?;
+//! let Some(attack) = actions.get(GameAction::Attack) else {
+//!     return Ok(());
+//! };
+//!
+//! let mut pad = PadInput::new();
+//! cs_task.run_recurring(
+//!     move |_: &FD4TaskData| {
+//!         pad.update();
+//!
+//!         if pad.pressed(attack) {
+//!             tracing::info!("Attacked");
+//!         }
+//!     },
+//!     CSTaskGroupIndex::PadStep,
+//! );
+//! ```
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use eldenring::dluid::VirtualInputData;
+use eldenring::fd4::FD4PadManager;
+
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::singleton::get_instance;
+
+#[derive(Error, Debug)]
+pub enum PadError {
+    #[error("Unknown action {0}.")]
+    UnknownAction(String),
+    #[error("Could not read action config. {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not parse action config. {0}")]
+    Config(#[from] toml::de::Error),
+}
+
+/// Index of the player's device in [`FD4PadManager::pad_devices`].
+const PLAYER_PAD_DEVICE: usize = 0;
+
+/// A virtual key, identified by its `DEFAULT_KEY_ASSIGN` row ID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PadAction(pub u32);
+
+/// Common actions of the player character. Which virtual key each one is
+/// bound to is looked up through a [`PadActionMap`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GameAction {
+    Attack,
+    StrongAttack,
+    Guard,
+    Skill,
+    Roll,
+    Jump,
+    Crouch,
+    Interact,
+    UseItem,
+    LockOn,
+}
+
+impl GameAction {
+    const ALL: [Self; 10] = [
+        Self::Attack,
+        Self::StrongAttack,
+        Self::Guard,
+        Self::Skill,
+        Self::Roll,
+        Self::Jump,
+        Self::Crouch,
+        Self::Interact,
+        Self::UseItem,
+        Self::LockOn,
+    ];
+
+    /// Name of the action as used in the config, ex: "use_item".
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Attack => "attack",
+            Self::StrongAttack => "strong_attack",
+            Self::Guard => "guard",
+            Self::Skill => "skill",
+            Self::Roll => "roll",
+            Self::Jump => "jump",
+            Self::Crouch => "crouch",
+            Self::Interact => "interact",
+            Self::UseItem => "use_item",
+            Self::LockOn => "lock_on",
+        }
+    }
+}
+
+impl FromStr for GameAction {
+    type Err = PadError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|a| a.name().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| PadError::UnknownAction(s.to_string()))
+    }
+}
+
+impl fmt::Display for GameAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PadActionConfig {
+    /// Maps action names to `DEFAULT_KEY_ASSIGN` row IDs.
+    #[serde(default)]
+    pub actions: HashMap<String, u32>,
+}
+
+/// Maps [`GameAction`]s to the virtual keys they're bound to.
+#[derive(Debug, Clone, Default)]
+pub struct PadActionMap {
+    actions: HashMap<GameAction, PadAction>,
+}
+
+impl PadActionMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_config(config: &PadActionConfig) -> Result<Self, PadError> {
+        let mut map = Self::new();
+        for (action, row) in config.actions.iter() {
+            map.bind(action.parse()?, PadAction(*row));
+        }
+
+        Ok(map)
+    }
+
+    pub fn from_toml(contents: &str) -> Result<Self, PadError> {
+        Self::from_config(&toml::from_str(contents)?)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, PadError> {
+        Self::from_toml(&std::fs::read_to_string(path)?)
+    }
+
+    /// Binds the action to a virtual key, replacing its previous binding.
+    pub fn bind(&mut self, action: GameAction, pad_action: PadAction) {
+        self.actions.insert(action, pad_action);
+    }
+
+    /// The virtual key the action is bound to, if any.
+    pub fn get(&self, action: GameAction) -> Option<PadAction> {
+        self.actions.get(&action).copied()
+    }
+}
+
+/// The pair of analog actions making up a stick.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PadStick {
+    pub x: PadAction,
+    pub y: PadAction,
+}
+
+/// Tracks the player's input between frames.
+#[derive(Default)]
+pub struct PadInput {
+    digital: Vec<u32>,
+    previous_digital: Vec<u32>,
+    analog: Vec<f32>,
+}
+
+impl PadInput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads the input polled by the game this frame. Call this once per
+    /// frame from a `CSTaskGroupIndex::PadStep` task.
+    ///
+    /// Reads as if nothing is held while the pad manager isn't available.
+    pub fn update(&mut self) {
+        match player_input() {
+            Some(input) => self.read(
+                input.digital_key_info.words(),
+                input.analog_key_info.values.items(),
+            ),
+            None => self.read(&[], &[]),
+        }
+    }
+
+    /// Snapshots the polled input.
+    fn read(&mut self, digital: &[u32], analog: &[f32]) {
+        std::mem::swap(&mut self.digital, &mut self.previous_digital);
+        self.digital.clear();
+        self.digital.extend_from_slice(digital);
+        self.analog.clear();
+        self.analog.extend_from_slice(analog);
+    }
+
+    /// Whether or not the action is held.
+    pub fn is_down(&self, action: PadAction) -> bool {
+        is_bit_set(&self.digital, action)
+    }
+
+    /// Whether or not the action went down this frame.
+    pub fn pressed(&self, action: PadAction) -> bool {
+        self.is_down(action) && !is_bit_set(&self.previous_digital, action)
+    }
+
+    /// Whether or not the action went up this frame.
+    pub fn released(&self, action: PadAction) -> bool {
+        !self.is_down(action) && is_bit_set(&self.previous_digital, action)
+    }
+
+    /// Analog value of the action, ex: how far a trigger is pulled. Returns 0
+    /// for actions without an analog value.
+    pub fn analog(&self, action: PadAction) -> f32 {
+        self.analog.get(action.0 as usize).copied().unwrap_or(0.0)
+    }
+
+    /// The x and y axis of the stick.
+    pub fn stick(&self, stick: PadStick) -> (f32, f32) {
+        (self.analog(stick.x), self.analog(stick.y))
+    }
+}
+
+fn player_input() -> Option<&'static VirtualInputData> {
+    // SAFETY: the game only touches the pad manager from its own PadStep task,
+    // which has already run by the time the mod's PadStep tasks run.
+    let pad_manager: &'static FD4PadManager = unsafe { get_instance::<FD4PadManager>() }.ok()??;
+
+    pad_manager
+        .pad_device(PLAYER_PAD_DEVICE)
+        .map(|d| &d.user_input_device.virtual_input_data)
+}
+
+fn is_bit_set(words: &[u32], action: PadAction) -> bool {
+    let index = action.0 as usize;
+    words
+        .get(index / 32)
+        .is_some_and(|w| w & (1 << (index % 32)) != 0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{GameAction, PadAction, PadActionMap, PadError, PadInput, PadStick};
+
+    const ATTACK: PadAction = PadAction(3);
+    const USE_ITEM: PadAction = PadAction(35);
+    const MOVE: PadStick = PadStick {
+        x: PadAction(0),
+        y: PadAction(1),
+    };
+
+    #[test]
+    fn transitions() {
+        let mut pad = PadInput::new();
+
+        pad.read(&[0, 0], &[]);
+        assert!(!pad.is_down(ATTACK));
+
+        pad.read(&[1 << 3, 0], &[]);
+        assert!(pad.is_down(ATTACK));
+        assert!(pad.pressed(ATTACK));
+
+        pad.read(&[1 << 3, 0], &[]);
+        assert!(pad.is_down(ATTACK));
+        assert!(!pad.pressed(ATTACK));
+
+        pad.read(&[0, 0], &[]);
+        assert!(!pad.is_down(ATTACK));
+        assert!(pad.released(ATTACK));
+
+        // Out of range actions are never down.
+        assert!(!pad.is_down(PadAction(64)));
+    }
+
+    #[test]
+    fn analog_values() {
+        let mut pad = PadInput::new();
+        pad.read(&[], &[0.5, -1.0, 0.25]);
+
+        assert_eq!(pad.stick(MOVE), (0.5, -1.0));
+        assert_eq!(pad.analog(PadAction(2)), 0.25);
+        assert_eq!(pad.analog(PadAction(3)), 0.0);
+    }
+
+    #[test]
+    fn parse_action_map() {
+        let map = PadActionMap::from_toml(
+            r#"
+            [actions]
+            attack = 3
+            USE_ITEM = 35
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(map.get(GameAction::Attack), Some(ATTACK));
+        assert_eq!(map.get(GameAction::UseItem), Some(USE_ITEM));
+        assert_eq!(map.get(GameAction::Roll), None);
+
+        assert!(matches!(
+            PadActionMap::from_toml("[actions]\nsprint = 3"),
+            Err(PadError::UnknownAction(action)) if action == "sprint"
+        ));
+    }
+
+    #[test]
+    fn action_names_round_trip() {
+        for action in GameAction::ALL {
+            assert_eq!(action.to_string().parse::<GameAction>().unwrap(), action);
+        }
+    }
+}
@@ -40,3 +40,205 @@ unsafe impl<'a> PeObject<'a> for Program<'a> {
         }
     }
 }
+
+/// Hand-built mapped PE64 image, used to run the static analysis against
+/// without requiring a game executable.
+#[cfg(test)]
+pub(crate) mod fixture {
+    use pelite::pe64::{Pe, PeFile, PeView, Rva};
+
+    use super::Program;
+
+    pub const IMAGE_BASE: u64 = 0x1_4000_0000;
+
+    const PAGE_SIZE: usize = 0x1000;
+    const FILE_ALIGNMENT: usize = 0x200;
+    const SIZE_OF_HEADERS: usize = 0x400;
+    const NT_HEADERS: usize = 0x40;
+    const FILE_HEADER: usize = NT_HEADERS + 0x4;
+    const OPTIONAL_HEADER: usize = FILE_HEADER + 0x14;
+    const SECTION_HEADERS: usize = OPTIONAL_HEADER + 0xf0;
+    const RESOURCE_DIRECTORY: usize = OPTIONAL_HEADER + 0x70 + 2 * 0x8;
+
+    pub struct TestImage {
+        // Backed by u64s as pelite expects the image to be aligned.
+        words: Vec<u64>,
+    }
+
+    impl TestImage {
+        /// Lays out a single page for every section right after the headers.
+        /// The .text section is filled with INT3 and counts as the code range.
+        /// In the file the sections directly follow the headers, so their file
+        /// offsets differ from their RVAs like they would for the game.
+        pub fn new(sections: &[&str]) -> Self {
+            let size_of_image = (sections.len() + 1) * PAGE_SIZE;
+            let mut image = Self {
+                words: vec![0; size_of_image / 8],
+            };
+
+            image.write(0x0, b"MZ");
+            image.write(0x3c, &(NT_HEADERS as u32).to_le_bytes());
+            image.write(NT_HEADERS as Rva, b"PE\0\0");
+
+            // Machine, NumberOfSections, SizeOfOptionalHeader and Characteristics.
+            image.write(FILE_HEADER as Rva, &0x8664u16.to_le_bytes());
+            image.write(
+                FILE_HEADER as Rva + 0x2,
+                &(sections.len() as u16).to_le_bytes(),
+            );
+            image.write(FILE_HEADER as Rva + 0x10, &0xf0u16.to_le_bytes());
+            image.write(FILE_HEADER as Rva + 0x12, &0x22u16.to_le_bytes());
+
+            let optional_header = OPTIONAL_HEADER as Rva;
+            image.write(optional_header, &0x20bu16.to_le_bytes());
+            image.write(optional_header + 0x18, &IMAGE_BASE.to_le_bytes());
+            image.write(optional_header + 0x20, &(PAGE_SIZE as u32).to_le_bytes());
+            image.write(
+                optional_header + 0x24,
+                &(FILE_ALIGNMENT as u32).to_le_bytes(),
+            );
+            image.write(
+                optional_header + 0x38,
+                &(size_of_image as u32).to_le_bytes(),
+            );
+            image.write(
+                optional_header + 0x3c,
+                &(SIZE_OF_HEADERS as u32).to_le_bytes(),
+            );
+            image.write(optional_header + 0x6c, &16u32.to_le_bytes());
+
+            for (index, name) in sections.iter().enumerate() {
+                let header = (SECTION_HEADERS + index * 0x28) as Rva;
+                let rva = ((index + 1) * PAGE_SIZE) as u32;
+                let is_code = *name == ".text";
+
+                let mut raw_name = [0u8; 8];
+                raw_name[..name.len()].copy_from_slice(name.as_bytes());
+                image.write(header, &raw_name);
+                image.write(header + 0x8, &(PAGE_SIZE as u32).to_le_bytes());
+                image.write(header + 0xc, &rva.to_le_bytes());
+                image.write(header + 0x10, &(PAGE_SIZE as u32).to_le_bytes());
+                let raw = (SIZE_OF_HEADERS + index * PAGE_SIZE) as u32;
+                image.write(header + 0x14, &raw.to_le_bytes());
+
+                let characteristics: u32 = if is_code { 0x60000020 } else { 0xc0000040 };
+                image.write(header + 0x24, &characteristics.to_le_bytes());
+
+                if is_code {
+                    image.write(optional_header + 0x4, &(PAGE_SIZE as u32).to_le_bytes());
+                    image.write(optional_header + 0x14, &rva.to_le_bytes());
+                    image.write(rva, &[0xcc; PAGE_SIZE]);
+                }
+            }
+
+            image
+        }
+
+        /// RVA of the start of a section.
+        pub fn section(&self, name: &str) -> Rva {
+            self.program()
+                .section_headers()
+                .by_name(name)
+                .expect("section not in test image")
+                .VirtualAddress
+        }
+
+        pub fn write(&mut self, rva: Rva, bytes: &[u8]) {
+            let rva = rva as usize;
+            self.bytes_mut()[rva..rva + bytes.len()].copy_from_slice(bytes);
+        }
+
+        pub fn set_image_base(&mut self, image_base: u64) {
+            self.write(OPTIONAL_HEADER as Rva + 0x18, &image_base.to_le_bytes());
+        }
+
+        pub fn set_timestamp(&mut self, timestamp: u32) {
+            self.write(FILE_HEADER as Rva + 0x4, &timestamp.to_le_bytes());
+        }
+
+        /// Places a single RT_VERSION resource containing `version_info` at
+        /// the start of the section.
+        pub fn set_version_info(&mut self, section: &str, version_info: &[u8]) {
+            let base = self.section(section);
+
+            // Type (16) -> name (1) -> language (0x409) -> data entry. Every
+            // directory holds a single entry which points at the next level.
+            for (level, id) in [16u32, 1, 0x409].into_iter().enumerate() {
+                let directory = base + level as Rva * 0x18;
+                let next = (level as u32 + 1) * 0x18;
+                let next = if level < 2 { next | 0x8000_0000 } else { next };
+
+                self.write(directory + 0xe, &1u16.to_le_bytes());
+                self.write(directory + 0x10, &id.to_le_bytes());
+                self.write(directory + 0x14, &next.to_le_bytes());
+            }
+
+            let data_entry = base + 3 * 0x18;
+            let data = base + 0x60;
+            self.write(data_entry, &data.to_le_bytes());
+            self.write(data_entry + 0x4, &(version_info.len() as u32).to_le_bytes());
+            self.write(data, version_info);
+
+            self.write(RESOURCE_DIRECTORY as Rva, &base.to_le_bytes());
+            self.write(
+                RESOURCE_DIRECTORY as Rva + 0x4,
+                &(PAGE_SIZE as u32).to_le_bytes(),
+            );
+        }
+
+        pub fn bytes(&self) -> &[u8] {
+            unsafe {
+                std::slice::from_raw_parts(self.words.as_ptr() as *const u8, self.words.len() * 8)
+            }
+        }
+
+        fn bytes_mut(&mut self) -> &mut [u8] {
+            unsafe {
+                std::slice::from_raw_parts_mut(
+                    self.words.as_mut_ptr() as *mut u8,
+                    self.words.len() * 8,
+                )
+            }
+        }
+
+        pub fn program(&self) -> Program<'_> {
+            Program::Mapping(PeView::from_bytes(self.bytes()).expect("malformed test image"))
+        }
+
+        /// Lays the image out like its file on disk.
+        pub fn to_file(&self) -> TestFile {
+            let bytes = self.bytes();
+            let section_count =
+                u16::from_le_bytes([bytes[FILE_HEADER + 0x2], bytes[FILE_HEADER + 0x3]]) as usize;
+
+            let mut file = bytes[..SIZE_OF_HEADERS].to_vec();
+            for index in 0..section_count {
+                let rva = (index + 1) * PAGE_SIZE;
+                file.extend_from_slice(&bytes[rva..rva + PAGE_SIZE]);
+            }
+
+            let mut words = vec![0u64; file.len() / 8];
+            for (word, chunk) in words.iter_mut().zip(file.chunks_exact(8)) {
+                *word = u64::from_le_bytes(chunk.try_into().unwrap());
+            }
+
+            TestFile { words }
+        }
+    }
+
+    /// A [`TestImage`] as it would be stored on disk.
+    pub struct TestFile {
+        // Backed by u64s as pelite expects the file to be aligned.
+        words: Vec<u64>,
+    }
+
+    impl TestFile {
+        pub fn program(&self) -> Program<'_> {
+            let bytes = unsafe {
+                std::slice::from_raw_parts(self.words.as_ptr() as *const u8, self.words.len() * 8)
+            };
+
+            Program::File(PeFile::from_bytes(bytes).expect("malformed test file"))
+        }
+    }
+}
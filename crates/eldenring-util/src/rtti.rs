@@ -1,6 +1,9 @@
 use std::ptr::NonNull;
 
-use pelite::pe64::{msvc::RTTICompleteObjectLocator, Pe, Rva, Va};
+use pelite::pe64::{
+    msvc::{RTTIBaseClassDescriptor, RTTIClassHierarchyDescriptor, RTTICompleteObjectLocator},
+    Pe, Rva, Va,
+};
 use undname::Flags;
 
 use crate::program::Program;
@@ -51,25 +54,11 @@ pub fn find_rtti_classes<'a>(program: &'a Program) -> impl Iterator<Item = Class
         .filter_map(|(meta, vftable)| {
             let col: &RTTICompleteObjectLocator = program.derva(meta).ok()?;
 
-            let ty_name = program
-                .derva_c_str(col.type_descriptor + 16)
-                .ok()?
-                .to_string();
-            if !ty_name
-                .chars()
-                .all(|ch| (0x20..=0x7e).contains(&(ch as u8)))
-            {
-                return None;
-            }
-
-            let demangled = undname::demangle(ty_name.as_str(), Flags::NAME_ONLY)
-                .map(|s| s.to_string())
-                .ok()?;
-
             Some(Class {
                 program,
-                name: demangled,
+                name: type_descriptor_name(program, col.type_descriptor)?,
                 vftable,
+                complete_object_locator: meta,
             })
         })
 }
@@ -95,10 +84,12 @@ pub fn vftable_classname(program: &Program, vftable_va: usize) -> Option<String>
     }
 
     let col: &RTTICompleteObjectLocator = program.derva(vftable_meta_rva).ok()?;
-    let ty_name = program
-        .derva_c_str(col.type_descriptor + 16)
-        .ok()?
-        .to_string();
+    type_descriptor_name(program, col.type_descriptor)
+}
+
+/// Reads and demangles the name stored in a RTTI type descriptor.
+fn type_descriptor_name(program: &Program, type_descriptor: Rva) -> Option<String> {
+    let ty_name = program.derva_c_str(type_descriptor + 16).ok()?.to_string();
     if !ty_name
         .chars()
         .all(|ch| (0x20..=0x7e).contains(&(ch as u8)))
@@ -106,11 +97,9 @@ pub fn vftable_classname(program: &Program, vftable_va: usize) -> Option<String>
         return None;
     }
 
-    let demangled = undname::demangle(ty_name.as_str(), Flags::NAME_ONLY)
+    undname::demangle(ty_name.as_str(), Flags::NAME_ONLY)
         .map(|s| s.to_string())
-        .ok()?;
-
-    Some(demangled)
+        .ok()
 }
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
@@ -124,9 +113,105 @@ pub struct Class<'a> {
     program: &'a Program<'a>,
     pub name: String,
     pub vftable: Rva,
+    /// RVA of the complete object locator sitting right in front of the vftable.
+    pub complete_object_locator: Rva,
+}
+
+/// Class hierarchy as described by the RTTI class hierarchy descriptor.
+#[derive(Debug, Clone)]
+pub struct ClassHierarchy {
+    /// Attributes of the class hierarchy descriptor. Bit 0 indicates multiple
+    /// inheritance, bit 1 indicates virtual inheritance.
+    pub attributes: u32,
+    /// All classes in the hierarchy in declaration order. The first entry is
+    /// the class itself.
+    pub base_classes: Vec<BaseClass>,
+}
+
+impl ClassHierarchy {
+    /// Iterates over the direct bases of the described class.
+    pub fn direct_bases(&self) -> impl Iterator<Item = &BaseClass> {
+        let mut index = 1;
+        std::iter::from_fn(move || {
+            let base = self.base_classes.get(index)?;
+            index += 1 + base.contained_bases as usize;
+            Some(base)
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BaseClass {
+    pub name: String,
+    /// Amount of base classes this base class itself has.
+    pub contained_bases: u32,
+    /// Offset of the base class inside of the derived class.
+    pub member_displacement: i32,
+    /// Offset of the vbtable, -1 if the base isn't inherited virtually.
+    pub vbtable_displacement: i32,
+    /// Offset of the displacement inside of the vbtable.
+    pub vbtable_entry_displacement: i32,
+    pub attributes: u32,
 }
 
 impl Class<'_> {
+    /// Offset of this vftable within the complete object. Non-zero for the
+    /// secondary vftables of classes with multiple inheritance.
+    pub fn vftable_offset(&self) -> Option<u32> {
+        let col: &RTTICompleteObjectLocator =
+            self.program.derva(self.complete_object_locator).ok()?;
+        Some(col.offset)
+    }
+
+    /// Counts the amount of entries in the VMT by walking it until it finds an
+    /// entry that doesn't point into .text. Returns None if the binary has no
+    /// .text section.
+    pub fn vftable_len(&self) -> Option<usize> {
+        let text = self.program.section_headers().by_name(".text")?;
+
+        let len = (0..)
+            .map_while(|index| {
+                let entry: &Va = self.program.derva(self.vftable + VA_SIZE * index).ok()?;
+                let rva = self.program.va_to_rva(*entry).ok()?;
+                text.virtual_range().contains(&rva).then_some(())
+            })
+            .count();
+
+        Some(len)
+    }
+
+    /// Reads the class hierarchy descriptor referenced by the complete object locator.
+    pub fn hierarchy(&self) -> Option<ClassHierarchy> {
+        let col: &RTTICompleteObjectLocator =
+            self.program.derva(self.complete_object_locator).ok()?;
+        let chd: &RTTIClassHierarchyDescriptor = self.program.derva(col.class_descriptor).ok()?;
+        let base_class_rvas: &[Rva] = self
+            .program
+            .derva_slice(chd.base_class_array, chd.num_base_classes as usize)
+            .ok()?;
+
+        let base_classes = base_class_rvas
+            .iter()
+            .map(|rva| {
+                let bcd: &RTTIBaseClassDescriptor = self.program.derva(*rva).ok()?;
+
+                Some(BaseClass {
+                    name: type_descriptor_name(self.program, bcd.type_descriptor)?,
+                    contained_bases: bcd.num_contained_bases,
+                    member_displacement: bcd.pmd.mdisp,
+                    vbtable_displacement: bcd.pmd.pdisp,
+                    vbtable_entry_displacement: bcd.pmd.vdisp,
+                    attributes: bcd.attributes,
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(ClassHierarchy {
+            attributes: chd.attributes,
+            base_classes,
+        })
+    }
+
     /// Retrieves the function pointer from the VMT.
     ///
     /// # Safety
@@ -148,3 +233,142 @@ impl Class<'_> {
         NonNull::new(ptr)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use pelite::pe64::Rva;
+
+    use super::find_rtti_classes;
+    use crate::program::fixture::{TestImage, IMAGE_BASE};
+
+    /// Type descriptors by mangled name, in base class array order.
+    const TYPES: [&str; 4] = [
+        ".?AVDerived@@",
+        ".?AVCSEzDraw@CS@@",
+        ".?AVRoot@@",
+        ".?AVOther@@",
+    ];
+    /// Amount of bases each entry of the base class array has itself.
+    const CONTAINED_BASES: [u32; 4] = [3, 1, 0, 0];
+
+    /// Lays out the RTTI for `Derived : CS::CSEzDraw, Other` where
+    /// `CS::CSEzDraw : Root`, with a three entry vftable for `Derived`.
+    fn test_image() -> TestImage {
+        let mut image = TestImage::new(&[".text", ".rdata"]);
+        let text = image.section(".text");
+        let rdata = image.section(".rdata");
+        let va = |rva: Rva| (IMAGE_BASE + rva as u64).to_le_bytes();
+
+        let type_descriptor = |index: usize| rdata + 0x100 + index as Rva * 0x40;
+        let base_class_descriptor = |index: usize| rdata + 0x300 + index as Rva * 0x20;
+        let base_class_array = rdata + 0x400;
+        let class_hierarchy_descriptor = rdata + 0x420;
+        let complete_object_locator = rdata + 0x440;
+
+        for (index, name) in TYPES.iter().enumerate() {
+            image.write(type_descriptor(index) + 0x10, name.as_bytes());
+
+            let descriptor = base_class_descriptor(index);
+            image.write(descriptor, &type_descriptor(index).to_le_bytes());
+            image.write(descriptor + 0x4, &CONTAINED_BASES[index].to_le_bytes());
+            // Other sits behind the vftable pointer and CS::CSEzDraw.
+            let member_displacement: i32 = if index == 3 { 0x10 } else { 0 };
+            image.write(descriptor + 0x8, &member_displacement.to_le_bytes());
+            image.write(descriptor + 0xc, &(-1i32).to_le_bytes());
+            image.write(descriptor + 0x14, &0x40u32.to_le_bytes());
+            image.write(descriptor + 0x18, &class_hierarchy_descriptor.to_le_bytes());
+
+            image.write(
+                base_class_array + index as Rva * 4,
+                &descriptor.to_le_bytes(),
+            );
+        }
+
+        // Multiple inheritance.
+        image.write(class_hierarchy_descriptor + 0x4, &1u32.to_le_bytes());
+        image.write(
+            class_hierarchy_descriptor + 0x8,
+            &(TYPES.len() as u32).to_le_bytes(),
+        );
+        image.write(
+            class_hierarchy_descriptor + 0xc,
+            &base_class_array.to_le_bytes(),
+        );
+
+        image.write(complete_object_locator, &1u32.to_le_bytes());
+        image.write(
+            complete_object_locator + 0xc,
+            &type_descriptor(0).to_le_bytes(),
+        );
+        image.write(
+            complete_object_locator + 0x10,
+            &class_hierarchy_descriptor.to_le_bytes(),
+        );
+
+        // The vftable is preceded by the locator and ends at the null entry.
+        image.write(rdata + 0x500, &va(complete_object_locator));
+        for index in 0..3 {
+            image.write(rdata + 0x508 + index * 8, &va(text + index * 0x10));
+        }
+
+        image
+    }
+
+    #[test]
+    fn finds_classes() {
+        let image = test_image();
+        let program = image.program();
+
+        let classes = find_rtti_classes(&program).collect::<Vec<_>>();
+        assert_eq!(classes.len(), 1);
+
+        let class = &classes[0];
+        assert_eq!(class.name, "Derived");
+        assert_eq!(class.vftable, image.section(".rdata") + 0x508);
+        assert_eq!(
+            class.complete_object_locator,
+            image.section(".rdata") + 0x440
+        );
+        assert_eq!(class.vftable_offset(), Some(0));
+        assert_eq!(class.vftable_len(), Some(3));
+    }
+
+    #[test]
+    fn reads_hierarchy() {
+        let image = test_image();
+        let program = image.program();
+        let class = find_rtti_classes(&program).next().unwrap();
+
+        let hierarchy = class.hierarchy().unwrap();
+        assert_eq!(hierarchy.attributes, 1);
+
+        let names = hierarchy
+            .base_classes
+            .iter()
+            .map(|b| b.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["Derived", "CS::CSEzDraw", "Root", "Other"]);
+
+        let other = &hierarchy.base_classes[3];
+        assert_eq!(other.member_displacement, 0x10);
+        assert_eq!(other.vbtable_displacement, -1);
+        assert_eq!(other.attributes, 0x40);
+    }
+
+    #[test]
+    fn direct_bases_skip_nested_bases() {
+        let image = test_image();
+        let program = image.program();
+        let hierarchy = find_rtti_classes(&program)
+            .next()
+            .unwrap()
+            .hierarchy()
+            .unwrap();
+
+        let direct = hierarchy
+            .direct_bases()
+            .map(|b| b.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(direct, ["CS::CSEzDraw", "Other"]);
+    }
+}
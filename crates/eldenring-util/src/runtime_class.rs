@@ -0,0 +1,140 @@
+use std::collections::HashSet;
+use std::sync::{Mutex, PoisonError};
+
+use eldenring::dlrf::DLRuntimeClass;
+use pelite::pe64::{Pe, PeObject, Va};
+
+use crate::program::Program;
+use crate::rtti::find_rtti_classes;
+
+const RUNTIME_CLASS_NAME: &str = "DLRF::DLRuntimeClass";
+
+type RuntimeClassTable = &'static [(String, usize)];
+
+/// Name and address of every runtime class per program, keyed by the address
+/// of the program's image.
+static RUNTIME_CLASS_TABLES: Mutex<Vec<(usize, RuntimeClassTable)>> = Mutex::new(Vec::new());
+
+/// Finds all runtime classes registered with DLRF. Runtime classes are
+/// statics so this looks for the vftables of all classes deriving from
+/// DLRuntimeClass and then scans .data for objects using them. The scan only
+/// happens on the first call that finds any runtime classes in the program,
+/// later calls reuse its results.
+///
+/// # Safety
+/// User must ensure that:
+///  - The program is the currently running game.
+///  - The statics have been constructed (wait_for_system_init).
+pub unsafe fn runtime_classes(program: &Program) -> Vec<&'static DLRuntimeClass> {
+    runtime_class_table(program)
+        .iter()
+        .map(|(_, va)| unsafe { &*(*va as *const DLRuntimeClass) })
+        .collect()
+}
+
+/// Finds the runtime class with the specified name, ex: "CSTaskImp".
+///
+/// # Safety
+/// Same as [`runtime_classes`].
+pub unsafe fn find_runtime_class(program: &Program, name: &str) -> Option<&'static DLRuntimeClass> {
+    runtime_class_table(program)
+        .iter()
+        .find(|(class_name, _)| class_name == name)
+        .map(|(_, va)| unsafe { &*(*va as *const DLRuntimeClass) })
+}
+
+unsafe fn runtime_class_table(program: &Program) -> RuntimeClassTable {
+    cached_table(program.image().as_ptr() as usize, || {
+        scan_runtime_classes(program)
+            .into_iter()
+            .map(|class| (class.name(), class as *const DLRuntimeClass as usize))
+            .collect()
+    })
+}
+
+/// Returns the table of the program at `image`, scanning it if there is none
+/// yet. Empty tables aren't cached since the runtime classes might not have
+/// been constructed yet.
+fn cached_table(image: usize, scan: impl FnOnce() -> Vec<(String, usize)>) -> RuntimeClassTable {
+    let mut tables = RUNTIME_CLASS_TABLES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    if let Some((_, table)) = tables.iter().find(|(key, _)| *key == image) {
+        return table;
+    }
+
+    let table = scan();
+    if table.is_empty() {
+        return &[];
+    }
+
+    let table = Vec::leak(table);
+    tables.push((image, table));
+    table
+}
+
+unsafe fn scan_runtime_classes(program: &Program) -> Vec<&'static DLRuntimeClass> {
+    let vftables = find_rtti_classes(program)
+        .filter(|class| {
+            class.name == RUNTIME_CLASS_NAME
+                || class.hierarchy().is_some_and(|h| {
+                    h.base_classes
+                        .iter()
+                        .any(|base| base.name == RUNTIME_CLASS_NAME)
+                })
+        })
+        .filter_map(|class| program.rva_to_va(class.vftable).ok())
+        .collect::<HashSet<_>>();
+
+    let Some(data) = program.section_headers().by_name(".data") else {
+        return Vec::new();
+    };
+
+    data.virtual_range()
+        .step_by(size_of::<Va>())
+        .filter_map(|rva| {
+            let vftable: &Va = program.derva(rva).ok()?;
+            if !vftables.contains(vftable) {
+                return None;
+            }
+
+            let va = program.rva_to_va(rva).ok()?;
+            Some(unsafe { &*(va as *const DLRuntimeClass) })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+
+    use super::cached_table;
+
+    #[test]
+    fn caches_tables_per_program() {
+        let scans = Cell::new(0);
+        let scan = |classes: &[&str]| {
+            scans.set(scans.get() + 1);
+            classes
+                .iter()
+                .enumerate()
+                .map(|(i, name)| (name.to_string(), i))
+                .collect::<Vec<_>>()
+        };
+
+        // Scans that found nothing are repeated.
+        assert!(cached_table(0x1000, || scan(&[])).is_empty());
+        let table = cached_table(0x1000, || scan(&["CSTaskImp", "CSFileImp"]));
+        assert_eq!(table.len(), 2);
+        assert_eq!(scans.get(), 2);
+
+        let table = cached_table(0x1000, || scan(&["CSTaskImp"]));
+        assert_eq!(table[1].0, "CSFileImp");
+        assert_eq!(scans.get(), 2);
+
+        // Other programs get their own table.
+        let other = cached_table(0x2000, || scan(&["WorldChrMan"]));
+        assert_eq!(other[0].0, "WorldChrMan");
+        assert_eq!(scans.get(), 3);
+    }
+}
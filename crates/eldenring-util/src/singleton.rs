@@ -1,17 +1,26 @@
 use dlrf::DLRFSingleton;
+use eldenring::fd4::FD4TaskData;
 use pelite::pattern;
 use pelite::pattern::Atom;
-use pelite::pe64::{Pe, Rva};
+use pelite::pe64::{Pe, Rva, Va};
 use std::collections;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::pin::Pin;
+use std::ptr::NonNull;
 use std::sync;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
 use thiserror::Error;
 
 use crate::program::Program;
 
 pub type SingletonMap = collections::HashMap<String, usize>;
-static SINGLETON_MAP: sync::OnceLock<SingletonMap> = sync::OnceLock::new();
+static SINGLETON_MAP: sync::OnceLock<Result<SingletonMap, SingletonMapError>> =
+    sync::OnceLock::new();
 
-#[derive(Error, Debug)]
+#[derive(Error, Debug, Clone)]
 pub enum SingletonMapError {
     #[error("Could not find section {0}.")]
     Section(&'static str),
@@ -25,6 +34,8 @@ pub enum LookupError {
     NotFound,
     #[error("Could not create the singleton map. {0}")]
     SingletonMapCreation(SingletonMapError),
+    #[error("Singleton {0} is already borrowed.")]
+    AlreadyBorrowed(&'static str),
 }
 
 /// Looks up instances of singleton instances by their name.
@@ -38,18 +49,307 @@ pub enum LookupError {
 ///  - Access to the singleton is exclusive (either by hooking or utilizing the task system).
 ///  - get_instance is not called multiple times such that it spawns multiple mutable references to the same singleton.
 pub unsafe fn get_instance<T: DLRFSingleton>() -> Result<Option<&'static mut T>, LookupError> {
-    let table = SINGLETON_MAP.get_or_init(|| {
-        build_singleton_table(&Program::current())
-            .map_err(LookupError::SingletonMapCreation)
-            .expect("Could not create singleton map")
-    });
+    Ok(instance_ptr::<T>()?.map(|mut instance| unsafe { instance.as_mut() }))
+}
+
+/// Reads the singleton's instance pointer without creating a reference to
+/// the instance.
+fn instance_ptr<T: DLRFSingleton>() -> Result<Option<NonNull<T>>, LookupError> {
+    singleton_static::<T>()?;
+    Ok(dlrf::instance_ptr::<T>())
+}
+
+/// Address of the static holding the singleton's instance pointer. The
+/// address is cached in the type's slot so only the first lookup has to go
+/// through the singleton map.
+fn singleton_static<T: DLRFSingleton>() -> Result<usize, LookupError> {
+    install_static_resolver();
+    if let Some(address) = dlrf::resolve_static::<T>() {
+        return Ok(address);
+    }
+
+    // Tell a singleton map that couldn't be built apart from a singleton
+    // that isn't in it.
+    singleton_map()?;
+    Err(LookupError::NotFound)
+}
+
+/// Builds the singleton map on first use.
+fn singleton_map() -> Result<&'static SingletonMap, LookupError> {
+    SINGLETON_MAP
+        .get_or_init(|| build_singleton_table(&Program::current()))
+        .as_ref()
+        .map_err(|e| LookupError::SingletonMapCreation(e.clone()))
+}
+
+/// Makes the `instance()` and `instance_mut()` accessors generated by
+/// `#[dlrf::singleton]` find the singleton statics through the singleton map.
+/// This happens on the first lookup through this module and in
+/// [`crate::system::init`], so it only needs to be called explicitly when
+/// neither has happened yet.
+pub fn install_static_resolver() {
+    dlrf::set_static_resolver(|name| singleton_map().ok()?.get(name).copied());
+}
+
+#[derive(Error, Debug)]
+pub enum WaitError {
+    #[error("Singleton was not instantiated before the timeout.")]
+    Timeout,
+    #[error("Singleton is not in the reflection table.")]
+    NotInReflectionTable,
+    #[error("Could not create the singleton map. {0}")]
+    SingletonMapCreation(SingletonMapError),
+    #[error("Singleton {0} is already borrowed.")]
+    AlreadyBorrowed(&'static str),
+}
+
+impl From<LookupError> for WaitError {
+    fn from(value: LookupError) -> Self {
+        match value {
+            LookupError::NotFound => Self::NotInReflectionTable,
+            LookupError::SingletonMapCreation(e) => Self::SingletonMapCreation(e),
+            LookupError::AlreadyBorrowed(name) => Self::AlreadyBorrowed(name),
+        }
+    }
+}
+
+/// Blocks the current thread until the singleton has been instantiated. The
+/// thread is parked in between checks with an increasing interval so this is
+/// cheap to call from a mod's init thread.
+///
+/// ```ignore
+/// wait_for_system_init(&Program::current(), Duration::MAX)?;
+/// let cs_task = wait_for_instance::<CSTaskImp>(Duration::from_secs(30))?;
+/// ```
+///
+/// # Safety
+/// Same as [`get_instance`]. Never call this from within a game task since
+/// the singleton might be waiting on that task to be constructed, use
+/// [`wait_for_instance_async`] there instead.
+pub unsafe fn wait_for_instance<T: DLRFSingleton>(
+    timeout: Duration,
+) -> Result<&'static mut T, WaitError> {
+    singleton_static::<T>()?;
+
+    let start = Instant::now();
+    let mut interval = Duration::from_millis(1);
+    loop {
+        if let Some(mut instance) = dlrf::instance_ptr::<T>() {
+            return Ok(unsafe { instance.as_mut() });
+        }
+
+        let remaining = timeout.saturating_sub(start.elapsed());
+        if remaining.is_zero() {
+            return Err(WaitError::Timeout);
+        }
+
+        std::thread::park_timeout(interval.min(remaining));
+        interval = (interval * 2).min(Duration::from_millis(100));
+    }
+}
+
+/// Resolves once the singleton has been instantiated. The static is checked
+/// every time the future is polled, which makes it suitable for executors
+/// that poll once per frame like the one in [`crate::executor`].
+///
+/// # Safety
+/// Same as [`get_instance`].
+pub unsafe fn wait_for_instance_async<T: DLRFSingleton + 'static>() -> WaitForInstance<T> {
+    WaitForInstance {
+        _marker: PhantomData,
+    }
+}
+
+pub struct WaitForInstance<T> {
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: DLRFSingleton + 'static> Future for WaitForInstance<T> {
+    type Output = Result<&'static mut T, WaitError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let instance = match instance_ptr::<T>() {
+            Ok(instance) => instance,
+            Err(e) => return Poll::Ready(Err(e.into())),
+        };
+
+        match instance {
+            Some(mut instance) => Poll::Ready(Ok(unsafe { instance.as_mut() })),
+            None => {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Shared access to a singleton for the duration of a task's execution.
+pub struct SingletonRef<'frame, T: DLRFSingleton> {
+    instance: &'frame T,
+}
+
+/// Exclusive access to a singleton for the duration of a task's execution.
+pub struct SingletonMut<'frame, T: DLRFSingleton> {
+    instance: &'frame mut T,
+}
+
+/// Borrows a singleton for the remainder of the task execution the task data
+/// was handed to. The guard borrows the task data, so it cannot outlive the
+/// task data it was created from.
+///
+/// The task data is only used for its lifetime. Nothing stops a caller from
+/// constructing its own `FD4TaskData` and borrowing outside of a task, which
+/// is why this is unsafe.
+///
+/// Debug builds track the outstanding guards per singleton and return
+/// [`LookupError::AlreadyBorrowed`] on conflicting borrows like a RefCell
+/// would, without ever creating the conflicting reference. Release builds
+/// skip the tracking entirely. References obtained through [`get_instance`]
+/// or the `instance()` accessors aren't tracked and can alias a guard.
+///
+/// ```ignore
+/// cs_task.run_recurring(
+///     |data: &FD4TaskData| {
+///         let Ok(Some(world_chr_man)) = (unsafe { borrow::<WorldChrMan>(data) }) else {
+///             return;
+///         };
+///         // ...
+///     },
+///     CSTaskGroupIndex::FrameBegin,
+/// );
+/// ```
+///
+/// # Safety
+/// Same as [`get_instance`], with the exception that aliasing between guards
+/// is checked in debug builds. The task data must be the one the game handed
+/// to the executing task.
+pub unsafe fn borrow<'frame, T: DLRFSingleton + 'static>(
+    _data: &'frame FD4TaskData,
+) -> Result<Option<SingletonRef<'frame, T>>, LookupError> {
+    let Some(instance) = instance_ptr::<T>()? else {
+        return Ok(None);
+    };
+
+    #[cfg(debug_assertions)]
+    borrow_tracking::acquire_shared(T::DLRF_NAME)?;
+
+    Ok(Some(SingletonRef {
+        instance: unsafe { instance.as_ref() },
+    }))
+}
 
-    let ptr = table
-        .get(T::DLRF_NAME)
-        .map(usize::to_owned)
-        .ok_or(LookupError::NotFound)?;
+/// Mutably borrows a singleton for the remainder of the task execution the
+/// task data was handed to. See [`borrow`].
+///
+/// # Safety
+/// Same as [`borrow`].
+pub unsafe fn borrow_mut<'frame, T: DLRFSingleton + 'static>(
+    _data: &'frame FD4TaskData,
+) -> Result<Option<SingletonMut<'frame, T>>, LookupError> {
+    let Some(mut instance) = instance_ptr::<T>()? else {
+        return Ok(None);
+    };
+
+    #[cfg(debug_assertions)]
+    borrow_tracking::acquire_exclusive(T::DLRF_NAME)?;
+
+    Ok(Some(SingletonMut {
+        instance: unsafe { instance.as_mut() },
+    }))
+}
+
+impl<T: DLRFSingleton> Deref for SingletonRef<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.instance
+    }
+}
+
+impl<T: DLRFSingleton> Deref for SingletonMut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.instance
+    }
+}
+
+impl<T: DLRFSingleton> DerefMut for SingletonMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.instance
+    }
+}
+
+#[cfg(debug_assertions)]
+impl<T: DLRFSingleton> Drop for SingletonRef<'_, T> {
+    fn drop(&mut self) {
+        borrow_tracking::release(T::DLRF_NAME);
+    }
+}
+
+#[cfg(debug_assertions)]
+impl<T: DLRFSingleton> Drop for SingletonMut<'_, T> {
+    fn drop(&mut self) {
+        borrow_tracking::release(T::DLRF_NAME);
+    }
+}
+
+#[cfg(debug_assertions)]
+mod borrow_tracking {
+    use std::collections::HashMap;
+    use std::sync::{LazyLock, Mutex, MutexGuard, PoisonError};
+
+    use super::LookupError;
+
+    /// Outstanding guards per singleton. Positive values count shared guards,
+    /// -1 marks an exclusive guard.
+    static BORROWS: LazyLock<Mutex<HashMap<&'static str, isize>>> = LazyLock::new(Default::default);
+
+    // The counts are updated in one go so a panic elsewhere can't leave them
+    // in a bad state.
+    fn borrows() -> MutexGuard<'static, HashMap<&'static str, isize>> {
+        BORROWS.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub(super) fn acquire_shared(name: &'static str) -> Result<(), LookupError> {
+        let mut borrows = borrows();
+        let count = borrows.entry(name).or_default();
+        if *count < 0 {
+            tracing::error!("{name} is already mutably borrowed");
+            return Err(LookupError::AlreadyBorrowed(name));
+        }
 
-    unsafe { Ok((*(ptr as *const *mut T)).as_mut()) }
+        *count += 1;
+        Ok(())
+    }
+
+    pub(super) fn acquire_exclusive(name: &'static str) -> Result<(), LookupError> {
+        let mut borrows = borrows();
+        let count = borrows.entry(name).or_default();
+        if *count != 0 {
+            tracing::error!("{name} is already borrowed");
+            return Err(LookupError::AlreadyBorrowed(name));
+        }
+
+        *count = -1;
+        Ok(())
+    }
+
+    pub(super) fn release(name: &'static str) {
+        let mut borrows = borrows();
+        if let Some(count) = borrows.get_mut(name) {
+            if *count < 0 {
+                *count = 0;
+            } else if *count > 0 {
+                *count -= 1;
+            }
+        }
+    }
+
+    #[cfg(test)]
+    pub(super) fn count(name: &'static str) -> isize {
+        borrows().get(name).copied().unwrap_or_default()
+    }
 }
 
 // MOV REG, [MEM]
@@ -67,13 +367,40 @@ const NULL_CHECK_PATTERN: &[Atom] = pattern!(
     "
 );
 
-/// Builds a table of all the singletons. It does so by looking for null checks
-/// in the game by using an instance pattern. It then cycles over all
-/// candidates and vets the involved pointers. We expect a pointer to the
+// The three name getter shapes below are what get_singleton_name is expected
+// to compile down to. They have only been checked against the fixture images
+// in the tests, not against a game executable, so a getter that doesn't match
+// any of them makes its singleton drop out of the static table.
+
+// LEA RAX, [name]
+// RET
+const NAME_CONSTANT_GETTER_PATTERN: &[Atom] = pattern!("48 8d 05 $ { ' } c3");
+
+// MOV RAX, [RCX + disp8]
+// RET
+const NAME_FIELD_GETTER_PATTERN: &[Atom] = pattern!("48 8b 41 u1 c3");
+
+// MOV RAX, [RCX]
+// JMP [RAX + disp8]
+const NAME_VIRTUAL_GETTER_PATTERN: &[Atom] = pattern!("48 8b 01 (48 ff 60 | ff 60) u1");
+
+// JMP target
+const THUNK_PATTERN: &[Atom] = pattern!("e9 $ { ' }");
+
+/// Singleton candidate as discovered by the null check pattern.
+struct SingletonCandidate {
+    static_rva: Rva,
+    metadata_rva: Rva,
+    get_singleton_name_rva: Rva,
+}
+
+/// Finds all null checks against singleton statics and yields the ones for
+/// which the involved pointers are plausible. We expect a pointer to the
 /// instance's static, a pointer to the reflection metadata and a pointer to
-/// the get_singleton_name fn. Once all checks out we call get_singleton_name
-/// with the metadata to obtain the instance's type name.
-pub fn build_singleton_table(program: &Program) -> Result<SingletonMap, SingletonMapError> {
+/// the get_singleton_name fn.
+fn singleton_candidates<'a>(
+    program: &'a Program,
+) -> Result<impl Iterator<Item = SingletonCandidate> + 'a, SingletonMapError> {
     let text_range = program
         .section_headers()
         .by_name(".text")
@@ -88,24 +415,40 @@ pub fn build_singleton_table(program: &Program) -> Result<SingletonMap, Singleto
 
     let mut matches = program.scanner().matches_code(NULL_CHECK_PATTERN);
     let mut captures: [Rva; 4] = [Rva::default(); 4];
-    let mut results: SingletonMap = Default::default();
 
-    while matches.next(&mut captures) {
-        let static_rva = captures[1];
-        let metadata_rva = captures[2];
-        let get_reflection_name_rva = captures[3];
+    Ok(std::iter::from_fn(move || loop {
+        if !matches.next(&mut captures) {
+            return None;
+        }
+
+        let candidate = SingletonCandidate {
+            static_rva: captures[1],
+            metadata_rva: captures[2],
+            get_singleton_name_rva: captures[3],
+        };
 
         // Check if all RVAs are plausible.
-        if !data_range.contains(&static_rva)
-            || !data_range.contains(&metadata_rva)
-            || !text_range.contains(&get_reflection_name_rva)
+        if data_range.contains(&candidate.static_rva)
+            && data_range.contains(&candidate.metadata_rva)
+            && text_range.contains(&candidate.get_singleton_name_rva)
         {
-            continue;
+            return Some(candidate);
         }
+    }))
+}
+
+/// Builds a table of all the singletons. It does so by looking for null checks
+/// in the game by using an instance pattern. It then cycles over all
+/// candidates and vets the involved pointers. Once all checks out we call
+/// get_singleton_name with the metadata to obtain the instance's type name.
+pub fn build_singleton_table(program: &Program) -> Result<SingletonMap, SingletonMapError> {
+    let mut results: SingletonMap = Default::default();
 
-        let metadata = program.rva_to_va(metadata_rva).unwrap();
-        let get_singleton_name: extern "C" fn(u64) -> *const i8 =
-            unsafe { std::mem::transmute(program.rva_to_va(get_reflection_name_rva).unwrap()) };
+    for candidate in singleton_candidates(program)? {
+        let metadata = program.rva_to_va(candidate.metadata_rva).unwrap();
+        let get_singleton_name: extern "C" fn(u64) -> *const i8 = unsafe {
+            std::mem::transmute(program.rva_to_va(candidate.get_singleton_name_rva).unwrap())
+        };
 
         let cstr = unsafe { std::ffi::CStr::from_ptr(get_singleton_name(metadata)) };
         let singleton_name = cstr
@@ -113,9 +456,486 @@ pub fn build_singleton_table(program: &Program) -> Result<SingletonMap, Singleto
             .map_err(|_| SingletonMapError::MalformedName)?
             .to_string();
 
-        let singleton_va = program.rva_to_va(static_rva).unwrap();
+        let singleton_va = program.rva_to_va(candidate.static_rva).unwrap();
         results.insert(singleton_name, singleton_va as usize);
     }
 
     Ok(results)
 }
+
+pub type StaticSingletonMap = collections::HashMap<String, Rva>;
+
+/// Result of [`build_singleton_table_static`].
+#[derive(Debug, Default)]
+pub struct StaticSingletonTable {
+    pub singletons: StaticSingletonMap,
+    /// RVAs of the statics whose singleton name could not be recovered.
+    pub skipped: Vec<Rva>,
+}
+
+/// Builds a table of all the singletons without executing any of the game's
+/// code, mapping the singleton names to the RVAs of their statics. Instead of
+/// calling get_singleton_name this decodes the function and reads the name
+/// from the reflection metadata the same way it would. This makes it usable
+/// on a PE file loaded from disk.
+///
+/// Candidates for which the name could not be recovered are skipped and
+/// listed in [`StaticSingletonTable::skipped`], the table is incomplete if
+/// there are any.
+pub fn build_singleton_table_static(
+    program: &Program,
+) -> Result<StaticSingletonTable, SingletonMapError> {
+    let mut results = StaticSingletonTable::default();
+
+    for candidate in singleton_candidates(program)? {
+        let singleton_name = read_runtime_class_name(
+            program,
+            candidate.get_singleton_name_rva,
+            candidate.metadata_rva,
+        );
+
+        match singleton_name {
+            Some(Ok(singleton_name)) => {
+                results
+                    .singletons
+                    .insert(singleton_name, candidate.static_rva);
+            }
+            Some(Err(_)) => {
+                tracing::debug!(
+                    "Skipping malformed name for singleton static at {:#x}",
+                    candidate.static_rva
+                );
+                results.skipped.push(candidate.static_rva);
+            }
+            None => {
+                tracing::debug!(
+                    "Could not statically resolve name for singleton static at {:#x}",
+                    candidate.static_rva
+                );
+                results.skipped.push(candidate.static_rva);
+            }
+        }
+    }
+
+    // The same static is null checked in many places.
+    results.skipped.sort_unstable();
+    results.skipped.dedup();
+    if !results.skipped.is_empty() {
+        tracing::warn!(
+            "Could not resolve the names of {} singleton statics, the singleton table is incomplete",
+            results.skipped.len()
+        );
+    }
+
+    Ok(results)
+}
+
+/// Statically evaluates a name getter against the runtime class metadata at
+/// `metadata_rva`. The getter either returns a constant, reads a field from
+/// the metadata or dispatches to a getter in the metadata's vftable.
+fn read_runtime_class_name(
+    program: &Program,
+    getter_rva: Rva,
+    metadata_rva: Rva,
+) -> Option<Result<String, SingletonMapError>> {
+    let scanner = program.scanner();
+    let mut captures: [Rva; 2] = [Rva::default(); 2];
+
+    // Follow any thunks leading up to the actual getter.
+    let mut getter_rva = getter_rva;
+    for _ in 0..4 {
+        if !scanner.exec(getter_rva, THUNK_PATTERN, &mut captures) {
+            break;
+        }
+        getter_rva = captures[1];
+    }
+
+    let name_rva = if scanner.exec(getter_rva, NAME_CONSTANT_GETTER_PATTERN, &mut captures) {
+        captures[1]
+    } else if scanner.exec(getter_rva, NAME_FIELD_GETTER_PATTERN, &mut captures) {
+        let name_va = program.derva::<Va>(metadata_rva + captures[1]).ok()?;
+        program.va_to_rva(*name_va).ok()?
+    } else if scanner.exec(getter_rva, NAME_VIRTUAL_GETTER_PATTERN, &mut captures) {
+        let vftable_va = program.derva::<Va>(metadata_rva).ok()?;
+        let vftable_rva = program.va_to_rva(*vftable_va).ok()?;
+        let slot_va = program.derva::<Va>(vftable_rva + captures[1]).ok()?;
+        let slot_rva = program.va_to_rva(*slot_va).ok()?;
+
+        // Prevent recursing into another vftable dispatch.
+        if scanner.exec(slot_rva, NAME_VIRTUAL_GETTER_PATTERN, &mut captures) {
+            return None;
+        }
+
+        return read_runtime_class_name(program, slot_rva, metadata_rva);
+    } else {
+        return None;
+    };
+
+    let name = program.derva_c_str(name_rva).ok()?;
+    Some(
+        name.to_str()
+            .map(str::to_string)
+            .map_err(|_| SingletonMapError::MalformedName),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use pelite::pe64::Rva;
+
+    use super::{
+        build_singleton_table_static, LookupError, SingletonMapError, StaticSingletonTable,
+        WaitError,
+    };
+    use crate::program::fixture::{TestImage, IMAGE_BASE};
+
+    /// Bump allocates code and data in a test image.
+    struct Assembler {
+        image: TestImage,
+        code: Rva,
+        data: Rva,
+    }
+
+    impl Assembler {
+        fn new() -> Self {
+            let image = TestImage::new(&[".text", ".data"]);
+            let code = image.section(".text");
+            let data = image.section(".data");
+
+            Self { image, code, data }
+        }
+
+        fn code(&mut self, bytes: &[u8]) -> Rva {
+            let rva = self.code;
+            self.image.write(rva, bytes);
+            self.code += bytes.len() as Rva;
+            rva
+        }
+
+        /// Emits an instruction that ends in a RIP-relative displacement to
+        /// `target`, followed by `rest`.
+        fn code_rel32(&mut self, opcode: &[u8], target: Rva, rest: &[u8]) -> Rva {
+            let next = self.code + opcode.len() as Rva + 4;
+            let displacement = target.wrapping_sub(next).to_le_bytes();
+            self.code(&[opcode, &displacement, rest].concat())
+        }
+
+        fn data(&mut self, bytes: &[u8]) -> Rva {
+            let rva = self.data;
+            self.image.write(rva, bytes);
+            self.data = (self.data + bytes.len() as Rva + 7) & !7;
+            rva
+        }
+
+        fn va(target: Rva) -> [u8; 8] {
+            (IMAGE_BASE + target as u64).to_le_bytes()
+        }
+
+        /// LEA RAX, [name]
+        /// RET
+        fn constant_getter(&mut self, name: &str) -> Rva {
+            self.raw_constant_getter(name.as_bytes())
+        }
+
+        fn raw_constant_getter(&mut self, name: &[u8]) -> Rva {
+            let name = self.data(&[name, b"\0"].concat());
+            self.code_rel32(&[0x48, 0x8d, 0x05], name, &[0xc3])
+        }
+
+        /// Emits the null check of the static that the scan starts from.
+        fn singleton(&mut self, getter: Rva, metadata: Rva) -> Rva {
+            let static_rva = self.data(&[0; 8]);
+
+            // MOV RAX, [static]; TEST RAX, RAX; JNZ +2e
+            self.code_rel32(
+                &[0x48, 0x8b, 0x05],
+                static_rva,
+                &[0x48, 0x85, 0xc0, 0x75, 0x2e],
+            );
+            // LEA RCX, [metadata]
+            self.code_rel32(&[0x48, 0x8d, 0x0d], metadata, &[]);
+            // CALL getter
+            self.code_rel32(&[0xe8], getter, &[]);
+
+            static_rva
+        }
+
+        fn table(&self) -> StaticSingletonTable {
+            build_singleton_table_static(&self.image.program()).unwrap()
+        }
+    }
+
+    #[test]
+    fn constant_getter() {
+        let mut asm = Assembler::new();
+        let getter = asm.constant_getter("CSWorldGeomMan");
+        let metadata = asm.data(&[0; 8]);
+        let static_rva = asm.singleton(getter, metadata);
+
+        let table = asm.table().singletons;
+        assert_eq!(table.len(), 1);
+        assert_eq!(table.get("CSWorldGeomMan"), Some(&static_rva));
+    }
+
+    #[test]
+    fn field_getter() {
+        let mut asm = Assembler::new();
+        let name = asm.data(b"WorldChrMan\0");
+        let metadata = asm.data(&[[0; 8], Assembler::va(name)].concat());
+        // MOV RAX, [RCX + 8]; RET
+        let getter = asm.code(&[0x48, 0x8b, 0x41, 0x08, 0xc3]);
+        let static_rva = asm.singleton(getter, metadata);
+
+        assert_eq!(asm.table().singletons.get("WorldChrMan"), Some(&static_rva));
+    }
+
+    /// Emits a metadata object whose vftable holds a constant getter for
+    /// `name` in its third slot.
+    fn virtual_metadata(asm: &mut Assembler, name: &str) -> Rva {
+        let slot = asm.constant_getter(name);
+        let vftable = asm.data(&[[0; 8], [0; 8], Assembler::va(slot)].concat());
+        asm.data(&Assembler::va(vftable))
+    }
+
+    #[test]
+    fn virtual_getter() {
+        let mut asm = Assembler::new();
+        let metadata = virtual_metadata(&mut asm, "CSFD4VirtualMemoryFlag");
+
+        // MOV RAX, [RCX]; JMP [RAX + 10]
+        let getter = asm.code(&[0x48, 0x8b, 0x01, 0xff, 0x60, 0x10]);
+        let static_rva = asm.singleton(getter, metadata);
+
+        let table = asm.table().singletons;
+        assert_eq!(table.len(), 1);
+        assert_eq!(table.get("CSFD4VirtualMemoryFlag"), Some(&static_rva));
+    }
+
+    #[test]
+    fn rex_virtual_getter() {
+        let mut asm = Assembler::new();
+        let metadata = virtual_metadata(&mut asm, "CSNetMan");
+
+        // MOV RAX, [RCX]; REX.W JMP [RAX + 10]
+        let getter = asm.code(&[0x48, 0x8b, 0x01, 0x48, 0xff, 0x60, 0x10]);
+        let static_rva = asm.singleton(getter, metadata);
+
+        let table = asm.table().singletons;
+        assert_eq!(table.len(), 1);
+        assert_eq!(table.get("CSNetMan"), Some(&static_rva));
+    }
+
+    #[test]
+    fn repeated_null_checks() {
+        let mut asm = Assembler::new();
+        let getter = asm.constant_getter("CSTaskImp");
+        let metadata = asm.data(&[0; 8]);
+        let static_rva = asm.singleton(getter, metadata);
+
+        // Another null check of the same static elsewhere in the code.
+        asm.code_rel32(
+            &[0x48, 0x8b, 0x05],
+            static_rva,
+            &[0x48, 0x85, 0xc0, 0x75, 0x2e],
+        );
+        asm.code_rel32(&[0x48, 0x8d, 0x0d], metadata, &[]);
+        asm.code_rel32(&[0xe8], getter, &[]);
+
+        let table = asm.table().singletons;
+        assert_eq!(table.len(), 1);
+        assert_eq!(table.get("CSTaskImp"), Some(&static_rva));
+    }
+
+    #[test]
+    fn duplicate_names_keep_the_last_static() {
+        let mut asm = Assembler::new();
+        let getter = asm.constant_getter("CSTaskImp");
+        let metadata = asm.data(&[0; 8]);
+        asm.singleton(getter, metadata);
+        let static_rva = asm.singleton(getter, metadata);
+
+        let table = asm.table().singletons;
+        assert_eq!(table.len(), 1);
+        assert_eq!(table.get("CSTaskImp"), Some(&static_rva));
+    }
+
+    #[test]
+    fn thunked_getter() {
+        let mut asm = Assembler::new();
+        let getter = asm.constant_getter("CSFeManImp");
+        let thunk = asm.code_rel32(&[0xe9], getter, &[]);
+        let double_thunk = asm.code_rel32(&[0xe9], thunk, &[]);
+        let metadata = asm.data(&[0; 8]);
+        let static_rva = asm.singleton(double_thunk, metadata);
+
+        assert_eq!(asm.table().singletons.get("CSFeManImp"), Some(&static_rva));
+    }
+
+    #[test]
+    fn unknown_getters_are_skipped() {
+        let mut asm = Assembler::new();
+
+        // XOR EAX, EAX; RET
+        let unknown = asm.code(&[0x31, 0xc0, 0xc3]);
+        let metadata = asm.data(&[0; 8]);
+        let unknown_static = asm.singleton(unknown, metadata);
+
+        // Virtual getter whose slot dispatches through the vftable again.
+        let nested = asm.code(&[0x48, 0x8b, 0x01, 0xff, 0x60, 0x00]);
+        let vftable = asm.data(&Assembler::va(nested));
+        let metadata = asm.data(&Assembler::va(vftable));
+        let getter = asm.code(&[0x48, 0x8b, 0x01, 0xff, 0x60, 0x00]);
+        let nested_static = asm.singleton(getter, metadata);
+
+        let known = asm.constant_getter("CSEventFlagMan");
+        let metadata = asm.data(&[0; 8]);
+        let static_rva = asm.singleton(known, metadata);
+
+        let table = asm.table();
+        assert_eq!(table.singletons.len(), 1);
+        assert_eq!(table.singletons.get("CSEventFlagMan"), Some(&static_rva));
+        assert_eq!(table.skipped, [unknown_static, nested_static]);
+    }
+
+    #[test]
+    fn malformed_names_are_skipped() {
+        let mut asm = Assembler::new();
+        let malformed = asm.raw_constant_getter(b"CSMenu\xffMan");
+        let metadata = asm.data(&[0; 8]);
+        let malformed_static = asm.singleton(malformed, metadata);
+
+        let known = asm.constant_getter("CSMenuManImp");
+        let static_rva = asm.singleton(known, metadata);
+
+        let table = asm.table();
+        assert_eq!(table.singletons.len(), 1);
+        assert_eq!(table.singletons.get("CSMenuManImp"), Some(&static_rva));
+        assert_eq!(table.skipped, [malformed_static]);
+    }
+
+    #[test]
+    fn implausible_candidates_are_skipped() {
+        let mut asm = Assembler::new();
+        let getter = asm.constant_getter("CSSessionManager");
+
+        // Metadata pointing into .text rather than .data.
+        asm.singleton(getter, getter);
+
+        assert!(asm.table().singletons.is_empty());
+    }
+
+    #[test]
+    fn wait_errors_keep_lookup_cause() {
+        assert!(matches!(
+            WaitError::from(LookupError::NotFound),
+            WaitError::NotInReflectionTable
+        ));
+        assert!(matches!(
+            WaitError::from(LookupError::SingletonMapCreation(
+                SingletonMapError::MalformedName
+            )),
+            WaitError::SingletonMapCreation(SingletonMapError::MalformedName)
+        ));
+        assert!(matches!(
+            WaitError::from(LookupError::AlreadyBorrowed("CSTaskImp")),
+            WaitError::AlreadyBorrowed("CSTaskImp")
+        ));
+    }
+
+    /// Every test tracks its own name since the borrow state is shared.
+    #[cfg(debug_assertions)]
+    mod borrows {
+        use dlrf::{DLRFSingleton, SingletonSlot};
+
+        use crate::singleton::borrow_tracking::{
+            acquire_exclusive, acquire_shared, count, release,
+        };
+        use crate::singleton::{LookupError, SingletonMut, SingletonRef};
+
+        #[test]
+        fn shared_borrows() {
+            const NAME: &str = "SharedBorrows";
+
+            acquire_shared(NAME).unwrap();
+            acquire_shared(NAME).unwrap();
+            assert_eq!(count(NAME), 2);
+
+            assert!(matches!(
+                acquire_exclusive(NAME),
+                Err(LookupError::AlreadyBorrowed(NAME))
+            ));
+            assert_eq!(count(NAME), 2);
+
+            release(NAME);
+            assert!(acquire_exclusive(NAME).is_err());
+            release(NAME);
+            assert_eq!(count(NAME), 0);
+            acquire_exclusive(NAME).unwrap();
+        }
+
+        #[test]
+        fn exclusive_borrows() {
+            const NAME: &str = "ExclusiveBorrows";
+
+            acquire_exclusive(NAME).unwrap();
+            assert_eq!(count(NAME), -1);
+            assert!(matches!(
+                acquire_shared(NAME),
+                Err(LookupError::AlreadyBorrowed(NAME))
+            ));
+            assert!(acquire_exclusive(NAME).is_err());
+            assert_eq!(count(NAME), -1);
+
+            release(NAME);
+            assert_eq!(count(NAME), 0);
+            acquire_shared(NAME).unwrap();
+            release(NAME);
+
+            // Releasing without a borrow leaves the state alone.
+            release(NAME);
+            release("NeverBorrowed");
+            assert_eq!(count(NAME), 0);
+            assert_eq!(count("NeverBorrowed"), 0);
+        }
+
+        struct GuardedSingleton(u32);
+
+        impl DLRFSingleton for GuardedSingleton {
+            const DLRF_NAME: &'static str = "GuardedSingleton";
+
+            fn static_slot() -> &'static SingletonSlot {
+                static SLOT: SingletonSlot = SingletonSlot::new();
+                &SLOT
+            }
+        }
+
+        #[test]
+        fn guards_release_on_drop() {
+            const NAME: &str = GuardedSingleton::DLRF_NAME;
+
+            let mut instance = GuardedSingleton(0);
+
+            acquire_exclusive(NAME).unwrap();
+            let mut guard = SingletonMut {
+                instance: &mut instance,
+            };
+            guard.0 += 1;
+            drop(guard);
+            assert_eq!(count(NAME), 0);
+
+            acquire_shared(NAME).unwrap();
+            acquire_shared(NAME).unwrap();
+            let first = SingletonRef {
+                instance: &instance,
+            };
+            let second = SingletonRef {
+                instance: &instance,
+            };
+            assert_eq!(first.0 + second.0, 2);
+            drop(first);
+            assert_eq!(count(NAME), 1);
+            drop(second);
+            assert_eq!(count(NAME), 0);
+        }
+    }
+}
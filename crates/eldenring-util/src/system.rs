@@ -1,14 +1,17 @@
 /// OG comes from Dasaav
 /// https://github.com/Dasaav-dsv/libER/blob/main/source/dantelion2/system.cpp
 use std::sync::atomic::{AtomicPtr, Ordering};
+use std::sync::OnceLock;
 use std::time::{Duration, Instant};
 
+use eldenring::fd4::FD4BasicHashString;
 use pelite::pattern;
 use pelite::pattern::Atom;
 use pelite::pe64::{Pe, Rva};
 use thiserror::Error;
 
 use crate::program::Program;
+use crate::rtti::find_rtti_classes;
 
 // WinMain -> SethInstance
 // used to set global hInstance later used by CSWindow
@@ -26,16 +29,25 @@ const GLOBAL_SET_HINSTANCE_PATTERN: &[Atom] = pattern!(
 
 static GLOBAL_HINSTANCE: AtomicPtr<usize> = AtomicPtr::new(0x0 as _);
 
+const HASH_STRING_CLASS_NAME: &str = "FD4::FD4BasicHashString";
+
+static HASH_STRING_VFTABLE: OnceLock<usize> = OnceLock::new();
+
 #[derive(Error, Debug)]
 pub enum SystemInitError {
     #[error("System initialization timed out")]
     Timeout,
     #[error("Could not translate RVA to VA")]
     InvalidRva,
+    #[error("Could not find the {HASH_STRING_CLASS_NAME} vftable")]
+    HashStringVftableNotFound,
 }
 
 /// Wait for the system to finish initializing by waiting a global hInstance to be populated for CSWindow.
 /// This happens after the CRT init and after duplicate instance checks.
+///
+/// Only waits, call [`init`] afterwards to set up the lookups that depend on
+/// the game's statics.
 pub fn wait_for_system_init(module: &Program, timeout: Duration) -> Result<(), SystemInitError> {
     if std::ptr::eq(GLOBAL_HINSTANCE.load(Ordering::Relaxed), 0x0 as _) {
         let mut captures = [Rva::default(); 2];
@@ -60,3 +72,33 @@ pub fn wait_for_system_init(module: &Program, timeout: Duration) -> Result<(), S
 
     Ok(())
 }
+
+/// Sets up the parts of this crate that need the game's statics:
+///  - Installs the resolver behind the singleton `instance()` accessors, see
+///    [`crate::singleton::install_static_resolver`].
+///  - Looks up the `FD4::FD4BasicHashString` vftable through RTTI for the hash
+///    strings created by [`FD4BasicHashString::new_in`]. This scans the
+///    entire image once.
+///
+/// Call this after [`wait_for_system_init`]. Once it succeeded calling it
+/// again does nothing.
+pub fn init(module: &Program) -> Result<(), SystemInitError> {
+    crate::singleton::install_static_resolver();
+    install_hash_string_vftable(module)
+}
+
+/// Makes the hash strings created through [`FD4BasicHashString::new_in`] use
+/// the game's vftable. Creating hash strings fails until this succeeded.
+fn install_hash_string_vftable(module: &Program) -> Result<(), SystemInitError> {
+    if HASH_STRING_VFTABLE.get().is_some() {
+        return Ok(());
+    }
+
+    let vftable = find_rtti_classes(module)
+        .find(|c| c.name == HASH_STRING_CLASS_NAME && c.vftable_offset() == Some(0))
+        .and_then(|c| module.rva_to_va(c.vftable).ok())
+        .ok_or(SystemInitError::HashStringVftableNotFound)?;
+
+    FD4BasicHashString::set_vftable(*HASH_STRING_VFTABLE.get_or_init(|| vftable as usize));
+    Ok(())
+}
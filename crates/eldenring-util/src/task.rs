@@ -1,17 +1,19 @@
 use std::{
     cell::UnsafeCell,
+    ffi::c_char,
+    ptr::NonNull,
     sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc,
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, OnceLock, PoisonError,
     },
+    time::Duration,
 };
 
 use crate::program::Program;
 use eldenring::fd4::FD4TaskData;
 use eldenring::{
     cs::{CSTaskGroupIndex, CSTaskImp},
-    dlrf::DLRuntimeClass,
-    fd4::FD4TaskBaseVmt,
+    dlrf::{DLRuntimeClass, DLRuntimeClassVmt},
 };
 use pelite::pe64::Pe;
 use pelite::{pattern, pattern::Atom};
@@ -37,13 +39,165 @@ static REGISTER_TASK_VA: LazyLock<u64> = LazyLock::new(|| {
         .expect("Call target for REGISTER_TASK_PATTERN was not in exe")
 });
 
+/// Registered tasks whose closure finished or got cancelled, along with the
+/// index of their task group. The game's task unregister fn hasn't been bound
+/// yet so tasks stay registered for as long as the game runs. New
+/// registrations in the same task group reuse these tasks instead of
+/// registering another one.
+static IDLE_TASKS: Mutex<Vec<(u32, Arc<RecurringTask>)>> = Mutex::new(Vec::new());
+
+/// Delay before a task gets to run, either in game time or in frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskDelay {
+    Time(Duration),
+    Frames(u32),
+}
+
+impl From<Duration> for TaskDelay {
+    fn from(value: Duration) -> Self {
+        Self::Time(value)
+    }
+}
+
+impl From<u32> for TaskDelay {
+    fn from(value: u32) -> Self {
+        Self::Frames(value)
+    }
+}
+
+impl TaskDelay {
+    /// Advances the delay by a single execution of the task group in which
+    /// `delta` game time passed. Returns whether the delay has passed.
+    fn advance(&mut self, delta: Duration) -> bool {
+        match self {
+            TaskDelay::Frames(0) => true,
+            // Frames in which game time stands still don't count.
+            TaskDelay::Frames(_) if delta.is_zero() => false,
+            TaskDelay::Frames(frames) => {
+                *frames -= 1;
+                false
+            }
+            TaskDelay::Time(time) => {
+                *time = time.saturating_sub(delta);
+                time.is_zero()
+            }
+        }
+    }
+}
+
+/// Game time that passed since the previous execution of the task group.
+fn delta_time(data: &FD4TaskData) -> Duration {
+    Duration::try_from_secs_f32(data.delta_time.time).unwrap_or_default()
+}
+
+/// Task body that runs the closure once after the delay has passed. Returns
+/// whether the task should keep running.
+fn delayed<F: FnOnce(&FD4TaskData)>(
+    delay: TaskDelay,
+    execute: F,
+) -> impl FnMut(&FD4TaskData) -> bool {
+    let mut execute = Some(execute);
+    let mut remaining = delay;
+
+    move |data: &FD4TaskData| {
+        if !remaining.advance(delta_time(data)) {
+            return true;
+        }
+
+        if let Some(execute) = execute.take() {
+            execute(data);
+        }
+        false
+    }
+}
+
+/// Task body that runs the closure for the specified amount of executions.
+/// Like [`TaskDelay::Frames`], executions in which no game time passed are
+/// skipped. Returns whether the task should keep running.
+fn finite<F: FnMut(&FD4TaskData)>(frames: u32, mut execute: F) -> impl FnMut(&FD4TaskData) -> bool {
+    let mut remaining = frames;
+
+    move |data: &FD4TaskData| {
+        if remaining == 0 {
+            return false;
+        }
+
+        if delta_time(data).is_zero() {
+            return true;
+        }
+
+        execute(data);
+        remaining -= 1;
+        remaining > 0
+    }
+}
+
+/// Task body that runs the closure every time the interval has passed.
+fn interval<F: FnMut(&FD4TaskData)>(
+    interval: Duration,
+    mut execute: F,
+) -> impl FnMut(&FD4TaskData) {
+    let mut elapsed = Duration::ZERO;
+
+    move |data: &FD4TaskData| {
+        elapsed += delta_time(data);
+        if elapsed < interval {
+            return;
+        }
+
+        // Skip intervals we've missed instead of running the closure
+        // several times in a row.
+        elapsed = Duration::from_nanos((elapsed.as_nanos() % interval.as_nanos().max(1)) as u64);
+        execute(data);
+    }
+}
+
 pub trait CSTaskImpExt {
     /// Registers the given closure as a task to the games task runtime.
+    ///
+    /// All fns registering tasks return a [RecurringTaskHandle]. Dropping
+    /// the handle leaves the task running, the task only stops once its
+    /// closure is done or the handle is used to cancel it.
     fn run_recurring<T: Into<RecurringTask>>(
         &self,
         execute: T,
         group: CSTaskGroupIndex,
     ) -> RecurringTaskHandle;
+
+    /// Runs the closure once during the next execution of the task group.
+    fn run_once<F: FnOnce(&FD4TaskData) + 'static + Send>(
+        &self,
+        group: CSTaskGroupIndex,
+        execute: F,
+    ) -> RecurringTaskHandle;
+
+    /// Runs the closure once after the delay has passed. Delays only progress
+    /// while game time advances: time based delays are measured in game time
+    /// and frame based delays skip executions of the task group in which no
+    /// game time passed.
+    fn run_after<F: FnOnce(&FD4TaskData) + 'static + Send>(
+        &self,
+        delay: impl Into<TaskDelay>,
+        group: CSTaskGroupIndex,
+        execute: F,
+    ) -> RecurringTaskHandle;
+
+    /// Runs the closure every frame for the specified amount of frames.
+    /// Frames in which no game time passed don't count and skip the closure.
+    fn run_for<F: FnMut(&FD4TaskData) + 'static + Send>(
+        &self,
+        frames: u32,
+        group: CSTaskGroupIndex,
+        execute: F,
+    ) -> RecurringTaskHandle;
+
+    /// Runs the closure every time the interval has passed in game time.
+    fn run_every<F: FnMut(&FD4TaskData) + 'static + Send>(
+        &self,
+        interval: Duration,
+        group: CSTaskGroupIndex,
+        execute: F,
+    ) -> RecurringTaskHandle;
 }
 
 impl CSTaskImpExt for CSTaskImp {
@@ -52,79 +206,281 @@ impl CSTaskImpExt for CSTaskImp {
         task: T,
         group: CSTaskGroupIndex,
     ) -> RecurringTaskHandle {
-        let register_task: extern "C" fn(&CSTaskImp, CSTaskGroupIndex, &RecurringTask) =
-            unsafe { std::mem::transmute(*REGISTER_TASK_VA) };
-
-        #[allow(clippy::arc_with_non_send_sync)]
-        let task: Arc<RecurringTask> = Arc::new(task.into());
-        // SAFETY: we hold a unique reference to the contents of `arc`
-        unsafe {
-            *task.self_ref.get() = Some(task.clone());
-        }
+        register_recurring(self, task.into(), group)
+    }
 
-        register_task(self, group, task.as_ref());
+    fn run_once<F: FnOnce(&FD4TaskData) + 'static + Send>(
+        &self,
+        group: CSTaskGroupIndex,
+        execute: F,
+    ) -> RecurringTaskHandle {
+        self.run_after(TaskDelay::Frames(0), group, execute)
+    }
 
-        RecurringTaskHandle { _task: task }
+    fn run_after<F: FnOnce(&FD4TaskData) + 'static + Send>(
+        &self,
+        delay: impl Into<TaskDelay>,
+        group: CSTaskGroupIndex,
+        execute: F,
+    ) -> RecurringTaskHandle {
+        let task = RecurringTask::new_finite(delayed(delay.into(), execute));
+        self.run_recurring(task, group)
+    }
+
+    fn run_for<F: FnMut(&FD4TaskData) + 'static + Send>(
+        &self,
+        frames: u32,
+        group: CSTaskGroupIndex,
+        execute: F,
+    ) -> RecurringTaskHandle {
+        let task = RecurringTask::new_finite(finite(frames, execute));
+        self.run_recurring(task, group)
+    }
+
+    fn run_every<F: FnMut(&FD4TaskData) + 'static + Send>(
+        &self,
+        interval: Duration,
+        group: CSTaskGroupIndex,
+        execute: F,
+    ) -> RecurringTaskHandle {
+        self.run_recurring(self::interval(interval, execute), group)
     }
 }
 
+fn register_recurring(
+    cs_task: &CSTaskImp,
+    task: RecurringTask,
+    group: CSTaskGroupIndex,
+) -> RecurringTaskHandle {
+    let mut task = match reuse_idle(task, group as u32) {
+        Ok(handle) => return handle,
+        Err(task) => task,
+    };
+
+    let register_task: extern "C" fn(&CSTaskImp, CSTaskGroupIndex, &RecurringTask) =
+        unsafe { std::mem::transmute(*REGISTER_TASK_VA) };
+
+    task.group = group as u32;
+    let task = Arc::new(task);
+    // The runtime holds on to the task for as long as the game runs, so the
+    // task keeps itself alive.
+    let _ = task.self_ref.set(task.clone());
+
+    register_task(cs_task, group, task.as_ref());
+
+    RecurringTaskHandle {
+        task,
+        generation: 1,
+    }
+}
+
+/// Hands the closure of the task to an idle task of the same task group.
+/// Returns the task if there are no idle tasks in the group.
+fn reuse_idle(task: RecurringTask, group: u32) -> Result<RecurringTaskHandle, RecurringTask> {
+    let idle = {
+        let mut idle_tasks = IDLE_TASKS.lock().unwrap_or_else(PoisonError::into_inner);
+        match idle_tasks.iter().position(|(g, _)| *g == group) {
+            Some(index) => idle_tasks.swap_remove(index).1,
+            None => return Err(task),
+        }
+    };
+
+    let generation = idle.generation.fetch_add(1, Ordering::AcqRel) + 1;
+    let body = task
+        .pending
+        .into_inner()
+        .unwrap_or_else(PoisonError::into_inner)
+        .map(|(_, body)| body);
+    *idle.pending.lock().unwrap_or_else(PoisonError::into_inner) =
+        body.map(|body| (generation, body));
+
+    Ok(RecurringTaskHandle {
+        task: idle,
+        generation,
+    })
+}
+
+/// Handle to a registered task. Dropping the handle leaves the task running,
+/// call [`RecurringTaskHandle::cancel`] to stop it.
+#[derive(Clone)]
 pub struct RecurringTaskHandle {
-    _task: Arc<RecurringTask>,
+    task: Arc<RecurringTask>,
+    /// Registration of the task this handle belongs to.
+    generation: u64,
 }
 
-impl Drop for RecurringTaskHandle {
-    fn drop(&mut self) {
-        self._task.cancel();
+impl RecurringTaskHandle {
+    /// Cancels the task. The closure does not run anymore after this. The
+    /// task itself stays registered with the game, see [`RecurringTask`].
+    pub fn cancel(&self) {
+        self.task.cancel_generation(self.generation);
     }
 }
 
+/// Returns whether or not the closure should keep running.
+type TaskBody = Box<dyn FnMut(&FD4TaskData) -> bool + Send>;
+
+/// Mirrors the layout of `FD4TaskBaseVmt`. Handles share the task with the
+/// game thread, so unlike `FD4TaskBaseVmt` none of the fns take the task
+/// mutably.
+#[vtable_rs::vtable]
+trait RecurringTaskVmt {
+    fn get_runtime_class(&self) -> &DLRuntimeClass;
+
+    fn destructor(&self);
+
+    fn execute(&self, data: &FD4TaskData);
+}
+
+/// Task running a closure every time its task group executes. Once the
+/// closure finishes or is cancelled the task stays registered and idles
+/// until a later registration in the same task group reuses it, see
+/// [`IDLE_TASKS`].
 #[repr(C)]
 pub struct RecurringTask {
-    vftable: VPtr<dyn FD4TaskBaseVmt, Self>,
+    vftable: VPtr<dyn RecurringTaskVmt, Self>,
     unk8: usize,
-    closure: Box<dyn FnMut(&FD4TaskData)>,
-    unregister_requested: AtomicBool,
-    self_ref: UnsafeCell<Option<Arc<Self>>>,
+    /// Closure of the registration being run and its generation. Only touched
+    /// from execute.
+    current: UnsafeCell<Option<(u64, TaskBody)>>,
+    /// Closure handed over by a registration, picked up by the next execute.
+    pending: Mutex<Option<(u64, TaskBody)>>,
+    /// Generation of the latest registration handed to the task.
+    generation: AtomicU64,
+    /// Latest generation that got cancelled.
+    cancelled: AtomicU64,
+    /// Index of the task group the task is registered in.
+    group: u32,
+    self_ref: OnceLock<Arc<Self>>,
 }
 
-impl FD4TaskBaseVmt for RecurringTask {
+// SAFETY: `current` is only accessed from execute, which the game runs on a
+// single thread at a time. Everything else is synchronized.
+unsafe impl Send for RecurringTask {}
+unsafe impl Sync for RecurringTask {}
+
+impl RecurringTaskVmt for RecurringTask {
     extern "C" fn get_runtime_class(&self) -> &DLRuntimeClass {
-        unimplemented!();
+        RECURRING_TASK_RUNTIME_CLASS.as_runtime_class()
     }
 
-    extern "C" fn destructor(&mut self) {
-        unimplemented!();
+    extern "C" fn destructor(&self) {
+        // Memory is owned by the Arc, nothing to do here.
     }
 
-    extern "C" fn execute(&mut self, data: &FD4TaskData) {
-        // Run the task if cancellation wasn't requested.
-        // if !self.unregister_requested.load(Ordering::Relaxed) {
-        (self.closure)(data);
-        // }
+    extern "C" fn execute(&self, data: &FD4TaskData) {
+        // SAFETY: see the Sync impl.
+        let current = unsafe { &mut *self.current.get() };
+        if current.is_none() {
+            // Don't hold up the game thread while a registration is being
+            // handed over, it'll get picked up next time.
+            if let Ok(mut pending) = self.pending.try_lock() {
+                *current = pending.take();
+            }
+        }
 
-        // TODO: implement the games unregister fn to properly get the task removed from the task
-        // pool instead of just not running the closure.
+        let Some((generation, body)) = current else {
+            return;
+        };
 
-        // Drop if we got cancelled in the meanwhile.
-        // if self.unregister_requested.load(Ordering::Relaxed) {
-        //     self.self_ref.get_mut().take();
-        // }
+        // Check for cancellation again after running the closure as the
+        // closure might cancel its own task.
+        let generation = *generation;
+        if !self.is_cancelled(generation) && body(data) && !self.is_cancelled(generation) {
+            return;
+        }
+
+        // Free the closure along with whatever it captured right away.
+        *current = None;
+        if let Some(task) = self.self_ref.get() {
+            IDLE_TASKS
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push((self.group, task.clone()));
+        }
     }
 }
 
 impl RecurringTask {
-    pub fn new<F: FnMut(&FD4TaskData) + 'static + Send>(closure: F) -> Self {
+    pub fn new<F: FnMut(&FD4TaskData) + 'static + Send>(mut closure: F) -> Self {
+        Self::new_finite(move |data: &FD4TaskData| {
+            closure(data);
+            true
+        })
+    }
+
+    /// Creates a task that stops running once the closure returns false.
+    pub fn new_finite<F: FnMut(&FD4TaskData) -> bool + 'static + Send>(closure: F) -> Self {
         Self {
             vftable: Default::default(),
             unk8: 0,
-            closure: Box::new(closure),
-            unregister_requested: AtomicBool::new(false),
-            self_ref: UnsafeCell::new(None),
+            current: UnsafeCell::new(None),
+            pending: Mutex::new(Some((1, Box::new(closure)))),
+            generation: AtomicU64::new(1),
+            cancelled: AtomicU64::new(0),
+            group: 0,
+            self_ref: OnceLock::new(),
         }
     }
 
+    /// Cancels the task's latest registration. The closure will not run
+    /// anymore after this.
     pub fn cancel(&self) {
-        self.unregister_requested.store(true, Ordering::Relaxed);
+        self.cancel_generation(self.generation.load(Ordering::Acquire));
+    }
+
+    fn cancel_generation(&self, generation: u64) {
+        self.cancelled.fetch_max(generation, Ordering::AcqRel);
+    }
+
+    fn is_cancelled(&self, generation: u64) -> bool {
+        self.cancelled.load(Ordering::Acquire) >= generation
+    }
+}
+
+static RECURRING_TASK_RUNTIME_CLASS: LazyLock<RecurringTaskRuntimeClass> =
+    LazyLock::new(|| RecurringTaskRuntimeClass {
+        vftable: Default::default(),
+        base_class: None,
+        unk10: [0; 7],
+    });
+
+static RECURRING_TASK_CLASS_NAME_W: LazyLock<Vec<u16>> =
+    LazyLock::new(|| "RecurringTask".encode_utf16().chain([0]).collect());
+
+/// Runtime class handed out by [`RecurringTask`] so the game and
+/// [`DLRuntimeClassHolder`](eldenring::dlrf::DLRuntimeClassHolder) users get a
+/// name for the task. Mirrors the layout of [`DLRuntimeClass`].
+#[repr(C)]
+struct RecurringTaskRuntimeClass {
+    vftable: VPtr<dyn DLRuntimeClassVmt, Self>,
+    base_class: Option<NonNull<DLRuntimeClass>>,
+    /// Remaining fields of DLRuntimeClass, including both allocators.
+    unk10: [usize; 7],
+}
+
+// SAFETY: the runtime class is never mutated after its construction.
+unsafe impl Send for RecurringTaskRuntimeClass {}
+unsafe impl Sync for RecurringTaskRuntimeClass {}
+
+impl RecurringTaskRuntimeClass {
+    fn as_runtime_class(&self) -> &DLRuntimeClass {
+        // SAFETY: layout matches DLRuntimeClass.
+        unsafe { &*(self as *const Self as *const DLRuntimeClass) }
+    }
+}
+
+impl DLRuntimeClassVmt for RecurringTaskRuntimeClass {
+    extern "C" fn destructor(&mut self, _param_2: u32) {
+        // Lives in a static, nothing to do here.
+    }
+
+    extern "C" fn class_name(&self) -> *const c_char {
+        c"RecurringTask".as_ptr()
+    }
+
+    extern "C" fn class_name_w(&self) -> *const u16 {
+        RECURRING_TASK_CLASS_NAME_W.as_ptr()
     }
 }
 
@@ -133,3 +489,228 @@ impl<F: FnMut(&FD4TaskData) + 'static + Send> From<F> for RecurringTask {
         Self::new(value)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use eldenring::fd4::FD4TaskData;
+
+    use super::{
+        delayed, finite, interval, reuse_idle, RecurringTask, RecurringTaskHandle,
+        RecurringTaskVmt, TaskDelay, IDLE_TASKS, RECURRING_TASK_RUNTIME_CLASS,
+    };
+
+    fn task_data(delta_time: f32) -> FD4TaskData {
+        let mut data: FD4TaskData = unsafe { std::mem::zeroed() };
+        data.delta_time.time = delta_time;
+        data
+    }
+
+    /// Counter that can be moved into a task body.
+    fn counter() -> (Arc<Mutex<u32>>, impl FnMut(&FD4TaskData) + Clone) {
+        let count = Arc::new(Mutex::new(0));
+        let increment = {
+            let count = count.clone();
+            move |_: &FD4TaskData| *count.lock().unwrap() += 1
+        };
+        (count, increment)
+    }
+
+    /// Sets up a task as if it was registered in the group.
+    fn registered(
+        mut task: RecurringTask,
+        group: u32,
+    ) -> (Arc<RecurringTask>, RecurringTaskHandle) {
+        task.group = group;
+        let task = Arc::new(task);
+        let _ = task.self_ref.set(task.clone());
+        let handle = RecurringTaskHandle {
+            task: task.clone(),
+            generation: 1,
+        };
+        (task, handle)
+    }
+
+    fn is_idle(task: &Arc<RecurringTask>) -> bool {
+        IDLE_TASKS
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|(_, t)| Arc::ptr_eq(t, task))
+    }
+
+    #[test]
+    fn frame_delay() {
+        let mut delay = TaskDelay::Frames(2);
+        let frame = Duration::from_millis(16);
+
+        assert!(!delay.advance(frame));
+        assert!(!delay.advance(frame));
+        assert!(delay.advance(frame));
+        // Stays passed.
+        assert!(delay.advance(frame));
+
+        assert!(TaskDelay::Frames(0).advance(Duration::ZERO));
+    }
+
+    #[test]
+    fn frame_delay_holds_without_game_time() {
+        let mut delay = TaskDelay::Frames(1);
+
+        assert!(!delay.advance(Duration::ZERO));
+        assert!(!delay.advance(Duration::ZERO));
+        assert_eq!(delay, TaskDelay::Frames(1));
+        assert!(!delay.advance(Duration::from_millis(16)));
+        assert!(delay.advance(Duration::ZERO));
+    }
+
+    #[test]
+    fn time_delay() {
+        let mut delay = TaskDelay::from(Duration::from_millis(40));
+
+        assert!(!delay.advance(Duration::from_millis(16)));
+        assert!(!delay.advance(Duration::ZERO));
+        assert!(!delay.advance(Duration::from_millis(16)));
+        assert!(delay.advance(Duration::from_millis(16)));
+
+        assert!(TaskDelay::Time(Duration::ZERO).advance(Duration::ZERO));
+    }
+
+    #[test]
+    fn delayed_runs_once() {
+        let (count, increment) = counter();
+        let mut body = delayed(TaskDelay::Frames(1), increment);
+
+        assert!(body(&task_data(0.016)));
+        assert_eq!(*count.lock().unwrap(), 0);
+        assert!(!body(&task_data(0.016)));
+        assert_eq!(*count.lock().unwrap(), 1);
+
+        // The body might still get called after it finished.
+        assert!(!body(&task_data(0.016)));
+        assert_eq!(*count.lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn delayed_ignores_bogus_delta_time() {
+        let (count, increment) = counter();
+        let mut body = delayed(TaskDelay::Time(Duration::from_secs(1)), increment);
+
+        assert!(body(&task_data(-1.0)));
+        assert!(body(&task_data(f32::NAN)));
+        assert_eq!(*count.lock().unwrap(), 0);
+        assert!(!body(&task_data(1.0)));
+        assert_eq!(*count.lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn finite_runs_for_frames() {
+        let (count, increment) = counter();
+        let mut body = finite(3, increment);
+
+        assert!(body(&task_data(0.016)));
+        assert!(body(&task_data(0.016)));
+        assert!(!body(&task_data(0.016)));
+        assert!(!body(&task_data(0.016)));
+        assert_eq!(*count.lock().unwrap(), 3);
+
+        let (count, increment) = counter();
+        assert!(!finite(0, increment)(&task_data(0.016)));
+        assert_eq!(*count.lock().unwrap(), 0);
+    }
+
+    #[test]
+    fn finite_skips_frames_without_game_time() {
+        let (count, increment) = counter();
+        let mut body = finite(2, increment);
+
+        assert!(body(&task_data(0.016)));
+        assert!(body(&task_data(0.0)));
+        assert!(body(&task_data(0.0)));
+        assert_eq!(*count.lock().unwrap(), 1);
+        assert!(!body(&task_data(0.016)));
+        assert_eq!(*count.lock().unwrap(), 2);
+    }
+
+    #[test]
+    fn interval_skips_missed_runs() {
+        let (count, increment) = counter();
+        let mut body = interval(Duration::from_millis(125), increment);
+
+        body(&task_data(0.0625));
+        assert_eq!(*count.lock().unwrap(), 0);
+        body(&task_data(0.0625));
+        assert_eq!(*count.lock().unwrap(), 1);
+
+        // A long frame only runs the closure once.
+        body(&task_data(0.5));
+        assert_eq!(*count.lock().unwrap(), 2);
+        body(&task_data(0.0625));
+        assert_eq!(*count.lock().unwrap(), 2);
+        body(&task_data(0.0625));
+        assert_eq!(*count.lock().unwrap(), 3);
+    }
+
+    #[test]
+    fn runtime_class() {
+        let runtime_class = RECURRING_TASK_RUNTIME_CLASS.as_runtime_class();
+
+        assert_eq!(runtime_class.name(), "RecurringTask");
+        assert!(runtime_class.is_a("RecurringTask"));
+        assert!(!runtime_class.is_a("CSTaskImp"));
+        assert!(runtime_class.base().is_none());
+        assert!(runtime_class.allocator().is_none());
+
+        let name_w = (runtime_class.vftable.class_name_w)(runtime_class);
+        let name_w = unsafe { std::slice::from_raw_parts(name_w, 14) };
+        assert_eq!(String::from_utf16_lossy(name_w), "RecurringTask\0");
+    }
+
+    #[test]
+    fn cancelled_tasks_idle() {
+        let (count, increment) = counter();
+        let (task, handle) = registered(RecurringTask::new(increment), 1000);
+
+        task.execute(&task_data(0.016));
+        task.execute(&task_data(0.016));
+        assert_eq!(*count.lock().unwrap(), 2);
+        assert!(!is_idle(&task));
+
+        handle.cancel();
+        task.execute(&task_data(0.016));
+        assert_eq!(*count.lock().unwrap(), 2);
+        assert!(is_idle(&task));
+    }
+
+    #[test]
+    fn finished_tasks_get_reused() {
+        let (task, handle) = registered(RecurringTask::new_finite(|_| false), 1001);
+        let old_handle = handle;
+
+        task.execute(&task_data(0.016));
+        assert!(is_idle(&task));
+
+        let (count, increment) = counter();
+        let Ok(new_handle) = reuse_idle(RecurringTask::new(increment), 1001) else {
+            panic!("idle task was not reused");
+        };
+        assert!(!is_idle(&task));
+
+        // Cancelling the finished registration leaves the new one running.
+        old_handle.cancel();
+        task.execute(&task_data(0.016));
+        assert_eq!(*count.lock().unwrap(), 1);
+
+        // Dropping the handle leaves the task running.
+        drop(new_handle.clone());
+        task.execute(&task_data(0.016));
+        assert_eq!(*count.lock().unwrap(), 2);
+
+        new_handle.cancel();
+        task.execute(&task_data(0.016));
+        assert_eq!(*count.lock().unwrap(), 2);
+        assert!(reuse_idle(RecurringTask::new(|_| {}), 1002).is_err());
+    }
+}
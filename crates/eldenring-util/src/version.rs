@@ -0,0 +1,451 @@
+use std::fmt;
+use std::ops::RangeBounds;
+use std::str::FromStr;
+
+use pelite::pe64::{Pe, PeObject};
+use pelite::resources::version_info::VersionInfo;
+use thiserror::Error;
+
+use crate::program::Program;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Game {
+    EldenRing,
+    Nightreign,
+}
+
+impl fmt::Display for Game {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Game::EldenRing => write!(f, "ELDEN RING"),
+            Game::Nightreign => write!(f, "ELDEN RING NIGHTREIGN"),
+        }
+    }
+}
+
+/// Version as stored in the executable's version resource. Note that this
+/// follows the file version of the executable which doesn't necessarily line
+/// up with the version displayed on the title screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version {
+    pub major: u16,
+    pub minor: u16,
+    pub patch: u16,
+    pub build: u16,
+}
+
+impl Version {
+    pub const fn new(major: u16, minor: u16, patch: u16, build: u16) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+            build,
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}.{}.{}.{}",
+            self.major, self.minor, self.patch, self.build
+        )
+    }
+}
+
+impl FromStr for Version {
+    type Err = VersionError;
+
+    /// Parses versions in the form of `major.minor.patch` with an optional
+    /// trailing `.build`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let components = s
+            .split('.')
+            .map(|c| c.trim().parse::<u16>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| VersionError::MalformedVersion(s.to_string()))?;
+
+        match components.as_slice() {
+            [major, minor, patch] => Ok(Self::new(*major, *minor, *patch, 0)),
+            [major, minor, patch, build] => Ok(Self::new(*major, *minor, *patch, *build)),
+            _ => Err(VersionError::MalformedVersion(s.to_string())),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum VersionError {
+    #[error("Could not find the version resource in the executable.")]
+    MissingVersionInfo,
+    #[error("Executable is not a known game. Original filename: {0:?}")]
+    UnknownGame(Option<String>),
+    #[error("Could not parse version {0}.")]
+    MalformedVersion(String),
+    #[error("Expected {expected} but found {found}.")]
+    WrongGame { expected: Game, found: Game },
+    #[error("{game} version {found} is not supported.")]
+    UnsupportedVersion { game: Game, found: Version },
+}
+
+/// Describes which build of which game an executable is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GameVersion {
+    pub game: Game,
+    pub version: Version,
+    /// Link timestamp from the COFF file header.
+    pub timestamp: u32,
+    /// FNV-1a hash over the PE headers, leaving out the ImageBase field as
+    /// the loader writes the actual base address there when relocating the
+    /// image. This yields the same result for the file on disk and the mapped
+    /// image, and it changes with every build since the headers include the
+    /// timestamp and checksum.
+    pub image_hash: u64,
+}
+
+impl GameVersion {
+    /// Reads the version information from either a mapped image or a file.
+    pub fn from_program(program: &Program) -> Result<Self, VersionError> {
+        let version_info = program
+            .resources()
+            .ok()
+            .and_then(|r| r.version_info().ok())
+            .ok_or(VersionError::MissingVersionInfo)?;
+
+        let fixed = version_info
+            .fixed()
+            .ok_or(VersionError::MissingVersionInfo)?;
+
+        let version = Version::new(
+            fixed.dwFileVersion.Major,
+            fixed.dwFileVersion.Minor,
+            fixed.dwFileVersion.Patch,
+            fixed.dwFileVersion.Build,
+        );
+
+        Ok(Self {
+            game: detect_game(version_info)?,
+            version,
+            timestamp: program.file_header().TimeDateStamp,
+            image_hash: header_hash(program),
+        })
+    }
+
+    /// Reads the version information of the currently running game.
+    pub fn current() -> Result<Self, VersionError> {
+        Self::from_program(&Program::current())
+    }
+}
+
+/// Checks if the program is the expected game and if its version falls within
+/// the supported range. Meant to be called when a mod starts so it can bail
+/// out instead of relying on RVAs and patterns from another build.
+///
+/// ```ignore
+/// require_version(&Program::current(), Game::EldenRing, Version::new(2, 6, 0, 0)..)?;
+/// ```
+pub fn require_version(
+    program: &Program,
+    game: Game,
+    supported: impl RangeBounds<Version>,
+) -> Result<GameVersion, VersionError> {
+    let game_version = GameVersion::from_program(program)?;
+
+    if game_version.game != game {
+        return Err(VersionError::WrongGame {
+            expected: game,
+            found: game_version.game,
+        });
+    }
+
+    if !supported.contains(&game_version.version) {
+        return Err(VersionError::UnsupportedVersion {
+            game,
+            found: game_version.version,
+        });
+    }
+
+    Ok(game_version)
+}
+
+/// Determines the game from the strings in the version resource.
+fn detect_game(version_info: VersionInfo) -> Result<Game, VersionError> {
+    let lang = version_info
+        .translation()
+        .first()
+        .copied()
+        .ok_or(VersionError::MissingVersionInfo)?;
+
+    let original_filename = version_info
+        .value(lang, "OriginalFilename")
+        .map(|s| s.to_ascii_lowercase());
+    let product_name = version_info
+        .value(lang, "ProductName")
+        .map(|s| s.to_ascii_lowercase());
+
+    let matches = |needle: &str| {
+        original_filename
+            .as_deref()
+            .is_some_and(|s| s.contains(needle))
+            || product_name.as_deref().is_some_and(|s| s.contains(needle))
+    };
+
+    // Nightreign's product name contains "elden ring" too so it needs to go first.
+    if matches("nightreign") {
+        Ok(Game::Nightreign)
+    } else if matches("eldenring") || matches("elden ring") {
+        Ok(Game::EldenRing)
+    } else {
+        Err(VersionError::UnknownGame(original_filename))
+    }
+}
+
+fn header_hash(program: &Program) -> u64 {
+    let size_of_headers = program.optional_header().SizeOfHeaders as usize;
+    let image = program.image();
+    let headers = &image[..size_of_headers.min(image.len())];
+
+    let image_base =
+        &program.optional_header().ImageBase as *const u64 as usize - image.as_ptr() as usize;
+    let image_base = image_base..image_base + size_of::<u64>();
+
+    headers
+        .iter()
+        .enumerate()
+        .filter(|(offset, _)| !image_base.contains(offset))
+        .fold(0xcbf29ce484222325, |hash, (_, byte)| {
+            (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use pelite::resources::version_info::VersionInfo;
+
+    use super::{detect_game, Game, GameVersion, Version, VersionError};
+    use crate::program::{fixture::TestImage, Program};
+
+    /// Encodes a single VS_VERSIONINFO style node, padded to 4 bytes.
+    fn node(key: &str, value: &[u8], value_length: u16, kind: u16, children: &[u8]) -> Vec<u8> {
+        fn pad(bytes: &mut Vec<u8>) {
+            bytes.resize(bytes.len().next_multiple_of(4), 0);
+        }
+
+        let mut bytes = vec![0; 4];
+        bytes.extend(kind.to_le_bytes());
+        bytes.extend(key.encode_utf16().chain([0]).flat_map(u16::to_le_bytes));
+        pad(&mut bytes);
+        bytes.extend(value);
+        pad(&mut bytes);
+        bytes.extend(children);
+        pad(&mut bytes);
+
+        let length = bytes.len() as u16;
+        bytes[0..2].copy_from_slice(&length.to_le_bytes());
+        bytes[2..4].copy_from_slice(&value_length.to_le_bytes());
+        bytes
+    }
+
+    fn version_info(version: Version, strings: &[(&str, &str)]) -> Vec<u8> {
+        let mut fixed = Vec::new();
+        fixed.extend(0xfeef04bdu32.to_le_bytes());
+        fixed.extend(0x10000u32.to_le_bytes());
+        for _ in 0..2 {
+            for part in [version.minor, version.major, version.build, version.patch] {
+                fixed.extend(part.to_le_bytes());
+            }
+        }
+        fixed.resize(52, 0);
+
+        let strings = strings
+            .iter()
+            .flat_map(|(key, value)| {
+                let value = value
+                    .encode_utf16()
+                    .chain([0])
+                    .flat_map(u16::to_le_bytes)
+                    .collect::<Vec<_>>();
+                node(key, &value, value.len() as u16 / 2, 1, &[])
+            })
+            .collect::<Vec<_>>();
+        let string_table = node("040904b0", &[], 0, 1, &strings);
+        let string_file_info = node("StringFileInfo", &[], 0, 1, &string_table);
+
+        let translation = [0x0409u16.to_le_bytes(), 0x04b0u16.to_le_bytes()].concat();
+        let translation = node("Translation", &translation, 4, 0, &[]);
+        let var_file_info = node("VarFileInfo", &[], 0, 1, &translation);
+
+        node(
+            "VS_VERSION_INFO",
+            &fixed,
+            fixed.len() as u16,
+            0,
+            &[string_file_info, var_file_info].concat(),
+        )
+    }
+
+    fn detect(strings: &[(&str, &str)]) -> Result<Game, VersionError> {
+        // VersionInfo expects the bytes to be aligned.
+        let bytes = version_info(Version::new(1, 0, 0, 0), strings);
+        let words = bytes
+            .chunks(4)
+            .map(|c| u32::from_le_bytes(c.try_into().unwrap()))
+            .collect::<Vec<_>>();
+        let bytes = unsafe { std::slice::from_raw_parts(words.as_ptr() as *const u8, bytes.len()) };
+
+        detect_game(VersionInfo::try_from(bytes).unwrap())
+    }
+
+    fn test_image(version: Version, strings: &[(&str, &str)]) -> TestImage {
+        let mut image = TestImage::new(&[".text", ".rsrc"]);
+        image.set_version_info(".rsrc", &version_info(version, strings));
+        image
+    }
+
+    #[test]
+    fn parse_version() {
+        assert_eq!(
+            "2.6.1".parse::<Version>().unwrap(),
+            Version::new(2, 6, 1, 0)
+        );
+        assert_eq!(
+            "1.2.3.4".parse::<Version>().unwrap(),
+            Version::new(1, 2, 3, 4)
+        );
+        assert_eq!(
+            " 2 . 6 . 0 ".parse::<Version>().unwrap(),
+            Version::new(2, 6, 0, 0)
+        );
+
+        for malformed in ["", "2", "2.6", "1.2.3.4.5", "2.x.0", "2.6.-1", "70000.0.0"] {
+            assert!(
+                matches!(
+                    malformed.parse::<Version>(),
+                    Err(VersionError::MalformedVersion(s)) if s == malformed
+                ),
+                "{malformed:?} should not parse"
+            );
+        }
+    }
+
+    #[test]
+    fn version_ordering_and_display() {
+        let version = Version::new(2, 6, 1, 2);
+
+        assert_eq!(version.to_string(), "2.6.1.2");
+        assert_eq!(version.to_string().parse::<Version>().unwrap(), version);
+        assert!(Version::new(2, 6, 0, 0) < Version::new(2, 6, 1, 0));
+        assert!(Version::new(1, 16, 0, 0) < Version::new(2, 0, 0, 0));
+    }
+
+    #[test]
+    fn detect_game_from_strings() {
+        assert_eq!(
+            detect(&[("OriginalFilename", "eldenring.exe")]).unwrap(),
+            Game::EldenRing
+        );
+        assert_eq!(
+            detect(&[("ProductName", "ELDEN RING")]).unwrap(),
+            Game::EldenRing
+        );
+        assert_eq!(
+            detect(&[
+                ("OriginalFilename", "nightreign.exe"),
+                ("ProductName", "ELDEN RING NIGHTREIGN"),
+            ])
+            .unwrap(),
+            Game::Nightreign
+        );
+        // The product name alone should not make this Elden Ring.
+        assert_eq!(
+            detect(&[("ProductName", "ELDEN RING NIGHTREIGN")]).unwrap(),
+            Game::Nightreign
+        );
+
+        assert!(matches!(
+            detect(&[("OriginalFilename", "DarkSoulsIII.exe")]),
+            Err(VersionError::UnknownGame(Some(name))) if name == "darksoulsiii.exe"
+        ));
+        assert!(matches!(detect(&[]), Err(VersionError::UnknownGame(None))));
+    }
+
+    #[test]
+    fn game_version_from_image() {
+        let mut image = test_image(
+            Version::new(2, 6, 1, 0),
+            &[("OriginalFilename", "eldenring.exe")],
+        );
+        image.set_timestamp(0x6800_0000);
+
+        let game_version = GameVersion::from_program(&image.program()).unwrap();
+        assert_eq!(game_version.game, Game::EldenRing);
+        assert_eq!(game_version.version, Version::new(2, 6, 1, 0));
+        assert_eq!(game_version.timestamp, 0x6800_0000);
+
+        assert!(super::require_version(
+            &image.program(),
+            Game::EldenRing,
+            Version::new(2, 6, 0, 0)..
+        )
+        .is_ok());
+        assert!(matches!(
+            super::require_version(&image.program(), Game::Nightreign, ..),
+            Err(VersionError::WrongGame { .. })
+        ));
+        assert!(matches!(
+            super::require_version(
+                &image.program(),
+                Game::EldenRing,
+                ..Version::new(2, 6, 0, 0)
+            ),
+            Err(VersionError::UnsupportedVersion { .. })
+        ));
+    }
+
+    #[test]
+    fn game_version_from_file() {
+        let mut image = test_image(
+            Version::new(1, 16, 0, 0),
+            &[("OriginalFilename", "nightreign.exe")],
+        );
+        image.set_timestamp(0x6800_0000);
+        let file = image.to_file();
+        assert!(matches!(file.program(), Program::File(_)));
+
+        let from_file = GameVersion::from_program(&file.program()).unwrap();
+        assert_eq!(from_file.game, Game::Nightreign);
+        assert_eq!(from_file.version, Version::new(1, 16, 0, 0));
+        assert_eq!(
+            from_file,
+            GameVersion::from_program(&image.program()).unwrap()
+        );
+    }
+
+    #[test]
+    fn missing_version_info() {
+        let image = TestImage::new(&[".text"]);
+
+        assert!(matches!(
+            GameVersion::from_program(&image.program()),
+            Err(VersionError::MissingVersionInfo)
+        ));
+    }
+
+    #[test]
+    fn image_hash_ignores_relocation() {
+        let strings = [("OriginalFilename", "eldenring.exe")];
+        let mut image = test_image(Version::new(2, 6, 1, 0), &strings);
+        let original = GameVersion::from_program(&image.program()).unwrap();
+
+        // Mapped somewhere else by the loader.
+        image.set_image_base(0x7ff6_1234_0000);
+        let relocated = GameVersion::from_program(&image.program()).unwrap();
+        assert_eq!(original.image_hash, relocated.image_hash);
+
+        // Another build.
+        image.set_timestamp(0x6800_0000);
+        let rebuilt = GameVersion::from_program(&image.program()).unwrap();
+        assert_ne!(original.image_hash, rebuilt.image_hash);
+    }
+}
@@ -0,0 +1,557 @@
+//! Hooks on individual vftable slots.
+//!
+//! [`VmtHook`] patches a slot of a vftable shared by every instance of a
+//! class. Hooks on the same slot chain like function hooks do, the slot points
+//! at the most recently installed hook and every hook's original points at the
+//! hook installed before it.
+//!
+//! [`ClonedVmt`] gives a single object its own copy of its vftable instead so
+//! that patching a slot doesn't affect any other instances.
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::marker::PhantomData;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+
+use pelite::pe64::Pe;
+use retour::Function;
+
+use crate::hook::{with_write_access, HookError};
+use crate::program::Program;
+use crate::rtti::find_rtti_classes;
+
+struct VmtHookEntry {
+    detour: usize,
+    original: AtomicUsize,
+}
+
+/// All hooks installed onto a single vftable slot.
+struct VmtHookChain {
+    /// Value of the slot before any hooks were installed.
+    original: usize,
+    /// Installed hooks, oldest first.
+    entries: Vec<Arc<VmtHookEntry>>,
+}
+
+impl VmtHookChain {
+    /// Rewires the originals of all entries and writes the most recently
+    /// installed hook into the slot.
+    unsafe fn relink(&self, slot: NonNull<usize>) -> Result<(), HookError> {
+        let mut next = self.original;
+        for entry in self.entries.iter() {
+            entry.original.store(next, Ordering::Release);
+            next = entry.detour;
+        }
+
+        with_write_access(slot.as_ptr() as *const c_void, size_of::<usize>(), || {
+            // Slots are pointer aligned so this write is atomic.
+            AtomicUsize::from_ptr(slot.as_ptr()).store(next, Ordering::Release)
+        })
+    }
+}
+
+static VMT_HOOK_CHAINS: LazyLock<Mutex<HashMap<usize, VmtHookChain>>> =
+    LazyLock::new(Default::default);
+
+/// A typed hook on a single slot of a vftable. The slot is restored when the
+/// hook is dropped.
+pub struct VmtHook<F: Function> {
+    slot: NonNull<usize>,
+    entry: Arc<VmtHookEntry>,
+    _marker: PhantomData<F>,
+}
+
+unsafe impl<F: Function> Send for VmtHook<F> {}
+unsafe impl<F: Function> Sync for VmtHook<F> {}
+
+impl<F: Function> VmtHook<F> {
+    /// Hooks a slot in the primary vftable of the class with the specified
+    /// (demangled) RTTI name, ex: `CS::CSEzDraw`.
+    ///
+    /// # Safety
+    /// Caller must ensure that the slot holds a function with the same
+    /// signature as F.
+    pub unsafe fn by_class_name(
+        class_name: &str,
+        index: u32,
+        detour: F,
+    ) -> Result<Self, HookError> {
+        let program = Program::current();
+        let class = find_rtti_classes(&program)
+            .find(|c| c.name == class_name && c.vftable_offset() == Some(0))
+            .ok_or_else(|| HookError::ClassNotFound(class_name.to_string()))?;
+
+        let vftable = program
+            .rva_to_va(class.vftable)
+            .map_err(|_| HookError::InvalidRva(class.vftable))? as *mut usize;
+
+        let len = unhooked_vftable_len(&program, vftable)?;
+        if index as usize >= len {
+            return Err(HookError::IndexOutOfRange { index, len });
+        }
+
+        let slot = NonNull::new(vftable.add(index as usize)).ok_or(HookError::InvalidVftable)?;
+        Self::at_slot(slot, detour)
+    }
+
+    /// Hooks a slot in the vftable of an existing object. This affects every
+    /// object sharing the vftable, use [`ClonedVmt`] to only hook a single
+    /// object.
+    ///
+    /// # Safety
+    /// Caller must ensure that:
+    ///  - The object starts with a vftable pointer to a vftable in the game's image.
+    ///  - The slot holds a function with the same signature as F.
+    pub unsafe fn by_object<T>(object: &T, index: u32, detour: F) -> Result<Self, HookError> {
+        Self::by_object_in(&Program::current(), object, index, detour)
+    }
+
+    unsafe fn by_object_in<T>(
+        program: &Program,
+        object: &T,
+        index: u32,
+        detour: F,
+    ) -> Result<Self, HookError> {
+        let vftable = *(object as *const T as *const *mut usize);
+
+        let len = unhooked_vftable_len(program, vftable)?;
+        if index as usize >= len {
+            return Err(HookError::IndexOutOfRange { index, len });
+        }
+
+        let slot = NonNull::new(vftable.add(index as usize)).ok_or(HookError::InvalidVftable)?;
+        Self::at_slot(slot, detour)
+    }
+
+    /// Hooks the specified vftable slot.
+    ///
+    /// # Safety
+    /// Caller must ensure that the slot is valid and holds a function with the
+    /// same signature as F.
+    pub unsafe fn at_slot(slot: NonNull<usize>, detour: F) -> Result<Self, HookError> {
+        let entry = Arc::new(VmtHookEntry {
+            detour: detour.to_ptr() as usize,
+            original: AtomicUsize::new(0),
+        });
+
+        let mut chains = VMT_HOOK_CHAINS.lock().unwrap();
+        let chain = chains
+            .entry(slot.as_ptr() as usize)
+            .or_insert_with(|| VmtHookChain {
+                original: *slot.as_ptr(),
+                entries: Vec::new(),
+            });
+
+        chain.entries.push(entry.clone());
+        if let Err(e) = chain.relink(slot) {
+            chain.entries.pop();
+            if chain.entries.is_empty() {
+                chains.remove(&(slot.as_ptr() as usize));
+            }
+            return Err(e);
+        }
+
+        tracing::debug!(
+            "Installed vftable hook on slot {:#x}",
+            slot.as_ptr() as usize
+        );
+
+        Ok(Self {
+            slot,
+            entry,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Returns the function to call in order to continue the chain.
+    pub fn original(&self) -> F {
+        unsafe { F::from_ptr(self.entry.original.load(Ordering::Acquire) as *const ()) }
+    }
+}
+
+impl<F: Function> Drop for VmtHook<F> {
+    fn drop(&mut self) {
+        let key = self.slot.as_ptr() as usize;
+        let mut chains = VMT_HOOK_CHAINS.lock().unwrap();
+        let Some(chain) = chains.get_mut(&key) else {
+            return;
+        };
+
+        chain.entries.retain(|e| !Arc::ptr_eq(e, &self.entry));
+        if let Err(e) = unsafe { chain.relink(self.slot) } {
+            tracing::debug!("Could not restore vftable slot {key:#x}: {e}");
+        }
+
+        if chain.entries.is_empty() {
+            chains.remove(&key);
+        }
+    }
+}
+
+/// Per-object copy of a vftable. The object is pointed at the copy for as long
+/// as this exists, slots can then be replaced without affecting other objects
+/// of the same class.
+pub struct ClonedVmt {
+    object: NonNull<*const usize>,
+    original: *const usize,
+    /// Copy of the vftable, prefixed with the complete object locator pointer
+    /// so RTTI keeps working on the object.
+    table: Box<[usize]>,
+}
+
+unsafe impl Send for ClonedVmt {}
+unsafe impl Sync for ClonedVmt {}
+
+impl ClonedVmt {
+    /// Gives the object a private copy of its vftable.
+    ///
+    /// # Safety
+    /// Caller must ensure that:
+    ///  - The object starts with a vftable pointer to a vftable in the game's image.
+    ///  - The object outlives the clone.
+    pub unsafe fn new<T>(object: &mut T) -> Result<Self, HookError> {
+        Self::new_in(&Program::current(), object)
+    }
+
+    unsafe fn new_in<T>(program: &Program, object: &mut T) -> Result<Self, HookError> {
+        let object = NonNull::from(object).cast::<*const usize>();
+        let original = *object.as_ptr();
+
+        let len = unhooked_vftable_len(program, original)?;
+        if len == 0 {
+            return Err(HookError::InvalidVftable);
+        }
+
+        // Copy the slots as they were before any VmtHooks were installed, the
+        // clone would otherwise keep calling detours that may be gone by the
+        // time it gets dropped.
+        let mut table = std::slice::from_raw_parts(original.sub(1), len + 1).to_vec();
+        for (index, entry) in table.iter_mut().skip(1).enumerate() {
+            *entry = unhooked_slot(original.add(index));
+        }
+        let table = table.into_boxed_slice();
+
+        *object.as_ptr() = table.as_ptr().add(1);
+
+        Ok(Self {
+            object,
+            original,
+            table,
+        })
+    }
+
+    /// Amount of functions in the vftable.
+    pub fn len(&self) -> usize {
+        self.table.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Replaces a slot in the cloned vftable, returning the function from the
+    /// original vftable.
+    ///
+    /// # Safety
+    /// Caller must ensure that the slot holds a function with the same
+    /// signature as F.
+    pub unsafe fn replace<F: Function>(&mut self, index: usize, detour: F) -> F {
+        assert!(index < self.len(), "vftable index out of bounds");
+
+        self.table[index + 1] = detour.to_ptr() as usize;
+        self.original(index)
+    }
+
+    /// Returns the function at the index in the original vftable, ignoring
+    /// any [`VmtHook`]s installed on it.
+    ///
+    /// # Safety
+    /// Caller must ensure that the slot holds a function with the same
+    /// signature as F.
+    pub unsafe fn original<F: Function>(&self, index: usize) -> F {
+        assert!(index < self.len(), "vftable index out of bounds");
+
+        F::from_ptr(unhooked_slot(self.original.add(index)) as *const ())
+    }
+}
+
+impl Drop for ClonedVmt {
+    fn drop(&mut self) {
+        unsafe {
+            // Leave the object alone if something else swapped the vftable in the meantime.
+            if *self.object.as_ptr() == self.table.as_ptr().add(1) {
+                *self.object.as_ptr() = self.original;
+            }
+        }
+    }
+}
+
+/// Reads a vftable slot, returning the value it had before any [`VmtHook`]s
+/// were installed on it.
+///
+/// # Safety
+/// Caller must ensure that the slot is readable.
+unsafe fn unhooked_slot(slot: *const usize) -> usize {
+    VMT_HOOK_CHAINS
+        .lock()
+        .unwrap()
+        .get(&(slot as usize))
+        .map(|chain| chain.original)
+        .unwrap_or_else(|| *slot)
+}
+
+/// Counts the entries of a vftable in the game's image by walking it until it
+/// finds an entry that doesn't point into .text. Slots patched by a
+/// [`VmtHook`] are judged by their original value, otherwise a hook in the
+/// middle of the table would cut the count short.
+fn unhooked_vftable_len(program: &Program, vftable: *const usize) -> Result<usize, HookError> {
+    program
+        .va_to_rva(vftable as u64)
+        .map_err(|_| HookError::InvalidVftable)?;
+    let text = program
+        .section_headers()
+        .by_name(".text")
+        .ok_or(HookError::InvalidVftable)?
+        .virtual_range();
+
+    let chains = VMT_HOOK_CHAINS.lock().unwrap();
+    let len = (0..)
+        .map_while(|index| {
+            let slot = unsafe { vftable.add(index) };
+            // Stop at the end of the image rather than reading past it.
+            program.va_to_rva(slot as u64).ok()?;
+
+            let entry = chains
+                .get(&(slot as usize))
+                .map(|chain| chain.original)
+                .unwrap_or_else(|| unsafe { *slot });
+            let rva = program.va_to_rva(entry as u64).ok()?;
+            text.contains(&rva).then_some(())
+        })
+        .count();
+
+    Ok(len)
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::{Arc, OnceLock};
+
+    use pelite::pe64::Rva;
+
+    use super::{unhooked_slot, ClonedVmt, VmtHook, VmtHookChain, VmtHookEntry, VMT_HOOK_CHAINS};
+    use crate::hook::HookError;
+    use crate::program::fixture::TestImage;
+
+    type SlotFn = extern "C" fn(u32) -> u32;
+
+    /// Amount of functions in the vftable of [`Vftable::new`].
+    const VFTABLE_LEN: usize = 3;
+
+    /// Test image holding a vftable in .data whose entries point into .text.
+    /// The image base matches the address of the image's bytes so the
+    /// vftable can be used through its VA.
+    struct Vftable {
+        image: TestImage,
+        vftable: Rva,
+    }
+
+    impl Vftable {
+        fn new() -> Self {
+            let mut image = TestImage::new(&[".text", ".data"]);
+            let text = image.section(".text");
+            let vftable = image.section(".data") + 0x8;
+
+            let base = image.bytes().as_ptr() as u64;
+            image.set_image_base(base);
+
+            // Complete object locator, the functions and a terminating null.
+            image.write(vftable - 0x8, &0xc01u64.to_le_bytes());
+            for index in 0..VFTABLE_LEN {
+                let entry = base + (text + 0x10 * index as Rva) as u64;
+                image.write(vftable + 0x8 * index as Rva, &entry.to_le_bytes());
+            }
+
+            Self { image, vftable }
+        }
+
+        fn va(&self) -> *mut usize {
+            unsafe { self.image.bytes().as_ptr().add(self.vftable as usize) as *mut usize }
+        }
+
+        fn slot(&self, index: usize) -> usize {
+            unsafe { *self.va().add(index) }
+        }
+
+        fn object(&self) -> Object {
+            Object { vftable: self.va() }
+        }
+    }
+
+    #[repr(C)]
+    struct Object {
+        vftable: *mut usize,
+    }
+
+    /// Stands in for the function in the slot of [`chain_forwards_to_the_original`].
+    extern "C" fn base(value: u32) -> u32 {
+        value
+    }
+
+    static HOOK_1: OnceLock<VmtHook<SlotFn>> = OnceLock::new();
+    static HOOK_2: OnceLock<VmtHook<SlotFn>> = OnceLock::new();
+
+    // Every hook appends its digit so the result spells out the call order.
+    extern "C" fn hook_1(value: u32) -> u32 {
+        HOOK_1.get().unwrap().original()(value * 10 + 1)
+    }
+
+    extern "C" fn hook_2(value: u32) -> u32 {
+        HOOK_2.get().unwrap().original()(value * 10 + 2)
+    }
+
+    extern "C" fn detour_1(value: u32) -> u32 {
+        value + 1
+    }
+
+    extern "C" fn detour_2(value: u32) -> u32 {
+        value + 2
+    }
+
+    extern "C" fn detour_3(value: u32) -> u32 {
+        value + 3
+    }
+
+    #[test]
+    fn chain_forwards_to_the_original() {
+        // Leaked since the hooks in the statics point into it.
+        let slot: &'static mut usize = Box::leak(Box::new(base as SlotFn as usize));
+        let slot_ptr = std::ptr::NonNull::from(&mut *slot);
+
+        let _ = HOOK_1.set(unsafe { VmtHook::at_slot(slot_ptr, hook_1 as SlotFn) }.unwrap());
+        let _ = HOOK_2.set(unsafe { VmtHook::at_slot(slot_ptr, hook_2 as SlotFn) }.unwrap());
+
+        let entry = unsafe { std::mem::transmute::<usize, SlotFn>(*slot) };
+        assert_eq!(entry(0), 21);
+    }
+
+    #[test]
+    fn slots_are_restored_in_any_drop_order() {
+        let vftable = Vftable::new();
+        let object = vftable.object();
+        let original = vftable.slot(1);
+
+        let program = vftable.image.program();
+        let hook = |detour: SlotFn| unsafe { VmtHook::by_object_in(&program, &object, 1, detour) };
+        let first = hook(detour_1).unwrap();
+        let second = hook(detour_2).unwrap();
+        let third = hook(detour_3).unwrap();
+        assert_eq!(vftable.slot(1), detour_3 as SlotFn as usize);
+        assert_eq!(third.original() as usize, detour_2 as SlotFn as usize);
+        assert_eq!(first.original() as usize, original);
+
+        drop(second);
+        assert_eq!(vftable.slot(1), detour_3 as SlotFn as usize);
+        assert_eq!(third.original() as usize, detour_1 as SlotFn as usize);
+
+        drop(third);
+        assert_eq!(vftable.slot(1), detour_1 as SlotFn as usize);
+        assert_eq!(first.original() as usize, original);
+
+        drop(first);
+        assert_eq!(vftable.slot(1), original);
+        assert!(!VMT_HOOK_CHAINS
+            .lock()
+            .unwrap()
+            .contains_key(&(unsafe { vftable.va().add(1) } as usize)));
+    }
+
+    #[test]
+    fn out_of_range_indices_are_rejected() {
+        let vftable = Vftable::new();
+        let object = vftable.object();
+        let program = vftable.image.program();
+
+        let result = unsafe {
+            VmtHook::by_object_in(&program, &object, VFTABLE_LEN as u32, detour_1 as SlotFn)
+        };
+        assert!(matches!(
+            result,
+            Err(HookError::IndexOutOfRange { index, len }) if index == VFTABLE_LEN as u32 && len == VFTABLE_LEN
+        ));
+
+        // Hooks in the middle of the table don't cut the count short.
+        let _hook =
+            unsafe { VmtHook::by_object_in(&program, &object, 1, detour_1 as SlotFn) }.unwrap();
+        let last = unsafe {
+            VmtHook::by_object_in(
+                &program,
+                &object,
+                VFTABLE_LEN as u32 - 1,
+                detour_2 as SlotFn,
+            )
+        };
+        assert!(last.is_ok());
+    }
+
+    #[test]
+    fn cloned_vmt_copies_and_restores() {
+        let vftable = Vftable::new();
+        let mut object = vftable.object();
+        let program = vftable.image.program();
+        let original = vftable.slot(0);
+
+        // Installed on the shared vftable before cloning.
+        let shared_hook =
+            unsafe { VmtHook::by_object_in(&program, &object, 0, detour_1 as SlotFn) }.unwrap();
+
+        let mut clone = unsafe { ClonedVmt::new_in(&program, &mut object) }.unwrap();
+        assert_eq!(clone.len(), VFTABLE_LEN);
+        assert_ne!(object.vftable, vftable.va());
+
+        let cloned = |index: usize| unsafe { *object.vftable.add(index) };
+        // Hooks on the shared vftable aren't copied.
+        assert_eq!(cloned(0), original);
+        assert_eq!(cloned(2), vftable.slot(2));
+        // RTTI keeps working.
+        assert_eq!(unsafe { *object.vftable.sub(1) }, 0xc01);
+
+        let replaced: SlotFn = unsafe { clone.replace(2, detour_2 as SlotFn) };
+        assert_eq!(replaced as usize, vftable.slot(2));
+        assert_eq!(cloned(2), detour_2 as SlotFn as usize);
+        // The shared vftable is left alone.
+        assert_eq!(vftable.slot(2), replaced as usize);
+        assert_eq!(vftable.slot(0), detour_1 as SlotFn as usize);
+
+        drop(clone);
+        assert_eq!(object.vftable, vftable.va());
+        drop(shared_hook);
+    }
+
+    #[test]
+    fn unhooked_slot_ignores_hooks() {
+        let hooked: usize = 0xd370;
+        let untouched: usize = 0x1234;
+        let key = &hooked as *const usize as usize;
+
+        VMT_HOOK_CHAINS.lock().unwrap().insert(
+            key,
+            VmtHookChain {
+                original: 0x5000,
+                entries: vec![Arc::new(VmtHookEntry {
+                    detour: hooked,
+                    original: AtomicUsize::new(0x5000),
+                })],
+            },
+        );
+
+        unsafe {
+            assert_eq!(unhooked_slot(&hooked), 0x5000);
+            assert_eq!(unhooked_slot(&untouched), 0x1234);
+        }
+
+        VMT_HOOK_CHAINS.lock().unwrap().remove(&key);
+    }
+}
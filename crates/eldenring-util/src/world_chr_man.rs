@@ -1,9 +1,54 @@
-use eldenring::cs::{ChrIns, FieldInsHandle, WorldChrMan};
+use std::collections::HashSet;
+use std::marker::PhantomData;
+use std::ptr::NonNull;
+
+use eldenring::cs::{
+    ChrIns, ChrSet, ChrType, EnemyIns, FieldInsHandle, MapId, PlayerIns, WorldChrMan,
+};
+use eldenring::dlrf::DLRuntimeClassHolder;
+use eldenring::position::HavokPosition;
 use shared::FSVector4;
 
 pub trait WorldChrManExt {
     fn chr_ins_by_handle(&mut self, handle: &FieldInsHandle) -> Option<&mut ChrIns>;
     fn spawn_debug_character(&mut self, request: &ChrDebugSpawnRequest);
+
+    /// Builds a query over every live character across all ChrSets. Every
+    /// character is yielded exactly once.
+    ///
+    /// ```ignore
+    /// let nearby_enemies = world_chr_man
+    ///     .characters()
+    ///     .chr_type(ChrType::Npc)
+    ///     .within(player_position, 10.0)
+    ///     .alive()
+    ///     .iter()
+    ///     .filter_map(|c| c.as_enemy_ins())
+    ///     .count();
+    /// ```
+    fn characters(&mut self) -> CharacterQuery<'_>;
+
+    /// Looks up the character registered with the specified event entity ID.
+    fn chr_ins_by_entity_id(&mut self, entity_id: u32) -> Option<&mut ChrIns>;
+
+    /// Like [`WorldChrManExt::chr_ins_by_entity_id`] but returns the
+    /// character's handle.
+    fn handle_by_entity_id(&mut self, entity_id: u32) -> Option<FieldInsHandle> {
+        self.chr_ins_by_entity_id(entity_id)
+            .map(|c| c.field_ins_handle.clone())
+    }
+
+    /// Looks up all characters belonging to the specified event group.
+    fn chr_inses_by_group_id(&mut self, group_id: u32) -> Vec<&mut ChrIns>;
+
+    /// Like [`WorldChrManExt::chr_inses_by_group_id`] but returns the
+    /// characters' handles.
+    fn handles_by_group_id(&mut self, group_id: u32) -> Vec<FieldInsHandle> {
+        self.chr_inses_by_group_id(group_id)
+            .into_iter()
+            .map(|c| c.field_ins_handle.clone())
+            .collect()
+    }
 }
 
 impl WorldChrManExt for WorldChrMan {
@@ -37,6 +82,267 @@ impl WorldChrManExt for WorldChrMan {
 
         self.debug_chr_creator.spawn = true;
     }
+
+    fn characters(&mut self) -> CharacterQuery<'_> {
+        CharacterQuery {
+            world_chr_man: NonNull::from(self),
+            chr_type: None,
+            team_type: None,
+            within: None,
+            character_id: None,
+            event_entity_id: None,
+            alive: None,
+            map_id: None,
+            _marker: PhantomData,
+        }
+    }
+
+    fn chr_ins_by_entity_id(&mut self, entity_id: u32) -> Option<&mut ChrIns> {
+        let player = self
+            .player_chr_set
+            .entity_id_mapping
+            .find_by(|m| m.entity_id.cmp(&entity_id))
+            .find_map(|m| unsafe { m.chr_set_entry.as_ref() }.chr_ins)
+            .map(|p| p.cast::<ChrIns>());
+
+        let mut chr_ins = player.or_else(|| {
+            chr_sets(self).find_map(|chr_set| {
+                chr_set
+                    .entity_id_mapping
+                    .find_by(|m| m.entity_id.cmp(&entity_id))
+                    .find_map(|m| unsafe { m.chr_set_entry.as_ref() }.chr_ins)
+            })
+        })?;
+
+        Some(unsafe { chr_ins.as_mut() })
+    }
+
+    fn chr_inses_by_group_id(&mut self, group_id: u32) -> Vec<&mut ChrIns> {
+        let players = self
+            .player_chr_set
+            .group_id_mapping
+            .find_by(|m| m.group_id.cmp(&group_id))
+            .filter_map(|m| unsafe { m.chr_set_entry.as_ref() }.chr_ins)
+            .map(|p| p.cast::<ChrIns>());
+
+        let characters = chr_sets(self).flat_map(|chr_set| {
+            chr_set
+                .group_id_mapping
+                .find_by(|m| m.group_id.cmp(&group_id))
+                .filter_map(|m| unsafe { m.chr_set_entry.as_ref() }.chr_ins)
+        });
+
+        let mut seen = HashSet::new();
+        players
+            .chain(characters)
+            .filter(|c| seen.insert(*c))
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|mut c| unsafe { c.as_mut() })
+            .collect()
+    }
+}
+
+/// Filters over all characters managed by [`WorldChrMan`]. Filters are
+/// combined, a character must match all of them to be yielded.
+pub struct CharacterQuery<'a> {
+    world_chr_man: NonNull<WorldChrMan>,
+    chr_type: Option<ChrType>,
+    team_type: Option<u8>,
+    within: Option<(HavokPosition, f32)>,
+    character_id: Option<u32>,
+    event_entity_id: Option<u32>,
+    alive: Option<bool>,
+    map_id: Option<MapId>,
+    _marker: PhantomData<&'a mut WorldChrMan>,
+}
+
+impl<'a> CharacterQuery<'a> {
+    pub fn chr_type(mut self, chr_type: ChrType) -> Self {
+        self.chr_type = Some(chr_type);
+        self
+    }
+
+    pub fn team_type(mut self, team_type: u8) -> Self {
+        self.team_type = Some(team_type);
+        self
+    }
+
+    /// Only yields characters within `radius` meters of `position`.
+    pub fn within(mut self, position: HavokPosition, radius: f32) -> Self {
+        self.within = Some((position, radius));
+        self
+    }
+
+    pub fn character_id(mut self, character_id: u32) -> Self {
+        self.character_id = Some(character_id);
+        self
+    }
+
+    pub fn event_entity_id(mut self, event_entity_id: u32) -> Self {
+        self.event_entity_id = Some(event_entity_id);
+        self
+    }
+
+    /// Only yields characters with HP left.
+    pub fn alive(mut self) -> Self {
+        self.alive = Some(true);
+        self
+    }
+
+    /// Only yields characters without HP left.
+    pub fn dead(mut self) -> Self {
+        self.alive = Some(false);
+        self
+    }
+
+    /// Only yields characters that are in the specified block.
+    pub fn in_map(mut self, map_id: MapId) -> Self {
+        self.map_id = Some(map_id);
+        self
+    }
+
+    /// Runs the query.
+    pub fn iter(self) -> impl Iterator<Item = &'a mut ChrIns> {
+        // SAFETY: the query holds the mutable borrow on WorldChrMan.
+        let world_chr_man = unsafe { self.world_chr_man.as_ref() };
+
+        let mut seen = HashSet::new();
+        all_characters(world_chr_man)
+            .into_iter()
+            .filter(move |chr_ins| seen.insert(*chr_ins))
+            .map(|mut chr_ins| unsafe { chr_ins.as_mut() })
+            .filter(move |chr_ins| self.matches(chr_ins))
+    }
+
+    fn matches(&self, chr_ins: &ChrIns) -> bool {
+        if self.chr_type.is_some_and(|t| chr_ins.chr_type != t) {
+            return false;
+        }
+
+        if self.team_type.is_some_and(|t| chr_ins.team_type != t) {
+            return false;
+        }
+
+        if self
+            .character_id
+            .is_some_and(|id| chr_ins.character_id != id)
+        {
+            return false;
+        }
+
+        if self
+            .event_entity_id
+            .is_some_and(|id| chr_ins.event_entity_id != id)
+        {
+            return false;
+        }
+
+        if self.map_id.is_some_and(|map_id| chr_ins.map_id_1 != map_id) {
+            return false;
+        }
+
+        if let Some(alive) = self.alive {
+            if (chr_ins.module_container.data.hp > 0) != alive {
+                return false;
+            }
+        }
+
+        if let Some((position, radius)) = self.within {
+            let delta = chr_ins.module_container.physics.position - position;
+            let distance = (delta.0 * delta.0 + delta.1 * delta.1 + delta.2 * delta.2).sqrt();
+            if distance > radius {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+impl<'a> IntoIterator for CharacterQuery<'a> {
+    type Item = &'a mut ChrIns;
+    type IntoIter = Box<dyn Iterator<Item = &'a mut ChrIns> + 'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Box::new(self.iter())
+    }
+}
+
+/// Collects the characters from all ChrSets. Characters can show up in more
+/// than one set (ex: the distance list) so this can contain duplicates.
+fn all_characters(world_chr_man: &WorldChrMan) -> Vec<NonNull<ChrIns>> {
+    let mut characters = world_chr_man
+        .player_chr_set
+        .characters()
+        .map(|p| NonNull::from(&mut p.chr_ins))
+        .collect::<Vec<_>>();
+
+    for chr_set in chr_sets(world_chr_man) {
+        characters.extend(chr_set.characters().map(NonNull::from));
+    }
+
+    characters.extend(
+        world_chr_man
+            .chr_inses_by_distance
+            .items()
+            .iter()
+            .map(|entry| entry.chr_ins),
+    );
+
+    characters
+}
+
+/// All ChrSets holding plain ChrIns.
+fn chr_sets(world_chr_man: &WorldChrMan) -> impl Iterator<Item = &ChrSet<ChrIns>> {
+    [
+        &world_chr_man.ghost_chr_set,
+        &world_chr_man.summon_buddy_chr_set,
+        &world_chr_man.debug_chr_set,
+        &world_chr_man.open_field_chr_set.base,
+    ]
+    .into_iter()
+    .chain(
+        world_chr_man
+            .chr_sets
+            .iter()
+            .filter_map(|chr_set| chr_set.as_deref()),
+    )
+}
+
+/// Typed downcasts for [`ChrIns`] based on the character's runtime class.
+///
+/// The runtime class names are read through
+/// [`DLRuntimeClassVmt::class_name`](eldenring::dlrf::DLRuntimeClassVmt::class_name),
+/// whose slot hasn't been confirmed against a game build. The downcasts are
+/// only tested against a fixture laid out like [`DLRuntimeClass`](eldenring::dlrf::DLRuntimeClass).
+pub trait ChrInsExt {
+    fn as_player_ins(&self) -> Option<&PlayerIns>;
+    fn as_player_ins_mut(&mut self) -> Option<&mut PlayerIns>;
+    fn as_enemy_ins(&self) -> Option<&EnemyIns>;
+    fn as_enemy_ins_mut(&mut self) -> Option<&mut EnemyIns>;
+}
+
+impl ChrInsExt for ChrIns {
+    fn as_player_ins(&self) -> Option<&PlayerIns> {
+        self.is_a("PlayerIns")
+            .then(|| unsafe { &*(self as *const ChrIns as *const PlayerIns) })
+    }
+
+    fn as_player_ins_mut(&mut self) -> Option<&mut PlayerIns> {
+        self.is_a("PlayerIns")
+            .then(|| unsafe { &mut *(self as *mut ChrIns as *mut PlayerIns) })
+    }
+
+    fn as_enemy_ins(&self) -> Option<&EnemyIns> {
+        self.is_a("EnemyIns")
+            .then(|| unsafe { &*(self as *const ChrIns as *const EnemyIns) })
+    }
+
+    fn as_enemy_ins_mut(&mut self) -> Option<&mut EnemyIns> {
+        self.is_a("EnemyIns")
+            .then(|| unsafe { &mut *(self as *mut ChrIns as *mut EnemyIns) })
+    }
 }
 
 pub struct ChrDebugSpawnRequest {
@@ -50,3 +356,98 @@ pub struct ChrDebugSpawnRequest {
     pub pos_y: f32,
     pub pos_z: f32,
 }
+
+#[cfg(test)]
+mod test {
+    use std::ffi::{c_char, CStr};
+    use std::ptr::NonNull;
+
+    use eldenring::cs::ChrIns;
+    use eldenring::dlrf::{DLRuntimeClass, DLRuntimeClassVmt};
+    use vtable_rs::VPtr;
+
+    use super::ChrInsExt;
+
+    /// Runtime class with the layout of DLRuntimeClass.
+    #[repr(C)]
+    struct TestRuntimeClass {
+        vftable: VPtr<dyn DLRuntimeClassVmt, Self>,
+        base_class: Option<NonNull<DLRuntimeClass>>,
+        unk10: [usize; 7],
+        name: &'static CStr,
+    }
+
+    impl TestRuntimeClass {
+        fn new(name: &'static CStr, base: Option<&TestRuntimeClass>) -> Self {
+            Self {
+                vftable: Default::default(),
+                base_class: base.map(|b| NonNull::from(b).cast()),
+                unk10: [0; 7],
+                name,
+            }
+        }
+    }
+
+    impl DLRuntimeClassVmt for TestRuntimeClass {
+        extern "C" fn destructor(&mut self, _param_2: u32) {}
+
+        extern "C" fn class_name(&self) -> *const c_char {
+            self.name.as_ptr()
+        }
+
+        extern "C" fn class_name_w(&self) -> *const u16 {
+            std::ptr::null()
+        }
+    }
+
+    /// Stands in for FieldInsBaseVmt::get_runtime_class, the fixture
+    /// characters keep their runtime class right after the vftable.
+    extern "C" fn get_runtime_class(chr_ins: &ChrIns) -> &DLRuntimeClass {
+        unsafe { &**(chr_ins as *const ChrIns as *const *const DLRuntimeClass).add(1) }
+    }
+
+    static CHR_INS_VFTABLE: [extern "C" fn(&ChrIns) -> &DLRuntimeClass; 1] = [get_runtime_class];
+
+    /// Zeroed memory the size of a ChrIns with only the vftable and runtime
+    /// class filled in.
+    struct TestChrIns(Vec<usize>);
+
+    impl TestChrIns {
+        fn new(class: &TestRuntimeClass) -> Self {
+            let mut words = vec![0; size_of::<ChrIns>().div_ceil(size_of::<usize>())];
+            words[0] = CHR_INS_VFTABLE.as_ptr() as usize;
+            words[1] = class as *const TestRuntimeClass as usize;
+            Self(words)
+        }
+
+        fn chr_ins(&mut self) -> &mut ChrIns {
+            unsafe { &mut *(self.0.as_mut_ptr() as *mut ChrIns) }
+        }
+    }
+
+    #[test]
+    fn downcasts_follow_the_runtime_class() {
+        let field_ins = TestRuntimeClass::new(c"CSFieldInsBase", None);
+        let chr_ins = TestRuntimeClass::new(c"ChrIns", Some(&field_ins));
+        let player_ins = TestRuntimeClass::new(c"PlayerIns", Some(&chr_ins));
+        let enemy_ins = TestRuntimeClass::new(c"EnemyIns", Some(&chr_ins));
+
+        let mut player = TestChrIns::new(&player_ins);
+        let player = player.chr_ins();
+        assert!(player.as_player_ins().is_some());
+        assert!(player.as_player_ins_mut().is_some());
+        assert!(player.as_enemy_ins().is_none());
+        assert!(player.as_enemy_ins_mut().is_none());
+
+        let mut enemy = TestChrIns::new(&enemy_ins);
+        let enemy = enemy.chr_ins();
+        assert!(enemy.as_player_ins().is_none());
+        assert!(enemy.as_enemy_ins().is_some());
+        assert!(enemy.as_enemy_ins_mut().is_some());
+
+        let mut other = TestChrIns::new(&chr_ins);
+        let other = other.chr_ins();
+        assert!(other.as_player_ins().is_none());
+        assert!(other.as_enemy_ins().is_none());
+    }
+}
@@ -4,6 +4,8 @@ use std::ffi::OsStr;
 use std::ptr::NonNull;
 use vtable_rs::VPtr;
 
+use crate::dlrf::{DLRuntimeClass, DLRuntimeClassHolder};
+
 use super::{CSBulletTargetingSystemOwner, CSTargetingSystemBase, FieldInsBaseVmt, FieldInsHandle};
 
 pub struct BulletParamLookupResult {
@@ -66,6 +68,12 @@ pub struct CSBulletIns {
     unke28: u64,
 }
 
+impl DLRuntimeClassHolder for CSBulletIns {
+    fn runtime_class(&self) -> &DLRuntimeClass {
+        (self.vftable.get_runtime_class)(self)
+    }
+}
+
 #[repr(C)]
 pub struct BulletPhysics {
     pub position: HavokPosition,
@@ -20,6 +20,7 @@ use crate::cs::session_manager::{SessionManagerPlayerEntry, SessionManagerPlayer
 use crate::cs::task::{CSEzRabbitNoUpdateTask, CSEzVoidTask};
 use crate::cs::world_chr_man::{ChrSetEntry, WorldBlockChr};
 use crate::cs::world_geom_man::{CSMsbParts, CSMsbPartsEne};
+use crate::dlrf::{runtime_class_of, DLRuntimeClass, DLRuntimeClassHolder};
 
 use super::{ItemId, NpcSpEffectEquipCtrl, SpecialEffect};
 
@@ -209,6 +210,13 @@ pub struct ChrIns {
     unk548: [u8; 0x38],
 }
 
+impl DLRuntimeClassHolder for ChrIns {
+    fn runtime_class(&self) -> &DLRuntimeClass {
+        // SAFETY: ChrInsVmt extends FieldInsBaseVmt which starts with get_runtime_class.
+        unsafe { runtime_class_of(self) }
+    }
+}
+
 #[repr(C)]
 pub struct ChrInsFlags([u8; 5]);
 
@@ -765,6 +773,12 @@ pub struct PlayerIns {
     pub block_orientation: f32,
 }
 
+impl DLRuntimeClassHolder for PlayerIns {
+    fn runtime_class(&self) -> &DLRuntimeClass {
+        self.chr_ins.runtime_class()
+    }
+}
+
 impl AsRef<ChrIns> for PlayerIns {
     fn as_ref(&self) -> &ChrIns {
         &self.chr_ins
@@ -786,6 +800,12 @@ pub struct EnemyIns {
     unk5b8: [u8; 0x28],
 }
 
+impl DLRuntimeClassHolder for EnemyIns {
+    fn runtime_class(&self) -> &DLRuntimeClass {
+        self.chr_ins.runtime_class()
+    }
+}
+
 #[repr(C)]
 /// Source of name: RTTI
 pub struct PlayerSessionHolder {
@@ -1,4 +1,5 @@
 use std::mem::ManuallyDrop;
+use std::ptr::NonNull;
 
 use crate::Tree;
 use shared::{FSVector4, OwnedPtr};
@@ -61,48 +62,36 @@ impl CSFD4VirtualMemoryFlag {
     /// Sets the event flag bit for a given event flag. Does not inherently network set flags.
     pub fn set_flag(&mut self, flag: impl Into<EventFlag>, state: bool) {
         let flag: EventFlag = flag.into();
-        let Some(group) = self
-            .flag_block_descriptors
-            .iter()
-            .find(|d| d.group == flag.group())
-        else {
+        let Some(mut location) = self.flag_block(flag.group()) else {
             return;
         };
 
-        let Some(location) = self.flag_block(group) else {
-            return;
-        };
-
-        location.set(flag, state)
+        unsafe { location.as_mut() }.set(flag, state)
     }
 
     /// Retrieves the event flag current state.
     pub fn get_flag(&self, flag: impl Into<EventFlag>) -> bool {
         let flag: EventFlag = flag.into();
-        let Some(group) = self
-            .flag_block_descriptors
-            .iter()
-            .find(|d| d.group == flag.group())
-        else {
+        let Some(location) = self.flag_block(flag.group()) else {
             return false;
         };
 
-        let Some(location) = self.flag_block(group) else {
-            return false;
-        };
-
-        location.get(flag)
+        unsafe { location.as_ref() }.get(flag)
     }
 
-    /// Locates a flag block for a given FlagBlockDescriptor.
-    fn flag_block<'a>(&self, descriptor: &'a mut FlagBlockDescriptor) -> Option<&'a mut FlagBlock> {
-        Some(match descriptor.location_mode {
+    /// Locates the flag block for a given event flag group.
+    fn flag_block(&self, group: u32) -> Option<NonNull<FlagBlock>> {
+        let descriptor = self
+            .flag_block_descriptors
+            .find_by(|d| d.group.cmp(&group))
+            .next()?;
+
+        NonNull::new(match descriptor.location_mode {
             1 => unsafe {
                 self.flag_blocks
                     .add(descriptor.location.holder_offset as usize)
-                    .as_mut()?
             },
-            2 => unsafe { (*descriptor.location.external_location).as_mut() },
+            2 => unsafe { descriptor.location.external_location.as_ptr() },
             _ => return None,
         })
     }
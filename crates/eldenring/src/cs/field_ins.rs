@@ -1,6 +1,7 @@
 use std::fmt::Display;
 
 use super::{AtkParamLookupResult, MapId};
+use crate::dlrf::DLRuntimeClass;
 
 /// Used to reference a specific FieldIns managed by its respective (external) domain.
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
@@ -72,7 +73,7 @@ impl FieldInsSelector {
 /// Describes the VMT for the FieldInsBase which ChrIns, GeomIns, BulletIns, etc derive from.
 pub trait FieldInsBaseVmt {
     /// Part of FieldInsBase, retrieves reflection metadata for FD4Component derivants.
-    fn get_runtime_metadata(&self) -> usize;
+    fn get_runtime_class(&self) -> &DLRuntimeClass;
 
     fn destructor(&mut self, param_2: u32);
 
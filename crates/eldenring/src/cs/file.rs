@@ -4,13 +4,14 @@ use std::ptr::NonNull;
 use vtable_rs::VPtr;
 
 use crate::dlkr::DLPlainLightMutex;
+use crate::dlrf::{DLRuntimeClass, DLRuntimeClassHolder};
 use crate::fd4::{FD4BasicHashString, FD4FileCap, FD4ResCap, FD4ResCapHolder, FD4ResRep};
 use crate::DoublyLinkedList;
 use shared::OwnedPtr;
 
 #[vtable_rs::vtable]
 pub trait CSFileImpVmt {
-    fn get_runtime_metadata(&self) -> usize;
+    fn get_runtime_class(&self) -> &DLRuntimeClass;
 
     fn destructor(&mut self, param_2: u32);
 
@@ -47,13 +48,72 @@ pub trait CSFileImpVmt {
 }
 
 /// Manages files used by the file, both virtual and on-disk.
+///
+/// Registered in the singleton table without the Imp suffix, like CSTaskImp
+/// and CSWindowImp.
 #[repr(C)]
+#[dlrf::singleton("CSFile")]
 pub struct CSFileImp {
     vftable: VPtr<dyn CSFileImpVmt, Self>,
     pub file_repository_1: OwnedPtr<CSFileRepository>,
     // TODO: Incomplete..
 }
 
+impl DLRuntimeClassHolder for CSFileImp {
+    fn runtime_class(&self) -> &DLRuntimeClass {
+        (self.vftable.get_runtime_class)(self)
+    }
+}
+
+impl CSFileImpVmt for CSFileImp {
+    extern "C" fn get_runtime_class(&self) -> &DLRuntimeClass {
+        (self.vftable.get_runtime_class)(self)
+    }
+
+    extern "C" fn destructor(&mut self, param_2: u32) {
+        (self.vftable.destructor)(self, param_2)
+    }
+
+    extern "C" fn get_file_cap(
+        &mut self,
+        name: &FD4BasicHashString,
+    ) -> Option<NonNull<UntypedFileCap>> {
+        (self.vftable.get_file_cap)(self, name)
+    }
+
+    extern "C" fn add_file_cap(
+        &mut self,
+        name: &FD4BasicHashString,
+        file_cap: &UntypedFileCap,
+        file_loading_queue: u32,
+    ) {
+        (self.vftable.add_file_cap)(self, name, file_cap, file_loading_queue)
+    }
+
+    extern "C" fn unk_add_file_cap(
+        &mut self,
+        name: &FD4BasicHashString,
+        file_cap: &UntypedFileCap,
+        param_4: usize,
+        param_5: usize,
+        file_loading_queue: u32,
+    ) {
+        (self.vftable.unk_add_file_cap)(self, name, file_cap, param_4, param_5, file_loading_queue)
+    }
+
+    extern "C" fn unload_file_cap_by_name(&mut self, name: &FD4BasicHashString) {
+        (self.vftable.unload_file_cap_by_name)(self, name)
+    }
+
+    extern "C" fn unload_file_cap(&mut self, file_cap: &UntypedFileCap) {
+        (self.vftable.unload_file_cap)(self, file_cap)
+    }
+
+    extern "C" fn unk40(&mut self, file_cap: &UntypedFileCap) {
+        (self.vftable.unk40)(self, file_cap)
+    }
+}
+
 /// Manages a set of files as well as keeps track of load state and such.
 #[repr(C)]
 pub struct CSFileRepository {
@@ -1,6 +1,8 @@
 use std::{
+    ffi::CStr,
     fmt::Display,
-    io::{Cursor, Read, Seek, SeekFrom},
+    fs::File,
+    io::{Cursor, ErrorKind, Read, Seek, SeekFrom, Write},
     ptr::NonNull,
 };
 
@@ -8,7 +10,7 @@ use vtable_rs::VPtr;
 
 use crate::{
     dlio::DLIOResult,
-    dlkr::{DLAllocatorBase, DLPlainLightMutex},
+    dlkr::{DLAllocatorBase, DLAllocatorRef, DLPlainLightMutex},
     dltx::{DLBasicString, DLString},
     dlut::DLDateTime,
     Vector,
@@ -171,7 +173,11 @@ pub trait DLFileOperatorVmt {
     /// It can be less than length if the file is smaller or, value is bigger than u32::MAX because of WinAPI limitations.
     unsafe fn read(&mut self, output: *mut u8, length: usize) -> i32;
 
-    fn write(&mut self, input: *const u8, length: usize) -> usize;
+    /// # Safety
+    ///
+    /// Caller must ensure that `input` points to at least `length` readable bytes.
+    /// Returns amount of bytes written.
+    unsafe fn write(&mut self, input: *const u8, length: usize) -> usize;
 
     fn get_async_block_size(&self) -> usize;
 
@@ -182,7 +188,10 @@ pub trait DLFileOperatorVmt {
     /// Caller must ensure that `output` is a pointer to valid memory.
     unsafe fn start_async_read(&mut self, output: *mut u8, length: usize) -> bool;
 
-    fn start_async_write(&mut self, input: *const u8, length: usize) -> bool;
+    /// # Safety
+    ///
+    /// Caller must ensure that `input` points to at least `length` readable bytes.
+    unsafe fn start_async_write(&mut self, input: *const u8, length: usize) -> bool;
 
     fn query_async_status(
         &mut self,
@@ -203,9 +212,15 @@ pub trait DLFileOperatorVmt {
 
     fn unk2(&mut self) -> bool;
 
-    fn rename_w(&mut self, path: *const u16) -> bool;
+    /// # Safety
+    ///
+    /// Caller must ensure that `path` is a null-terminated UTF-16 string.
+    unsafe fn rename_w(&mut self, path: *const u16) -> bool;
 
-    fn rename(&mut self, path: *const u8) -> bool;
+    /// # Safety
+    ///
+    /// Caller must ensure that `path` is a null-terminated string.
+    unsafe fn rename(&mut self, path: *const u8) -> bool;
 
     fn create_directory(&mut self) -> bool;
 }
@@ -278,8 +293,7 @@ where
             owning_operator_container: NonNull::from(operator_container),
             io_state: DLFileOperatorIOState::default(),
             owning_file_device: NonNull::from(file_device),
-            // TODO: Implement DLString copy constructor
-            path: DLString::default(),
+            path: path.clone_in(unsafe { DLAllocatorRef::new(NonNull::from(allocator)) }),
         }
     }
 }
@@ -358,26 +372,128 @@ impl DLFileDeviceVmt for DLFileDeviceBase {
     }
 }
 
+/// Write side of the storage behind a writable [AdapterFileOperator].
+///
+/// Only `set_len` is required, the file system operations default to
+/// [ErrorKind::Unsupported] for storage that doesn't live on disk.
+pub trait AdapterFileWrite: Write {
+    /// Resizes the storage, used when the game truncates the file at its cursor.
+    fn set_len(&mut self, length: u64) -> std::io::Result<()>;
+
+    /// Called when the game closes the file. Implementations holding OS handles should
+    /// release them here so the file can be renamed or deleted afterwards.
+    fn close(&mut self) -> std::io::Result<()> {
+        self.flush()
+    }
+
+    /// Whether the file exists in the storage. Opening a file that doesn't exist without
+    /// write or append access fails with [DLIOResult::NotFound].
+    fn exists(&self) -> bool {
+        true
+    }
+
+    fn delete(&mut self) -> std::io::Result<()> {
+        Err(ErrorKind::Unsupported.into())
+    }
+
+    /// Moves the file to `path`, as passed by the game.
+    fn rename(&mut self, path: &str) -> std::io::Result<()> {
+        Err(ErrorKind::Unsupported.into())
+    }
+
+    /// Creates a directory at the file's path.
+    fn create_directory(&mut self) -> std::io::Result<()> {
+        Err(ErrorKind::Unsupported.into())
+    }
+}
+
+impl AdapterFileWrite for Cursor<Vec<u8>> {
+    fn set_len(&mut self, length: u64) -> std::io::Result<()> {
+        self.get_mut().resize(length as usize, 0);
+        Ok(())
+    }
+}
+
+impl AdapterFileWrite for File {
+    fn set_len(&mut self, length: u64) -> std::io::Result<()> {
+        File::set_len(self, length)
+    }
+}
+
+impl<T: AdapterFileWrite + ?Sized> AdapterFileWrite for Box<T> {
+    fn set_len(&mut self, length: u64) -> std::io::Result<()> {
+        (**self).set_len(length)
+    }
+
+    fn close(&mut self) -> std::io::Result<()> {
+        (**self).close()
+    }
+
+    fn exists(&self) -> bool {
+        (**self).exists()
+    }
+
+    fn delete(&mut self) -> std::io::Result<()> {
+        (**self).delete()
+    }
+
+    fn rename(&mut self, path: &str) -> std::io::Result<()> {
+        (**self).rename(path)
+    }
+
+    fn create_directory(&mut self) -> std::io::Result<()> {
+        (**self).create_directory()
+    }
+}
+
+/// File operator that serves a rust [Read] + [Seek] implementation to the game.
+///
+/// Operators created with [AdapterFileOperator::new] are read-only, calls that would modify
+/// the file fail with [DLIOResult::OperationUnsupported] or [DLIOResult::AccessDenied].
+/// Operators created with [AdapterFileOperator::new_writable] pass writes, truncation and
+/// file system operations on to the [AdapterFileWrite] implementation of the buffer.
+///
+/// Async reads and writes complete synchronously, [DLFileOperatorVmt::query_async_status]
+/// reports the last transfer as done.
 #[repr(C)]
 pub struct AdapterFileOperator<R>
 where
     R: Read + Seek + 'static,
 {
-    pub base: DLFileOperatorBase,
+    pub base: DLFileOperatorBase<Self>,
     buffer: R,
+    /// Open mode passed to the latest open call.
+    open_mode: OpenFileMode,
+    /// FILETIME reported for both last access and last modify time.
+    modify_time: u64,
+    /// Cached cursor position and length of buffer, used by the methods taking &self.
+    position: u64,
+    length: u64,
+    /// Amount of bytes transferred by the latest async read or write.
+    async_transferred: usize,
+    /// Exposes the write side of buffer, None for read-only operators.
+    writer: Option<fn(&mut R) -> &mut dyn AdapterFileWrite>,
 }
 
 impl<R> AdapterFileOperator<R>
 where
     R: Read + Seek + 'static,
 {
+    /// Block size reported to the game for async reads. Reads complete synchronously so the
+    /// value only influences how the game chunks its requests.
+    const ASYNC_BLOCK_SIZE: usize = 0x10000;
+    const ASYNC_BUFFER_ALIGNMENT: usize = 0x10;
+
     pub fn new(
         allocator: &DLAllocatorBase,
         path: &DLString,
         operator_container: &DLFileOperatorContainer,
         file_device: &DLFileDeviceBase,
-        buffer: R,
+        mut buffer: R,
     ) -> Self {
+        let length = stream_length(&mut buffer).unwrap_or_default();
+        let position = buffer.stream_position().unwrap_or_default();
+
         Self {
             base: DLFileOperatorBase::new(
                 Default::default(),
@@ -387,8 +503,143 @@ where
                 file_device,
             ),
             buffer,
+            open_mode: OpenFileMode(0),
+            modify_time: 0,
+            position,
+            length,
+            async_transferred: 0,
+            writer: None,
         }
     }
+
+    /// Creates an operator that also allows the game to write to `buffer`.
+    pub fn new_writable(
+        allocator: &DLAllocatorBase,
+        path: &DLString,
+        operator_container: &DLFileOperatorContainer,
+        file_device: &DLFileDeviceBase,
+        buffer: R,
+    ) -> Self
+    where
+        R: AdapterFileWrite,
+    {
+        fn as_writer<W: AdapterFileWrite>(buffer: &mut W) -> &mut dyn AdapterFileWrite {
+            buffer
+        }
+
+        Self {
+            writer: Some(as_writer::<R>),
+            ..Self::new(allocator, path, operator_container, file_device, buffer)
+        }
+    }
+
+    /// Sets the FILETIME reported as last access and last modify time.
+    pub fn with_modify_time(mut self, time64: u64) -> Self {
+        self.modify_time = time64;
+        self
+    }
+
+    fn finish(&mut self, result: DLIOResult) -> bool {
+        let success = matches!(result, DLIOResult::Success);
+        self.base.result = result;
+        success
+    }
+
+    fn writer(&mut self) -> Option<&mut dyn AdapterFileWrite> {
+        let writer = self.writer?;
+        Some(writer(&mut self.buffer))
+    }
+
+    /// Applies an operation on the write side of the buffer, setting the result accordingly.
+    fn with_writer<F>(&mut self, operation: F) -> bool
+    where
+        F: FnOnce(&mut dyn AdapterFileWrite) -> std::io::Result<()>,
+    {
+        let result = match self.writer() {
+            Some(writer) => match operation(writer) {
+                Ok(()) => DLIOResult::Success,
+                Err(e) => io_result(&e),
+            },
+            None => DLIOResult::OperationUnsupported,
+        };
+
+        self.finish(result)
+    }
+
+    fn write_from(&mut self, input: &[u8]) -> std::io::Result<()> {
+        let Some(writer) = self.writer() else {
+            return Err(ErrorKind::Unsupported.into());
+        };
+
+        let result = writer.write_all(input);
+        self.position = match result {
+            Ok(()) => self.position + input.len() as u64,
+            // Partial writes leave the cursor in an unknown spot.
+            Err(_) => self.buffer.stream_position().unwrap_or(self.position),
+        };
+        self.length = self.length.max(self.position);
+
+        result
+    }
+
+    fn rename_to(&mut self, path: String) -> bool {
+        if !self.with_writer(|writer| writer.rename(&path)) {
+            return false;
+        }
+
+        self.base.path.assign(&path);
+        true
+    }
+
+    fn write_time(&self, ptr: *const DLDateTime) -> *const DLDateTime {
+        if !ptr.is_null() {
+            unsafe {
+                ptr.cast_mut()
+                    .write(DLDateTime::from_time64(self.modify_time, true))
+            };
+        }
+
+        ptr
+    }
+
+    fn read_into(&mut self, output: &mut [u8]) -> std::io::Result<usize> {
+        let mut filled = 0;
+        while filled < output.len() {
+            match self.buffer.read(&mut output[filled..]) {
+                Ok(0) => break,
+                Ok(read) => filled += read,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+
+        self.position += filled as u64;
+        Ok(filled)
+    }
+}
+
+/// Maps IO errors onto the closest result the game knows about.
+fn io_result(error: &std::io::Error) -> DLIOResult {
+    match error.kind() {
+        ErrorKind::NotFound => DLIOResult::NotFound,
+        ErrorKind::PermissionDenied => DLIOResult::AccessDenied,
+        ErrorKind::StorageFull => DLIOResult::DiskFull,
+        ErrorKind::OutOfMemory => DLIOResult::OutOfMemory,
+        ErrorKind::DirectoryNotEmpty => DLIOResult::DirNotEmpty,
+        ErrorKind::Unsupported => DLIOResult::OperationUnsupported,
+        _ => DLIOResult::Invalid,
+    }
+}
+
+/// Determines the length of the stream without moving its cursor.
+fn stream_length<S: Seek>(stream: &mut S) -> std::io::Result<u64> {
+    let current = stream.stream_position()?;
+    let end = stream.seek(SeekFrom::End(0))?;
+    if current != end {
+        stream.seek(SeekFrom::Start(current))?;
+    }
+
+    Ok(end)
 }
 
 impl<R> Display for AdapterFileOperator<R>
@@ -404,13 +655,21 @@ impl<R> DLFileOperatorVmt for AdapterFileOperator<R>
 where
     R: Read + Seek + 'static,
 {
+    /// Drops the reader and frees the path in place. The memory of the operator itself is
+    /// released by its owner through the allocator it was created with.
     extern "C" fn destructor(&mut self) {
         tracing::debug!("{self}::destructor()");
+
+        unsafe {
+            std::ptr::drop_in_place(&mut self.buffer);
+            // The path was copied into the operator's allocator on construction.
+            self.base.path.free();
+        }
     }
 
     extern "C" fn copy_from(&mut self, source: &DLFileOperatorBase) -> bool {
         tracing::debug!("{self}::copy_from()");
-        unimplemented!()
+        self.finish(DLIOResult::OperationUnsupported)
     }
 
     extern "C" fn set_path(&mut self, path: &DLString, param_3: bool, param_4: bool) -> bool {
@@ -433,13 +692,7 @@ where
         param_3: bool,
         param_4: bool,
     ) -> bool {
-        tracing::debug!(
-            "{self}::set_path_other_1({}, {}, {})",
-            path.to_string(),
-            param_3,
-            param_4
-        );
-        unimplemented!()
+        self.set_path(path, param_3, param_4)
     }
 
     extern "C" fn set_path_other_2(
@@ -448,13 +701,7 @@ where
         param_3: bool,
         param_4: bool,
     ) -> bool {
-        tracing::debug!(
-            "{self}::set_path_other_2({}, {}, {})",
-            path.to_string(),
-            param_3,
-            param_4
-        );
-        unimplemented!()
+        self.set_path(path, param_3, param_4)
     }
 
     extern "C" fn set_state(&mut self, param_2: bool, param_3: bool) -> bool {
@@ -468,12 +715,12 @@ where
 
     extern "C" fn clear_file_info(&mut self) -> bool {
         tracing::debug!("{self}::clear_file_info()");
-        unimplemented!()
+        self.close()
     }
 
     extern "C" fn get_virtual_disk_operator(&self) -> *const DLFileOperatorBase {
         tracing::debug!("{self}::get_virtual_disk_operator()");
-        unimplemented!()
+        std::ptr::null()
     }
 
     extern "C" fn bind_device_image(
@@ -481,79 +728,120 @@ where
         image_spi: &DLFileDeviceImageSPIBase,
     ) -> *const DLFileDeviceImageSPIBase {
         tracing::debug!("{self}::bind_device_image()");
-        unimplemented!()
+        std::ptr::null()
     }
 
     extern "C" fn is_readable(&mut self) -> bool {
         tracing::debug!("{self}::is_readable()");
-        unimplemented!()
+        self.writer().is_none_or(|writer| writer.exists())
     }
+
     extern "C" fn is_writable(&mut self) -> bool {
         tracing::debug!("{self}::is_writable()");
-        unimplemented!()
+        self.writer.is_some()
     }
 
     extern "C" fn last_access_time(&self, ptr: *const DLDateTime) -> *const DLDateTime {
         tracing::debug!("{self}::last_access_time()");
-        unimplemented!()
+        self.write_time(ptr)
     }
 
     extern "C" fn last_modify_time(&self, ptr: *const DLDateTime) -> *const DLDateTime {
         tracing::debug!("{self}::last_modify_time()");
-        unimplemented!()
+        self.write_time(ptr)
     }
 
     extern "C" fn file_size(&mut self) -> usize {
-        let current = self.buffer.stream_position().unwrap();
-        let end = self.buffer.seek(SeekFrom::End(0)).unwrap() as usize;
-        self.buffer.seek(SeekFrom::Start(current));
-        tracing::debug!("{self}::file_size() -> {end}");
-        end
+        if let Ok(length) = stream_length(&mut self.buffer) {
+            self.length = length;
+        }
+
+        tracing::debug!("{self}::file_size() -> {}", self.length);
+        self.length as usize
     }
 
     extern "C" fn get_read_size(&mut self) -> usize {
         tracing::debug!("{self}::get_read_size()");
-        unimplemented!()
+        self.length.saturating_sub(self.position) as usize
     }
 
     extern "C" fn get_write_size(&self) -> usize {
         tracing::debug!("{self}::get_write_size()");
-        unimplemented!()
+        match self.writer {
+            Some(_) => self.length.saturating_sub(self.position) as usize,
+            None => 0,
+        }
     }
 
     extern "C" fn set_eof(&mut self) {
         tracing::debug!("{self}::set_eof()");
-        unimplemented!()
+
+        let position = self.position;
+        if self.with_writer(|writer| writer.set_len(position)) {
+            self.length = position;
+        }
     }
 
     extern "C" fn is_eof(&self) -> bool {
         tracing::debug!("{self}::is_eof()");
-        unimplemented!()
+        self.position >= self.length
     }
 
     extern "C" fn is_directory(&self) -> bool {
         tracing::debug!("{self}::is_directory()");
-        unimplemented!()
+        false
     }
 
     extern "C" fn is_open(&self) -> bool {
         tracing::debug!("{self}::is_open()");
-        true
+        self.base.io_state.is_open()
     }
 
     extern "C" fn open(&mut self, open_mode: OpenFileMode) -> bool {
         tracing::debug!("{self}::open({:?})", open_mode);
-        true
+
+        let writing = open_mode.write() || open_mode.append();
+        match self.writer() {
+            None if writing => return self.finish(DLIOResult::AccessDenied),
+            // Writable storage is created on the first write, reading it before then fails
+            // like reading any other missing file.
+            Some(writer) if !writing && !writer.exists() => {
+                return self.finish(DLIOResult::NotFound)
+            }
+            _ => {}
+        }
+
+        let start = match open_mode.append() {
+            true => SeekFrom::End(0),
+            false => SeekFrom::Start(0),
+        };
+        let position = match self.buffer.seek(start) {
+            Ok(position) => position,
+            Err(e) => return self.finish(io_result(&e)),
+        };
+
+        self.open_mode = open_mode;
+        self.position = position;
+        // Writable storage may have been created or changed since the operator was made.
+        self.length = stream_length(&mut self.buffer).unwrap_or(position);
+        self.base.io_state.0 |= 0x1;
+        self.finish(DLIOResult::Success)
     }
 
     extern "C" fn close(&mut self) -> bool {
         tracing::debug!("{self}::close()");
-        true
+
+        let was_open = self.base.io_state.is_open();
+        self.base.io_state.0 &= !0x1;
+        match was_open && self.writer.is_some() {
+            true => self.with_writer(|writer| writer.close()),
+            false => self.finish(DLIOResult::Success),
+        }
     }
 
     extern "C" fn set_read_only(&mut self, is_open: bool) -> bool {
         tracing::debug!("{self}::set_read_only({})", is_open);
-        unimplemented!()
+        true
     }
 
     extern "C" fn seek(
@@ -563,91 +851,564 @@ where
         seek_mode: DLFileSeekDirection,
     ) -> bool {
         tracing::debug!("{self}::seek({}, {}, {:?})", is_stream, offset, seek_mode);
-        unimplemented!()
+
+        let target = match seek_mode {
+            DLFileSeekDirection::Head => match u64::try_from(offset) {
+                Ok(offset) => SeekFrom::Start(offset),
+                Err(_) => return self.finish(DLIOResult::Invalid),
+            },
+            DLFileSeekDirection::Current => SeekFrom::Current(offset),
+            DLFileSeekDirection::Tail => SeekFrom::End(offset),
+        };
+
+        match self.buffer.seek(target) {
+            Ok(position) => {
+                self.position = position;
+                self.finish(DLIOResult::Success)
+            }
+            Err(e) => self.finish(io_result(&e)),
+        }
     }
 
     extern "C" fn cursor_position(&self) -> usize {
         tracing::debug!("{self}::cursor_position()");
-        unimplemented!()
+        self.position as usize
     }
 
     unsafe extern "C" fn read(&mut self, output: *mut u8, length: usize) -> i32 {
         tracing::debug!("{self}::read({:x?}, {})", output, length);
-        let mut buffer = vec![0x0u8; length];
-        self.buffer.read_exact(&mut buffer).unwrap();
 
-        unsafe { std::ptr::copy_nonoverlapping(buffer.as_ptr(), output, length) };
-        self.base.result = DLIOResult::Success;
-        length as i32
+        if !self.base.io_state.is_open() {
+            self.finish(DLIOResult::Invalid);
+            return -1;
+        }
+        if !self.open_mode.read_only() {
+            self.finish(DLIOResult::AccessDenied);
+            return -1;
+        }
+
+        let length = length.min(i32::MAX as usize);
+        let output = unsafe { std::slice::from_raw_parts_mut(output, length) };
+        match self.read_into(output) {
+            Ok(read) => {
+                self.finish(DLIOResult::Success);
+                read as i32
+            }
+            Err(e) => {
+                tracing::error!("{self}::read failed: {e}");
+                self.finish(io_result(&e));
+                -1
+            }
+        }
     }
 
-    extern "C" fn write(&mut self, input: *const u8, length: usize) -> usize {
+    unsafe extern "C" fn write(&mut self, input: *const u8, length: usize) -> usize {
         tracing::debug!("{self}::write_file({:x?}, {})", input, length);
-        unimplemented!()
+
+        if !self.base.io_state.is_open() || (input.is_null() && length != 0) {
+            self.finish(DLIOResult::Invalid);
+            return 0;
+        }
+        if self.writer.is_none() {
+            self.finish(DLIOResult::OperationUnsupported);
+            return 0;
+        }
+        if !self.open_mode.write() && !self.open_mode.append() {
+            self.finish(DLIOResult::AccessDenied);
+            return 0;
+        }
+
+        let input = match length {
+            0 => &[],
+            _ => unsafe { std::slice::from_raw_parts(input, length) },
+        };
+        match self.write_from(input) {
+            Ok(()) => {
+                self.finish(DLIOResult::Success);
+                length
+            }
+            Err(e) => {
+                tracing::error!("{self}::write failed: {e}");
+                self.finish(io_result(&e));
+                0
+            }
+        }
     }
 
     extern "C" fn get_async_block_size(&self) -> usize {
         tracing::debug!("{self}::get_async_block_size()");
-        unimplemented!()
+        Self::ASYNC_BLOCK_SIZE
     }
+
     extern "C" fn get_async_buffer_alignment_size(&self) -> usize {
         tracing::debug!("{self}::get_async_buffer_alignment_size()");
-        unimplemented!()
+        Self::ASYNC_BUFFER_ALIGNMENT
     }
 
     unsafe extern "C" fn start_async_read(&mut self, output: *mut u8, length: usize) -> bool {
         tracing::debug!("{self}::start_async_read({:x?}, {})", output, length);
-        unimplemented!()
+
+        let read = unsafe { self.read(output, length) };
+        self.async_transferred = read.max(0) as usize;
+        read >= 0
     }
-    extern "C" fn start_async_write(&mut self, input: *const u8, length: usize) -> bool {
+
+    unsafe extern "C" fn start_async_write(&mut self, input: *const u8, length: usize) -> bool {
         tracing::debug!("{self}::start_async_write({:x?}, {})", input, length);
-        unimplemented!()
+
+        self.async_transferred = unsafe { self.write(input, length) };
+        matches!(self.base.result, DLIOResult::Success)
     }
+
     extern "C" fn query_async_status(
         &mut self,
         bytes_remaining: &mut usize,
         bytes_transferred: Option<&mut usize>,
     ) -> bool {
         tracing::debug!("{self}::query_async_status()");
-        unimplemented!()
+
+        *bytes_remaining = 0;
+        if let Some(bytes_transferred) = bytes_transferred {
+            *bytes_transferred = self.async_transferred;
+        }
+
+        true
     }
+
     extern "C" fn get_open_mode(&self) -> OpenFileMode {
         tracing::debug!("{self}::get_open_mode()");
-        unimplemented!()
+        self.open_mode
     }
 
     extern "C" fn delete(&mut self) -> bool {
         tracing::debug!("{self}::delete()");
-        unimplemented!()
+        self.with_writer(|writer| writer.delete())
     }
 
     extern "C" fn flush(&mut self) {
         tracing::debug!("{self}::flush()");
-        unimplemented!()
+
+        if self.writer.is_some() {
+            self.with_writer(|writer| writer.flush());
+        }
     }
 
     extern "C" fn populate_file_info(&mut self) -> bool {
         tracing::debug!("{self}::populate_file_info()");
-        unimplemented!()
+        self.file_size();
+        true
     }
 
     extern "C" fn unk2(&mut self) -> bool {
         tracing::debug!("{self}::unk2()");
-        unimplemented!()
+        self.finish(DLIOResult::OperationUnsupported)
     }
 
-    extern "C" fn rename_w(&mut self, path: *const u16) -> bool {
+    unsafe extern "C" fn rename_w(&mut self, path: *const u16) -> bool {
         tracing::debug!("{self}::rename_w({:x?})", path);
-        unimplemented!()
+
+        if path.is_null() {
+            return self.finish(DLIOResult::Invalid);
+        }
+
+        let path = unsafe {
+            let length = (0..).take_while(|&i| *path.add(i) != 0).count();
+            String::from_utf16_lossy(std::slice::from_raw_parts(path, length))
+        };
+        self.rename_to(path)
     }
 
-    extern "C" fn rename(&mut self, path: *const u8) -> bool {
+    unsafe extern "C" fn rename(&mut self, path: *const u8) -> bool {
         tracing::debug!("{self}::rename({:x?})", path);
-        unimplemented!()
+
+        if path.is_null() {
+            return self.finish(DLIOResult::Invalid);
+        }
+
+        let path = unsafe { CStr::from_ptr(path.cast()) }
+            .to_string_lossy()
+            .into_owned();
+        self.rename_to(path)
     }
 
     extern "C" fn create_directory(&mut self) -> bool {
         tracing::debug!("{self}::create_directory()");
-        unimplemented!()
+        self.with_writer(|writer| writer.create_directory())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+    use std::mem::ManuallyDrop;
+    use std::ptr::NonNull;
+
+    use super::{
+        AdapterFileOperator, AdapterFileWrite, DLFileDeviceBase, DLFileOperatorBase,
+        DLFileOperatorContainer, DLFileOperatorVmt,
+    };
+    use crate::dlio::{DLFileSeekDirection, DLIOResult, OpenFileMode};
+    use crate::dlkr::mock::MockAllocator;
+    use crate::dlkr::DLPlainLightMutex;
+    use crate::dltx::DLString;
+    use shared::OwnedPtr;
+
+    const READ: OpenFileMode = OpenFileMode(0x1);
+    const WRITE: OpenFileMode = OpenFileMode(0x2);
+    const APPEND: OpenFileMode = OpenFileMode(0x4);
+
+    /// Stand-ins for the game objects an operator points at.
+    struct Fixture {
+        allocator: Box<MockAllocator>,
+        container: DLFileOperatorContainer,
+        // Never dropped since tearing down the mutex calls into Windows.
+        device: ManuallyDrop<DLFileDeviceBase>,
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            // Never dereferenced by the tests.
+            let operator = || unsafe { OwnedPtr::from_raw(NonNull::dangling()) };
+
+            Self {
+                allocator: MockAllocator::new(),
+                container: DLFileOperatorContainer {
+                    allocator: NonNull::dangling(),
+                    read_file_operator: operator(),
+                    write_file_operator: operator(),
+                    flags: 0,
+                },
+                device: ManuallyDrop::new(DLFileDeviceBase {
+                    vftable: Default::default(),
+                    unk8: false,
+                    ref_count: 1,
+                    mutex: DLPlainLightMutex {
+                        vftable: Default::default(),
+                        critical_section: Default::default(),
+                    },
+                }),
+            }
+        }
+
+        fn operator<R: Read + Seek>(&mut self, buffer: R) -> AdapterFileOperator<R> {
+            let allocator = self.allocator.handle();
+            let mut path = DLString::new_in("data0:/test/file.bin", allocator);
+
+            let operator = AdapterFileOperator::new(
+                unsafe { allocator.as_ptr().as_ref() },
+                &path,
+                &self.container,
+                &self.device,
+                buffer,
+            );
+            unsafe { path.free() };
+            operator
+        }
+
+        fn writable_operator<R>(&mut self, buffer: R) -> AdapterFileOperator<R>
+        where
+            R: Read + Seek + AdapterFileWrite,
+        {
+            let allocator = self.allocator.handle();
+            let mut path = DLString::new_in("savedata:/ER0000.sl2", allocator);
+
+            let operator = AdapterFileOperator::new_writable(
+                unsafe { allocator.as_ptr().as_ref() },
+                &path,
+                &self.container,
+                &self.device,
+                buffer,
+            );
+            unsafe { path.free() };
+            operator
+        }
+    }
+
+    /// Destroys the operator the way the game does.
+    fn destroy<R: Read + Seek>(operator: AdapterFileOperator<R>) {
+        ManuallyDrop::new(operator).destructor();
+    }
+
+    fn read(operator: &mut AdapterFileOperator<impl Read + Seek>, length: usize) -> Vec<u8> {
+        let mut output = vec![0; length];
+        let read = unsafe { operator.read(output.as_mut_ptr(), length) };
+        output.truncate(read.try_into().unwrap());
+        output
+    }
+
+    #[test]
+    fn read_and_seek() {
+        let mut fixture = Fixture::new();
+        let mut operator = fixture.operator(Cursor::new((0..16).collect::<Vec<u8>>()));
+
+        assert!(operator.open(READ));
+        assert!(operator.is_open());
+        assert_eq!(operator.file_size(), 16);
+        assert_eq!(operator.get_read_size(), 16);
+
+        assert_eq!(read(&mut operator, 4), [0, 1, 2, 3]);
+        assert_eq!(operator.cursor_position(), 4);
+        assert_eq!(operator.get_read_size(), 12);
+        assert!(!operator.is_eof());
+
+        assert!(operator.seek(false, 8, DLFileSeekDirection::Current));
+        assert_eq!(operator.cursor_position(), 12);
+        assert!(operator.seek(false, -2, DLFileSeekDirection::Tail));
+        assert_eq!(operator.cursor_position(), 14);
+
+        // Reads past the end are cut short.
+        assert_eq!(read(&mut operator, 8), [14, 15]);
+        assert!(operator.is_eof());
+        assert_eq!(operator.get_read_size(), 0);
+        assert_eq!(read(&mut operator, 8), []);
+        assert!(matches!(operator.base.result, DLIOResult::Success));
+
+        assert!(operator.seek(false, 1, DLFileSeekDirection::Head));
+        assert_eq!(read(&mut operator, 2), [1, 2]);
+
+        assert!(!operator.seek(false, -1, DLFileSeekDirection::Head));
+        assert!(matches!(operator.base.result, DLIOResult::Invalid));
+        assert_eq!(operator.cursor_position(), 3);
+
+        assert!(operator.close());
+        assert!(!operator.is_open());
+        destroy(operator);
+    }
+
+    #[test]
+    fn reopening_rewinds() {
+        let mut fixture = Fixture::new();
+        let mut operator = fixture.operator(Cursor::new(b"abcdef".to_vec()));
+
+        assert!(operator.open(READ));
+        assert_eq!(read(&mut operator, 4), b"abcd");
+        assert!(operator.close());
+
+        assert!(operator.open(READ));
+        assert_eq!(operator.cursor_position(), 0);
+        assert_eq!(read(&mut operator, 8), b"abcdef");
+        destroy(operator);
+    }
+
+    #[test]
+    fn read_only_operators_reject_writes() {
+        let mut fixture = Fixture::new();
+        let mut operator = fixture.operator(Cursor::new(b"abcdef".to_vec()));
+
+        assert!(!operator.is_writable());
+        assert!(operator.is_readable());
+        assert!(!operator.open(OpenFileMode(0x2)));
+        assert!(matches!(operator.base.result, DLIOResult::AccessDenied));
+        assert!(!operator.open(OpenFileMode(0x4)));
+
+        assert!(operator.open(READ));
+        assert_eq!(operator.get_write_size(), 0);
+        assert_eq!(unsafe { operator.write(b"xy".as_ptr(), 2) }, 0);
+        assert!(matches!(
+            operator.base.result,
+            DLIOResult::OperationUnsupported
+        ));
+
+        operator.set_eof();
+        assert_eq!(operator.file_size(), 6);
+        assert!(!operator.delete());
+        destroy(operator);
+    }
+
+    #[test]
+    fn transfers_require_an_open_file() {
+        let mut fixture = Fixture::new();
+        let mut operator = fixture.writable_operator(Cursor::new(b"abc".to_vec()));
+
+        assert_eq!(unsafe { operator.read([0; 4].as_mut_ptr(), 4) }, -1);
+        assert!(matches!(operator.base.result, DLIOResult::Invalid));
+        assert_eq!(write(&mut operator, b"x"), 0);
+        assert!(matches!(operator.base.result, DLIOResult::Invalid));
+
+        assert!(operator.open(READ));
+        assert!(operator.close());
+        assert_eq!(unsafe { operator.read([0; 4].as_mut_ptr(), 4) }, -1);
+        assert!(matches!(operator.base.result, DLIOResult::Invalid));
+        assert_eq!(operator.buffer.get_ref(), b"abc");
+        destroy(operator);
+    }
+
+    #[test]
+    fn transfers_follow_the_open_mode() {
+        let mut fixture = Fixture::new();
+        let mut operator = fixture.writable_operator(Cursor::new(b"abc".to_vec()));
+
+        assert!(operator.open(READ));
+        assert_eq!(write(&mut operator, b"x"), 0);
+        assert!(matches!(operator.base.result, DLIOResult::AccessDenied));
+        assert_eq!(operator.buffer.get_ref(), b"abc");
+
+        assert!(operator.open(WRITE));
+        assert_eq!(unsafe { operator.read([0; 4].as_mut_ptr(), 4) }, -1);
+        assert!(matches!(operator.base.result, DLIOResult::AccessDenied));
+
+        assert!(operator.open(OpenFileMode(READ.0 | WRITE.0)));
+        assert_eq!(write(&mut operator, b"x"), 1);
+        assert_eq!(read(&mut operator, 4), b"bc");
+        destroy(operator);
+    }
+
+    #[test]
+    fn destructor_frees_the_path() {
+        let mut fixture = Fixture::new();
+        let operator = fixture.operator(Cursor::new(Vec::new()));
+        assert_eq!(operator.base.path.to_string(), "data0:/test/file.bin");
+        assert_eq!(fixture.allocator.live_allocations(), 1);
+
+        destroy(operator);
+        assert_eq!(fixture.allocator.live_allocations(), 0);
+    }
+
+    /// Storage that might not exist yet and records the calls made to it.
+    struct Storage {
+        contents: Cursor<Vec<u8>>,
+        exists: bool,
+        calls: Vec<String>,
+    }
+
+    impl Storage {
+        fn new(contents: &[u8]) -> Self {
+            Self {
+                contents: Cursor::new(contents.to_vec()),
+                exists: true,
+                calls: Vec::new(),
+            }
+        }
+
+        fn missing() -> Self {
+            Self {
+                exists: false,
+                ..Self::new(&[])
+            }
+        }
+    }
+
+    impl Read for Storage {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.contents.read(buf)
+        }
+    }
+
+    impl Seek for Storage {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.contents.seek(pos)
+        }
+    }
+
+    impl Write for Storage {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.exists = true;
+            self.calls.push(format!("write({})", buf.len()));
+            self.contents.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.calls.push("flush".to_string());
+            Ok(())
+        }
+    }
+
+    impl AdapterFileWrite for Storage {
+        fn set_len(&mut self, length: u64) -> std::io::Result<()> {
+            self.calls.push(format!("set_len({length})"));
+            self.contents.set_len(length)
+        }
+
+        fn close(&mut self) -> std::io::Result<()> {
+            self.calls.push("close".to_string());
+            Ok(())
+        }
+
+        fn exists(&self) -> bool {
+            self.exists
+        }
+    }
+
+    fn write(operator: &mut AdapterFileOperator<impl Read + Seek>, input: &[u8]) -> usize {
+        unsafe { operator.write(input.as_ptr(), input.len()) }
+    }
+
+    #[test]
+    fn missing_files_only_open_for_writing() {
+        let mut fixture = Fixture::new();
+        let mut operator = fixture.writable_operator(Storage::missing());
+
+        assert!(operator.is_writable());
+        assert!(!operator.is_readable());
+        assert!(!operator.open(READ));
+        assert!(matches!(operator.base.result, DLIOResult::NotFound));
+        assert!(!operator.is_open());
+
+        assert!(operator.open(WRITE));
+        assert_eq!(operator.file_size(), 0);
+        assert_eq!(write(&mut operator, b"save"), 4);
+        assert!(operator.close());
+
+        assert!(operator.is_readable());
+        assert!(operator.open(READ));
+        assert_eq!(read(&mut operator, 8), b"save");
+        destroy(operator);
+    }
+
+    #[test]
+    fn set_eof_truncates_at_cursor() {
+        let mut fixture = Fixture::new();
+        let mut operator = fixture.writable_operator(Cursor::new(b"abcdef".to_vec()));
+
+        assert!(operator.open(WRITE));
+        assert_eq!(operator.get_write_size(), 6);
+        assert!(operator.seek(false, 2, DLFileSeekDirection::Head));
+        operator.set_eof();
+        assert!(matches!(operator.base.result, DLIOResult::Success));
+        assert!(operator.is_eof());
+        assert_eq!(operator.file_size(), 2);
+        assert_eq!(operator.buffer.get_ref(), b"ab");
+
+        assert_eq!(write(&mut operator, b"xyz"), 3);
+        assert_eq!(operator.file_size(), 5);
+        assert_eq!(operator.buffer.get_ref(), b"abxyz");
+        destroy(operator);
+    }
+
+    #[test]
+    fn append_starts_at_the_end() {
+        let mut fixture = Fixture::new();
+        let mut operator = fixture.writable_operator(Cursor::new(b"abc".to_vec()));
+
+        assert!(operator.open(APPEND));
+        assert_eq!(operator.cursor_position(), 3);
+        assert_eq!(write(&mut operator, b"def"), 3);
+        assert_eq!(operator.buffer.get_ref(), b"abcdef");
+        destroy(operator);
+    }
+
+    #[test]
+    fn flush_set_eof_close() {
+        let mut fixture = Fixture::new();
+        let mut operator = fixture.writable_operator(Storage::new(b"old save data"));
+
+        // How the game finishes rewriting a file with DLFileOutputStream.
+        assert!(operator.open(WRITE));
+        assert_eq!(write(&mut operator, b"new"), 3);
+        operator.flush();
+        operator.set_eof();
+        assert!(operator.close());
+
+        assert_eq!(
+            operator.buffer.calls,
+            ["write(3)", "flush", "set_len(3)", "close"]
+        );
+        assert_eq!(operator.buffer.contents.get_ref(), b"new");
+        assert!(!operator.is_open());
+
+        // Closing again doesn't reach the storage.
+        assert!(operator.close());
+        assert_eq!(operator.buffer.calls.len(), 4);
+        destroy(operator);
     }
 }
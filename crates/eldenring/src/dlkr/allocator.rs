@@ -1,3 +1,6 @@
+use std::alloc::{AllocError, Allocator, GlobalAlloc, Layout};
+use std::ptr::{self, NonNull};
+
 use vtable_rs::VPtr;
 
 #[vtable_rs::vtable]
@@ -69,45 +72,47 @@ pub struct DLAllocatorBase {
     pub vftable: VPtr<dyn DLAllocatorVmt, Self>,
 }
 
+/// Forwards every call to the game's implementation of the allocator through
+/// its vftable.
 impl DLAllocatorVmt for DLAllocatorBase {
     extern "C" fn destructor(&mut self, param_2: bool) {
-        todo!()
+        (self.vftable.destructor)(self, param_2)
     }
 
     extern "C" fn allocator_id(&self) -> u32 {
-        todo!()
+        (self.vftable.allocator_id)(self)
     }
 
     extern "C" fn unk10(&self) {
-        todo!()
+        (self.vftable.unk10)(self)
     }
 
     extern "C" fn heap_flags(&self) -> &u64 {
-        todo!()
+        (self.vftable.heap_flags)(self)
     }
 
     extern "C" fn heap_capacity(&self) -> usize {
-        todo!()
+        (self.vftable.heap_capacity)(self)
     }
 
     extern "C" fn heap_size(&self) -> usize {
-        todo!()
+        (self.vftable.heap_size)(self)
     }
 
     extern "C" fn backing_heap_capacity(&self) -> usize {
-        todo!()
+        (self.vftable.backing_heap_capacity)(self)
     }
 
     extern "C" fn heap_allocation_count(&self) -> usize {
-        todo!()
+        (self.vftable.heap_allocation_count)(self)
     }
 
     extern "C" fn allocation_size(&self, allocation: *const u8) -> usize {
-        todo!()
+        (self.vftable.allocation_size)(self, allocation)
     }
 
     extern "C" fn allocate(&mut self, size: usize) -> *const u8 {
-        todo!()
+        (self.vftable.allocate)(self, size)
     }
 
     extern "C" fn allocate_aligned(&mut self, size: usize, alignment: usize) -> *const u8 {
@@ -115,7 +120,7 @@ impl DLAllocatorVmt for DLAllocatorBase {
     }
 
     extern "C" fn reallocate(&mut self, allocation: *const u8, size: usize) -> *const u8 {
-        todo!()
+        (self.vftable.reallocate)(self, allocation, size)
     }
 
     extern "C" fn reallocate_aligned(
@@ -124,23 +129,23 @@ impl DLAllocatorVmt for DLAllocatorBase {
         size: usize,
         alignment: usize,
     ) -> *const u8 {
-        todo!()
+        (self.vftable.reallocate_aligned)(self, allocation, size, alignment)
     }
 
     extern "C" fn deallocate(&mut self, allocation: *const u8) {
-        todo!()
+        (self.vftable.deallocate)(self, allocation)
     }
 
     extern "C" fn allocate_second(&mut self, size: usize) -> *const u8 {
-        todo!()
+        (self.vftable.allocate_second)(self, size)
     }
 
     extern "C" fn allocate_aligned_second(&mut self, size: usize, alignment: usize) -> *const u8 {
-        todo!()
+        (self.vftable.allocate_aligned_second)(self, size, alignment)
     }
 
     extern "C" fn reallocate_second(&mut self, allocation: *const u8, size: usize) -> *const u8 {
-        todo!()
+        (self.vftable.reallocate_second)(self, allocation, size)
     }
 
     extern "C" fn reallocate_aligned_second(
@@ -149,34 +154,377 @@ impl DLAllocatorVmt for DLAllocatorBase {
         size: usize,
         alignment: usize,
     ) -> *const u8 {
-        todo!()
+        (self.vftable.reallocate_aligned_second)(self, allocation, size, alignment)
     }
 
     extern "C" fn deallocate_second(&mut self, allocation: *const u8) {
-        todo!()
+        (self.vftable.deallocate_second)(self, allocation)
     }
 
     extern "C" fn unka0(&self) -> bool {
-        todo!()
+        (self.vftable.unka0)(self)
     }
 
     extern "C" fn allocation_belongs_to_first_allocator(&mut self, allocation: *const u8) -> bool {
-        todo!()
+        (self.vftable.allocation_belongs_to_first_allocator)(self, allocation)
     }
 
     extern "C" fn allocation_belongs_to_second_allocator(&mut self, allocation: *const u8) -> bool {
-        todo!()
+        (self.vftable.allocation_belongs_to_second_allocator)(self, allocation)
     }
 
     extern "C" fn lock(&mut self) {
-        todo!()
+        (self.vftable.lock)(self)
     }
 
     extern "C" fn unlock(&mut self) {
-        todo!()
+        (self.vftable.unlock)(self)
     }
 
     extern "C" fn get_memory_block_for_allocation(&mut self, allocation: *const u8) -> *const u8 {
-        todo!()
+        (self.vftable.get_memory_block_for_allocation)(self, allocation)
+    }
+}
+
+/// Handle to one of the game's allocators that can be used to allocate memory
+/// from Rust that the game can later free (and vice versa).
+///
+/// ```ignore
+/// let allocator = unsafe { DLAllocatorRef::new(runtime_class.allocator1.unwrap()) };
+/// let mut entries: DLVec<u32> = Vec::new_in(allocator);
+/// entries.push(10);
+/// ```
+#[repr(transparent)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DLAllocatorRef(NonNull<DLAllocatorBase>);
+
+// SAFETY: the game's allocators do their own locking and are used from all of
+// the game's threads.
+unsafe impl Send for DLAllocatorRef {}
+unsafe impl Sync for DLAllocatorRef {}
+
+/// Box allocated with one of the game's allocators.
+pub type DLBox<T> = Box<T, DLAllocatorRef>;
+
+/// Vec allocated with one of the game's allocators.
+pub type DLVec<T> = Vec<T, DLAllocatorRef>;
+
+impl DLAllocatorRef {
+    /// # Safety
+    /// The allocator must be a live game allocator and must outlive all
+    /// allocations made through the handle.
+    pub unsafe fn new(allocator: NonNull<DLAllocatorBase>) -> Self {
+        Self(allocator)
+    }
+
+    pub fn as_ptr(&self) -> NonNull<DLAllocatorBase> {
+        self.0
+    }
+
+    fn base(&self) -> &DLAllocatorBase {
+        unsafe { self.0.as_ref() }
+    }
+
+    pub fn allocator_id(&self) -> u32 {
+        self.base().allocator_id()
+    }
+
+    /// Amount of bytes currently allocated from the heap.
+    pub fn heap_size(&self) -> usize {
+        self.base().heap_size()
+    }
+
+    /// Amount of bytes the heap can hold.
+    pub fn heap_capacity(&self) -> usize {
+        self.base().heap_capacity()
+    }
+
+    /// Amount of live allocations in the heap.
+    pub fn heap_allocation_count(&self) -> usize {
+        self.base().heap_allocation_count()
+    }
+}
+
+unsafe impl Allocator for DLAllocatorRef {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        if layout.size() == 0 {
+            let dangling =
+                unsafe { NonNull::new_unchecked(ptr::without_provenance_mut(layout.align())) };
+            return Ok(NonNull::slice_from_raw_parts(dangling, 0));
+        }
+
+        let allocation =
+            unsafe { (*self.0.as_ptr()).allocate_aligned(layout.size(), layout.align()) };
+
+        NonNull::new(allocation as *mut u8)
+            .map(|a| NonNull::slice_from_raw_parts(a, layout.size()))
+            .ok_or(AllocError)
+    }
+
+    unsafe fn deallocate(&self, allocation: NonNull<u8>, layout: Layout) {
+        if layout.size() != 0 {
+            (*self.0.as_ptr()).deallocate(allocation.as_ptr());
+        }
+    }
+}
+
+unsafe impl GlobalAlloc for DLAllocatorRef {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        (*self.0.as_ptr()).allocate_aligned(layout.size(), layout.align()) as *mut u8
+    }
+
+    unsafe fn dealloc(&self, allocation: *mut u8, _layout: Layout) {
+        (*self.0.as_ptr()).deallocate(allocation);
+    }
+
+    unsafe fn realloc(&self, allocation: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        (*self.0.as_ptr()).reallocate_aligned(allocation, new_size, layout.align()) as *mut u8
+    }
+}
+
+/// Allocator backed by the Rust global allocator that keeps track of live
+/// allocations, can be handed to containers in place of a game allocator.
+/// Available to other crates' tests through the `mock` feature.
+#[cfg(any(test, feature = "mock"))]
+pub mod mock {
+    use std::alloc::{alloc, dealloc, Layout};
+    use std::collections::HashMap;
+    use std::ptr::NonNull;
+
+    use vtable_rs::VPtr;
+
+    use super::{DLAllocatorRef, DLAllocatorVmt};
+
+    #[repr(C)]
+    pub struct MockAllocator {
+        vftable: VPtr<dyn DLAllocatorVmt, Self>,
+        allocations: HashMap<usize, Layout>,
+    }
+
+    impl MockAllocator {
+        pub fn new() -> Box<Self> {
+            Box::new(Self {
+                vftable: VPtr::new(),
+                allocations: HashMap::new(),
+            })
+        }
+
+        pub fn handle(&mut self) -> DLAllocatorRef {
+            unsafe { DLAllocatorRef::new(NonNull::from(self).cast()) }
+        }
+
+        /// Amount of allocations that haven't been freed yet.
+        pub fn live_allocations(&self) -> usize {
+            self.allocations.len()
+        }
+    }
+
+    impl DLAllocatorVmt for MockAllocator {
+        extern "C" fn destructor(&mut self, _param_2: bool) {}
+
+        extern "C" fn allocator_id(&self) -> u32 {
+            0
+        }
+
+        extern "C" fn unk10(&self) {}
+
+        extern "C" fn heap_flags(&self) -> &u64 {
+            &0
+        }
+
+        extern "C" fn heap_capacity(&self) -> usize {
+            usize::MAX
+        }
+
+        extern "C" fn heap_size(&self) -> usize {
+            self.allocations.values().map(|l| l.size()).sum()
+        }
+
+        extern "C" fn backing_heap_capacity(&self) -> usize {
+            usize::MAX
+        }
+
+        extern "C" fn heap_allocation_count(&self) -> usize {
+            self.allocations.len()
+        }
+
+        extern "C" fn allocation_size(&self, allocation: *const u8) -> usize {
+            self.allocations[&(allocation as usize)].size()
+        }
+
+        extern "C" fn allocate(&mut self, size: usize) -> *const u8 {
+            self.allocate_aligned(size, 0x10)
+        }
+
+        extern "C" fn allocate_aligned(&mut self, size: usize, alignment: usize) -> *const u8 {
+            let layout = Layout::from_size_align(size.max(1), alignment).unwrap();
+            let allocation = unsafe { alloc(layout) };
+            self.allocations.insert(allocation as usize, layout);
+            allocation
+        }
+
+        extern "C" fn reallocate(&mut self, allocation: *const u8, size: usize) -> *const u8 {
+            self.reallocate_aligned(allocation, size, 0x10)
+        }
+
+        // allocation_size panics on pointers this allocator didn't hand out
+        // before anything is read.
+        #[allow(clippy::not_unsafe_ptr_arg_deref)]
+        extern "C" fn reallocate_aligned(
+            &mut self,
+            allocation: *const u8,
+            size: usize,
+            alignment: usize,
+        ) -> *const u8 {
+            let new = self.allocate_aligned(size, alignment);
+            if !allocation.is_null() {
+                let old_size = self.allocation_size(allocation);
+                unsafe {
+                    std::ptr::copy_nonoverlapping(allocation, new as *mut u8, old_size.min(size))
+                };
+                self.deallocate(allocation);
+            }
+            new
+        }
+
+        extern "C" fn deallocate(&mut self, allocation: *const u8) {
+            let layout = self
+                .allocations
+                .remove(&(allocation as usize))
+                .expect("freeing memory that was not allocated by this allocator");
+            unsafe { dealloc(allocation as *mut u8, layout) };
+        }
+
+        extern "C" fn allocate_second(&mut self, size: usize) -> *const u8 {
+            self.allocate(size)
+        }
+
+        extern "C" fn allocate_aligned_second(
+            &mut self,
+            size: usize,
+            alignment: usize,
+        ) -> *const u8 {
+            self.allocate_aligned(size, alignment)
+        }
+
+        extern "C" fn reallocate_second(
+            &mut self,
+            allocation: *const u8,
+            size: usize,
+        ) -> *const u8 {
+            self.reallocate(allocation, size)
+        }
+
+        extern "C" fn reallocate_aligned_second(
+            &mut self,
+            allocation: *const u8,
+            size: usize,
+            alignment: usize,
+        ) -> *const u8 {
+            self.reallocate_aligned(allocation, size, alignment)
+        }
+
+        extern "C" fn deallocate_second(&mut self, allocation: *const u8) {
+            self.deallocate(allocation)
+        }
+
+        extern "C" fn unka0(&self) -> bool {
+            false
+        }
+
+        extern "C" fn allocation_belongs_to_first_allocator(
+            &mut self,
+            allocation: *const u8,
+        ) -> bool {
+            self.allocations.contains_key(&(allocation as usize))
+        }
+
+        extern "C" fn allocation_belongs_to_second_allocator(
+            &mut self,
+            _allocation: *const u8,
+        ) -> bool {
+            false
+        }
+
+        extern "C" fn lock(&mut self) {}
+
+        extern "C" fn unlock(&mut self) {}
+
+        extern "C" fn get_memory_block_for_allocation(
+            &mut self,
+            allocation: *const u8,
+        ) -> *const u8 {
+            allocation
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::alloc::{Allocator, GlobalAlloc, Layout};
+
+    use super::{mock::MockAllocator, DLBox, DLVec};
+
+    #[test]
+    fn dl_box_allocates_through_game_allocator() {
+        let mut allocator = MockAllocator::new();
+        let handle = allocator.handle();
+
+        let boxed = DLBox::new_in(0x1337u64, handle);
+        assert_eq!(*boxed, 0x1337);
+        assert_eq!(handle.heap_allocation_count(), 1);
+        assert_eq!(handle.heap_size(), 8);
+
+        drop(boxed);
+        assert_eq!(handle.heap_allocation_count(), 0);
+    }
+
+    #[test]
+    fn dl_vec_grows_and_frees() {
+        let mut allocator = MockAllocator::new();
+        let handle = allocator.handle();
+
+        let mut entries: DLVec<u32> = Vec::new_in(handle);
+        assert_eq!(handle.heap_allocation_count(), 0);
+
+        (0..100).for_each(|i| entries.push(i));
+        assert_eq!(entries.iter().sum::<u32>(), 4950);
+        assert_eq!(handle.heap_allocation_count(), 1);
+
+        drop(entries);
+        assert_eq!(handle.heap_allocation_count(), 0);
+    }
+
+    #[test]
+    fn zero_sized_allocations_skip_game_allocator() {
+        let mut allocator = MockAllocator::new();
+        let handle = allocator.handle();
+
+        let layout = Layout::from_size_align(0, 16).unwrap();
+        let allocation = handle.allocate(layout).unwrap();
+        assert_eq!(allocation.cast::<u8>().as_ptr() as usize % 16, 0);
+        assert_eq!(handle.heap_allocation_count(), 0);
+
+        unsafe { handle.deallocate(allocation.cast(), layout) };
+    }
+
+    #[test]
+    fn global_alloc_respects_alignment() {
+        let mut allocator = MockAllocator::new();
+        let handle = allocator.handle();
+
+        let layout = Layout::from_size_align(24, 64).unwrap();
+        unsafe {
+            let allocation = handle.alloc(layout);
+            assert_eq!(allocation as usize % 64, 0);
+
+            let allocation = handle.realloc(allocation, layout, 128);
+            assert_eq!(allocation as usize % 64, 0);
+            assert_eq!(handle.heap_size(), 128);
+
+            handle.dealloc(allocation, layout);
+        }
+
+        assert_eq!(handle.heap_allocation_count(), 0);
     }
 }
@@ -1,19 +1,207 @@
 use std::ffi;
+use std::ptr::NonNull;
 
-use shared::OwnedPtr;
+use vtable_rs::VPtr;
+
+use crate::dlkr::DLAllocatorBase;
+
+/// The slot order hasn't been sourced, neither RTTI nor the game's code that
+/// has been looked at so far pins which slot holds which getter. It has not
+/// been confirmed against a game build either, so anything reading names
+/// through it ([`DLRuntimeClass::name`], [`DLRuntimeClass::is_a`], ...) relies
+/// on `class_name` being slot 1.
+#[vtable_rs::vtable]
+pub trait DLRuntimeClassVmt {
+    fn destructor(&mut self, param_2: u32);
+
+    /// Name of the described class, ex: "CSTaskImp".
+    fn class_name(&self) -> *const ffi::c_char;
+
+    /// Same as class_name but as a null-terminated UTF-16 string.
+    fn class_name_w(&self) -> *const u16;
+}
 
 #[repr(C)]
 /// Part of the DLRF namespace, describes some aspects of a tracked class.
 ///
 /// Source of name: RTTI
 pub struct DLRuntimeClass {
-    vftable: *const ffi::c_void,
-    pub base_class: OwnedPtr<DLRuntimeClass>,
+    pub vftable: VPtr<dyn DLRuntimeClassVmt, Self>,
+    /// Runtime class of the class this one derives from. Null for classes at
+    /// the root of the hierarchy.
+    base_class: Option<NonNull<DLRuntimeClass>>,
     unk10: usize,
     unk18: usize,
     unk20: usize,
     unk28: usize,
     unk30: usize,
-    allocator1: usize,
-    allocator2: usize,
+    /// Allocator used for instances of the class.
+    pub allocator1: Option<NonNull<DLAllocatorBase>>,
+    /// Allocator used for instances of the class when the first allocator is
+    /// not available.
+    pub allocator2: Option<NonNull<DLAllocatorBase>>,
+}
+
+impl DLRuntimeClass {
+    /// Name of the described class, ex: "CSTaskImp".
+    pub fn name(&self) -> String {
+        String::from_utf8_lossy(self.name_bytes()).into_owned()
+    }
+
+    /// Same as name but without copying the name out of the game.
+    pub fn name_bytes(&self) -> &[u8] {
+        let name = (self.vftable.class_name)(self);
+        if name.is_null() {
+            return &[];
+        }
+
+        unsafe { ffi::CStr::from_ptr(name) }.to_bytes()
+    }
+
+    /// Runtime class of the class this one derives from.
+    pub fn base(&self) -> Option<&DLRuntimeClass> {
+        self.base_class.map(|b| unsafe { b.as_ref() })
+    }
+
+    /// Runtime class of the class this one derives from.
+    ///
+    /// # Panics
+    /// Panics if the class is at the root of the hierarchy.
+    #[deprecated(note = "use base(), which returns None for classes at the root of the hierarchy")]
+    pub fn base_class(&self) -> &DLRuntimeClass {
+        self.base()
+            .expect("runtime class is at the root of the hierarchy")
+    }
+
+    /// Iterates over this class and all classes it derives from, starting with
+    /// this class.
+    pub fn hierarchy(&self) -> impl Iterator<Item = &DLRuntimeClass> {
+        std::iter::successors(Some(self), |c| c.base())
+    }
+
+    /// Checks if the described class is or derives from the class with the
+    /// specified name.
+    pub fn is_a(&self, name: &str) -> bool {
+        self.hierarchy().any(|c| c.name_bytes() == name.as_bytes())
+    }
+
+    pub fn allocator(&self) -> Option<&DLAllocatorBase> {
+        self.allocator1.map(|a| unsafe { a.as_ref() })
+    }
+
+    pub fn secondary_allocator(&self) -> Option<&DLAllocatorBase> {
+        self.allocator2.map(|a| unsafe { a.as_ref() })
+    }
+}
+
+/// Implemented by objects that can hand out their runtime class through their
+/// vftable.
+pub trait DLRuntimeClassHolder {
+    fn runtime_class(&self) -> &DLRuntimeClass;
+
+    /// Name of the object's class, ex: "PlayerIns".
+    fn class_name(&self) -> String {
+        self.runtime_class().name()
+    }
+
+    /// Checks if the object's class is or derives from the class with the
+    /// specified name.
+    fn is_a(&self, name: &str) -> bool {
+        self.runtime_class().is_a(name)
+    }
+
+    /// Address of the object's runtime class.
+    #[deprecated(note = "renamed to get_runtime_class, use runtime_class instead")]
+    fn get_runtime_metadata(&self) -> usize {
+        self.runtime_class() as *const DLRuntimeClass as usize
+    }
+}
+
+/// Calls the get_runtime_class function in the first slot of the object's
+/// vftable.
+///
+/// # Safety
+/// Caller must ensure that the object starts with a vftable pointer and that
+/// the first slot of that vftable is a get_runtime_class function.
+pub unsafe fn runtime_class_of<T>(object: &T) -> &DLRuntimeClass {
+    let vftable = *(object as *const T as *const *const extern "C" fn(&T) -> &DLRuntimeClass);
+    (*vftable)(object)
+}
+
+#[cfg(test)]
+mod test {
+    use std::ffi::{c_char, CStr};
+    use std::ptr::NonNull;
+
+    use vtable_rs::VPtr;
+
+    use super::{DLRuntimeClass, DLRuntimeClassVmt};
+
+    /// Runtime class with the layout of DLRuntimeClass and a configurable name.
+    #[repr(C)]
+    struct TestClass {
+        vftable: VPtr<dyn DLRuntimeClassVmt, Self>,
+        base_class: Option<NonNull<DLRuntimeClass>>,
+        unk10: [usize; 7],
+        name: Option<&'static CStr>,
+    }
+
+    impl TestClass {
+        fn new(name: Option<&'static CStr>, base: Option<&TestClass>) -> Self {
+            Self {
+                vftable: Default::default(),
+                base_class: base.map(|b| NonNull::from(b.as_runtime_class())),
+                unk10: [0; 7],
+                name,
+            }
+        }
+
+        fn as_runtime_class(&self) -> &DLRuntimeClass {
+            unsafe { &*(self as *const Self as *const DLRuntimeClass) }
+        }
+    }
+
+    impl DLRuntimeClassVmt for TestClass {
+        extern "C" fn destructor(&mut self, _param_2: u32) {}
+
+        extern "C" fn class_name(&self) -> *const c_char {
+            self.name.map_or(std::ptr::null(), CStr::as_ptr)
+        }
+
+        extern "C" fn class_name_w(&self) -> *const u16 {
+            std::ptr::null()
+        }
+    }
+
+    #[test]
+    fn is_a_walks_the_hierarchy() {
+        let field_ins = TestClass::new(Some(c"CSFieldInsBase"), None);
+        let chr_ins = TestClass::new(Some(c"ChrIns"), Some(&field_ins));
+        let player_ins = TestClass::new(Some(c"PlayerIns"), Some(&chr_ins));
+        let player_ins = player_ins.as_runtime_class();
+
+        assert_eq!(player_ins.name(), "PlayerIns");
+        assert_eq!(player_ins.name_bytes(), b"PlayerIns");
+        assert_eq!(player_ins.hierarchy().count(), 3);
+        assert!(player_ins.is_a("PlayerIns"));
+        assert!(player_ins.is_a("ChrIns"));
+        assert!(player_ins.is_a("CSFieldInsBase"));
+
+        // Names have to match exactly.
+        assert!(!player_ins.is_a("Player"));
+        assert!(!player_ins.is_a("PlayerInsX"));
+        assert!(!player_ins.is_a("EnemyIns"));
+        assert!(!chr_ins.as_runtime_class().is_a("PlayerIns"));
+    }
+
+    #[test]
+    fn unnamed_classes() {
+        let unnamed = TestClass::new(None, None);
+        let unnamed = unnamed.as_runtime_class();
+
+        assert_eq!(unnamed.name(), "");
+        assert!(unnamed.name_bytes().is_empty());
+        assert!(unnamed.is_a(""));
+        assert!(!unnamed.is_a("ChrIns"));
+    }
 }
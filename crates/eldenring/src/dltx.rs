@@ -1,35 +1,44 @@
+use std::alloc::{handle_alloc_error, Allocator, Layout};
 use std::ffi;
 use std::fmt::Display;
 use std::marker;
 use std::marker::PhantomData;
 use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::ptr::{self, NonNull};
 use std::slice;
 
+use crate::dlkr::DLAllocatorRef;
+
+/// Amount of UTF-16 units that fit in the inline buffer, including the null
+/// terminator. Longer strings are moved to the heap.
+const INLINE_CAPACITY: usize = 8;
+
 #[repr(C)]
-#[derive(Default)]
 pub struct DLBasicString {
     inner: [u8; 0x10],
     pub length: usize,
     pub capacity: usize,
 }
 
+impl Default for DLBasicString {
+    /// Empty inline string, inline strings always report the capacity of the
+    /// inline buffer like the game's do.
+    fn default() -> Self {
+        Self {
+            inner: [0; 0x10],
+            length: 0,
+            capacity: INLINE_CAPACITY - 1,
+        }
+    }
+}
+
 impl Display for DLBasicString {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let slice_size = self.length * mem::size_of::<u16>();
-        let bytes = if slice_size >= 16 {
-            let ptr = usize::from_le_bytes(self.inner[0..8].try_into().unwrap());
-
-            unsafe { slice::from_raw_parts(ptr as *const u16, self.length).to_vec() }
-        } else {
-            self.inner[0..slice_size]
-                .chunks_exact(2)
-                .map(|c| ((c[1] as u16) << 8) | c[0] as u16)
-                .collect::<Vec<u16>>()
-        };
-
-        write!(f, "{}", String::from_utf16_lossy(bytes.as_slice()))
+        write!(f, "{}", String::from_utf16_lossy(self.as_utf16()))
     }
 }
+
 impl DLBasicString {
     /// # Safety
     ///
@@ -38,12 +47,119 @@ impl DLBasicString {
     pub unsafe fn raw(&self) -> &[u8] {
         &self.inner
     }
+
+    /// Creates a string holding a copy of `units`, allocating with `allocator`
+    /// if the string does not fit in the inline buffer.
+    pub fn from_utf16_in(units: &[u16], allocator: DLAllocatorRef) -> Self {
+        let mut string = Self::default();
+
+        // SAFETY: the string is inline so there's no previous allocation.
+        unsafe { string.assign(units, allocator) };
+        string
+    }
+
+    /// Whether the characters are stored in the inline buffer or on the heap.
+    pub fn is_inline(&self) -> bool {
+        self.capacity < INLINE_CAPACITY
+    }
+
+    fn heap_ptr(&self) -> *mut u16 {
+        usize::from_le_bytes(self.inner[0..8].try_into().unwrap()) as *mut u16
+    }
+
+    /// Pointer to the null-terminated characters.
+    pub fn as_ptr(&self) -> *const u16 {
+        if self.is_inline() {
+            self.inner.as_ptr() as *const u16
+        } else {
+            self.heap_ptr()
+        }
+    }
+
+    pub fn as_utf16(&self) -> &[u16] {
+        if self.length == 0 {
+            return &[];
+        }
+
+        unsafe { slice::from_raw_parts(self.as_ptr(), self.length) }
+    }
+
+    /// Replaces the contents of the string, reusing the current buffer if it
+    /// is large enough.
+    ///
+    /// # Safety
+    /// If the string lives on the heap its buffer must have been allocated by
+    /// `allocator`.
+    pub unsafe fn assign(&mut self, units: &[u16], allocator: DLAllocatorRef) {
+        if units.len() > self.usable_capacity() {
+            let capacity = units.len() | (INLINE_CAPACITY - 1);
+            let layout = Self::buffer_layout(capacity);
+            let buffer = allocator
+                .allocate(layout)
+                .unwrap_or_else(|_| handle_alloc_error(layout))
+                .cast::<u16>();
+
+            self.free(allocator);
+            self.inner[0..8].copy_from_slice(&(buffer.as_ptr() as usize).to_le_bytes());
+            self.capacity = capacity;
+        }
+
+        self.write_units(units);
+    }
+
+    /// Copies the units into the current buffer, which must be large enough.
+    unsafe fn write_units(&mut self, units: &[u16]) {
+        debug_assert!(units.len() <= self.usable_capacity());
+
+        // Keep length <= capacity for inline strings that were zeroed.
+        if self.is_inline() {
+            self.capacity = INLINE_CAPACITY - 1;
+        }
+
+        let destination = self.as_ptr() as *mut u16;
+        destination.copy_from_nonoverlapping(units.as_ptr(), units.len());
+        destination.add(units.len()).write(0);
+        self.length = units.len();
+    }
+
+    /// Amount of units that fit in the current buffer, excluding the null
+    /// terminator.
+    fn usable_capacity(&self) -> usize {
+        if self.is_inline() {
+            INLINE_CAPACITY - 1
+        } else {
+            self.capacity
+        }
+    }
+
+    /// Frees the heap buffer (if any) and resets the string to an empty inline
+    /// string.
+    ///
+    /// # Safety
+    /// If the string lives on the heap its buffer must have been allocated by
+    /// `allocator`.
+    pub unsafe fn free(&mut self, allocator: DLAllocatorRef) {
+        if !self.is_inline() {
+            allocator.deallocate(
+                NonNull::new_unchecked(self.heap_ptr()).cast(),
+                Self::buffer_layout(self.capacity),
+            );
+        }
+
+        self.inner = [0; 0x10];
+        self.length = 0;
+        self.capacity = INLINE_CAPACITY - 1;
+    }
+
+    fn buffer_layout(capacity: usize) -> Layout {
+        Layout::array::<u16>(capacity + 1).expect("string too large")
+    }
 }
 
 #[repr(C)]
 #[derive(Default)]
 pub struct DLString {
-    allocator: usize,
+    allocator: Option<DLAllocatorRef>,
     pub inner: DLBasicString,
     unk28: u32,
     unk2c: u32,
@@ -56,6 +172,83 @@ impl Display for DLString {
 }
 
 impl DLString {
+    /// Creates a string holding `value` that is owned by `allocator`.
+    ///
+    /// The buffer is not freed when the string is dropped, the string is meant
+    /// to be handed over to the game. Use [`OwnedDLString`] for strings that
+    /// stay on the Rust side.
+    pub fn new_in(value: &str, allocator: DLAllocatorRef) -> Self {
+        Self::from_utf16_in(&value.encode_utf16().collect::<Vec<_>>(), allocator)
+    }
+
+    /// Creates a string holding a copy of `units` that is owned by
+    /// `allocator`.
+    pub fn from_utf16_in(units: &[u16], allocator: DLAllocatorRef) -> Self {
+        Self {
+            allocator: Some(allocator),
+            inner: DLBasicString::from_utf16_in(units, allocator),
+            unk28: 0,
+            unk2c: 0,
+        }
+    }
+
+    /// Copies the string into a new string owned by `allocator`. Like
+    /// [`DLString::new_in`] the copy is not freed when dropped.
+    pub fn clone_in(&self, allocator: DLAllocatorRef) -> Self {
+        Self {
+            allocator: Some(allocator),
+            inner: DLBasicString::from_utf16_in(self.inner.as_utf16(), allocator),
+            unk28: self.unk28,
+            unk2c: self.unk2c,
+        }
+    }
+
+    pub fn allocator(&self) -> Option<DLAllocatorRef> {
+        self.allocator
+    }
+
+    /// Frees the heap buffer (if any) with the string's allocator and resets
+    /// it to an empty string.
+    ///
+    /// # Safety
+    /// The string must own its buffer, ex: it was created with
+    /// [`DLString::new_in`] and not handed over to the game.
+    pub unsafe fn free(&mut self) {
+        if let Some(allocator) = self.allocator {
+            self.inner.free(allocator);
+        }
+    }
+
+    pub fn as_utf16(&self) -> &[u16] {
+        self.inner.as_utf16()
+    }
+
+    /// Replaces the contents of the string.
+    ///
+    /// # Panics
+    /// Panics if the string has no allocator and the value does not fit in
+    /// the inline buffer.
+    pub fn assign(&mut self, value: &str) {
+        self.assign_utf16(&value.encode_utf16().collect::<Vec<_>>())
+    }
+
+    /// Like [`DLString::assign`] but takes UTF-16.
+    pub fn assign_utf16(&mut self, units: &[u16]) {
+        match self.allocator {
+            // SAFETY: any heap buffer was allocated by the string's allocator.
+            Some(allocator) => unsafe { self.inner.assign(units, allocator) },
+            None => {
+                assert!(
+                    units.len() < INLINE_CAPACITY,
+                    "DLString without allocator can only hold inline strings"
+                );
+
+                // SAFETY: without allocator the string can only be inline.
+                unsafe { self.inner.write_units(units) }
+            }
+        }
+    }
+
     /// # Safety
     ///
     /// The caller must ensure that the string is actually a DLString and is
@@ -65,6 +258,72 @@ impl DLString {
     }
 }
 
+/// A [`DLString`] created on the Rust side that frees its buffer when dropped.
+///
+/// [`DLString`] itself describes the game's strings, which are freed by the
+/// game, so it doesn't free anything on drop.
+pub struct OwnedDLString(DLString);
+
+impl OwnedDLString {
+    /// Creates a string holding `value` that is owned by `allocator`.
+    pub fn new_in(value: &str, allocator: DLAllocatorRef) -> Self {
+        Self(DLString::new_in(value, allocator))
+    }
+
+    /// Creates a string holding a copy of `units` that is owned by
+    /// `allocator`.
+    pub fn from_utf16_in(units: &[u16], allocator: DLAllocatorRef) -> Self {
+        Self(DLString::from_utf16_in(units, allocator))
+    }
+
+    /// Gives up ownership of the buffer, ex: to hand the string over to the
+    /// game.
+    pub fn into_inner(self) -> DLString {
+        let string = mem::ManuallyDrop::new(self);
+        // SAFETY: the wrapper is not dropped so the buffer has a single owner.
+        unsafe { ptr::read(&string.0) }
+    }
+
+    fn allocator(&self) -> DLAllocatorRef {
+        self.0
+            .allocator
+            .expect("OwnedDLString is always created with an allocator")
+    }
+}
+
+impl Deref for OwnedDLString {
+    type Target = DLString;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for OwnedDLString {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl Display for OwnedDLString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Clone for OwnedDLString {
+    fn clone(&self) -> Self {
+        Self(self.0.clone_in(self.allocator()))
+    }
+}
+
+impl Drop for OwnedDLString {
+    fn drop(&mut self) {
+        // SAFETY: the wrapper owns the buffer.
+        unsafe { self.0.free() };
+    }
+}
+
 pub type DLAllocatedString = DLString;
 
 #[repr(C)]
@@ -94,3 +353,100 @@ impl<const U: usize, const N: usize> Display for DLInplaceStr<U, N> {
         write!(f, "{}", self.inner)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::dlkr::mock::MockAllocator;
+    use crate::dltx::{DLString, OwnedDLString};
+
+    #[test]
+    fn proper_sizes() {
+        assert_eq!(0x30, size_of::<DLString>());
+    }
+
+    #[test]
+    fn short_strings_stay_inline() {
+        let mut allocator = MockAllocator::new();
+        let string = OwnedDLString::new_in("c0000", allocator.handle());
+
+        assert!(string.inner.is_inline());
+        assert_eq!(string.to_string(), "c0000");
+        assert_eq!(allocator.live_allocations(), 0);
+
+        // 7 units plus the terminator still fit.
+        let string = OwnedDLString::new_in("abcdefg", allocator.handle());
+        assert!(string.inner.is_inline());
+        assert_eq!(unsafe { *string.inner.as_ptr().add(7) }, 0);
+    }
+
+    #[test]
+    fn long_strings_move_to_heap() {
+        let mut allocator = MockAllocator::new();
+        let mut string = OwnedDLString::new_in("abcdefgh", allocator.handle());
+
+        assert!(!string.inner.is_inline());
+        assert_eq!(string.to_string(), "abcdefgh");
+        assert_eq!(string.inner.length, 8);
+        assert!(string.inner.capacity >= 8);
+        assert_eq!(allocator.live_allocations(), 1);
+
+        // Shrinking reuses the heap buffer.
+        string.assign("abc");
+        assert!(!string.inner.is_inline());
+        assert_eq!(string.to_string(), "abc");
+        assert_eq!(allocator.live_allocations(), 1);
+
+        string.assign("data0:/regulation.bin");
+        assert_eq!(string.to_string(), "data0:/regulation.bin");
+        assert_eq!(allocator.live_allocations(), 1);
+
+        let clone = string.clone();
+        assert_eq!(clone.to_string(), "data0:/regulation.bin");
+        assert_eq!(allocator.live_allocations(), 2);
+
+        drop(string);
+        drop(clone);
+        assert_eq!(allocator.live_allocations(), 0);
+    }
+
+    #[test]
+    fn strings_without_allocator_can_hold_inline_values() {
+        let mut string = DLString::default();
+        assert_eq!(string.inner.capacity, 7);
+
+        string.assign("abc");
+        assert_eq!(string.to_string(), "abc");
+        assert_eq!(string.inner.length, 3);
+        assert!(string.inner.length <= string.inner.capacity);
+    }
+
+    #[test]
+    fn game_strings_are_not_freed_on_drop() {
+        let mut allocator = MockAllocator::new();
+        let string = DLString::new_in("data0:/regulation.bin", allocator.handle());
+
+        // By-value copies of the game's strings must not free its buffers.
+        {
+            let _copy = unsafe { std::ptr::read(&string) };
+        }
+        assert_eq!(allocator.live_allocations(), 1);
+        assert_eq!(string.to_string(), "data0:/regulation.bin");
+
+        {
+            let _copy = string.clone_in(allocator.handle());
+        }
+        assert_eq!(allocator.live_allocations(), 2);
+    }
+
+    #[test]
+    fn into_inner_hands_over_the_buffer() {
+        let mut allocator = MockAllocator::new();
+        let string = OwnedDLString::new_in("data0:/regulation.bin", allocator.handle());
+
+        let mut string = string.into_inner();
+        assert_eq!(allocator.live_allocations(), 1);
+
+        unsafe { string.free() };
+        assert_eq!(allocator.live_allocations(), 0);
+    }
+}
@@ -0,0 +1,115 @@
+use std::ptr::NonNull;
+
+use crate::Vector;
+
+/// Virtual input device merging the physical devices (pad, keyboard and
+/// mouse) assigned to a user. Its input data is rewritten by the pad manager
+/// during `CSTaskGroupIndex::PadStep` and read by everything running after.
+/// Only read access is exposed until the layout has been confirmed.
+///
+/// Source of name: RTTI
+///
+/// The offset of `virtual_input_data` and the layouts of the structures it
+/// contains haven't been confirmed against a game build.
+#[repr(C)]
+pub struct DLUserInputDevice {
+    vftable: usize,
+    unk8: [u8; 0x8],
+    /// Input of the device in terms of virtual keys. The virtual keys are the
+    /// row IDs of the `DEFAULT_KEY_ASSIGN` param.
+    pub virtual_input_data: VirtualInputData,
+}
+
+/// Source of name: RTTI
+#[repr(C)]
+pub struct VirtualInputData {
+    vftable: usize,
+    /// Analog state of the virtual keys, ex: stick axes and triggers.
+    pub analog_key_info: VirtualAnalogKeyInfo<f32>,
+    /// Down state of the virtual keys.
+    pub digital_key_info: DynamicBitset,
+}
+
+/// Source of name: RTTI
+#[repr(C)]
+pub struct VirtualAnalogKeyInfo<T> {
+    vftable: usize,
+    /// Value per virtual key, indexed by the virtual key.
+    pub values: Vector<T>,
+}
+
+impl<T: Copy> VirtualAnalogKeyInfo<T> {
+    pub fn get(&self, key: usize) -> Option<T> {
+        self.values.items().get(key).copied()
+    }
+}
+
+/// Source of name: RTTI
+#[repr(C)]
+pub struct DynamicBitset {
+    vftable: usize,
+    bit_count: u32,
+    unkc: u32,
+    words: Option<NonNull<u32>>,
+    allocator: usize,
+}
+
+impl DynamicBitset {
+    /// Amount of bits in the set.
+    pub fn len(&self) -> usize {
+        self.bit_count as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The backing words, the first bit lives in the lowest bit of the first
+    /// word.
+    pub fn words(&self) -> &[u32] {
+        let Some(words) = self.words else {
+            return &[];
+        };
+
+        unsafe { std::slice::from_raw_parts(words.as_ptr(), self.len().div_ceil(32)) }
+    }
+
+    pub fn get(&self, index: usize) -> Option<bool> {
+        if index >= self.len() {
+            return None;
+        }
+
+        Some(self.words()[index / 32] & (1 << (index % 32)) != 0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::ptr::NonNull;
+
+    use crate::dluid::{DLUserInputDevice, DynamicBitset, VirtualInputData};
+
+    #[test]
+    fn proper_sizes() {
+        assert_eq!(0x20, size_of::<DynamicBitset>());
+        assert_eq!(0x50, size_of::<VirtualInputData>());
+        assert_eq!(0x60, size_of::<DLUserInputDevice>());
+    }
+
+    #[test]
+    fn bitset_get() {
+        let mut words = [0u32, 0b1];
+        let bitset = DynamicBitset {
+            vftable: 0,
+            bit_count: 40,
+            unkc: 0,
+            words: NonNull::new(words.as_mut_ptr()),
+            allocator: 0,
+        };
+
+        assert_eq!(bitset.words().len(), 2);
+        assert_eq!(bitset.get(32), Some(true));
+        assert_eq!(bitset.get(0), Some(false));
+        assert_eq!(bitset.get(40), None);
+    }
+}
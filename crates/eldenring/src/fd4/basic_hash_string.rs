@@ -1,7 +1,49 @@
-use std::{ffi, fmt::Display};
+use std::{
+    ffi,
+    fmt::Display,
+    mem::ManuallyDrop,
+    ops::{Deref, DerefMut},
+    ptr,
+    sync::atomic::{AtomicU8, AtomicUsize, Ordering},
+};
 
+use thiserror::Error;
+
+use crate::dlkr::DLAllocatorRef;
 use crate::dltx::DLBasicString;
 
+const FNV_OFFSET_BASIS: u32 = 0x811c9dc5;
+const FNV_PRIME: u32 = 0x01000193;
+
+/// The game's vftable for FD4BasicHashString, null until it has been set.
+static VFTABLE: AtomicUsize = AtomicUsize::new(0);
+
+const HASH_UNCHECKED: u8 = 0;
+const HASH_CONFIRMED: u8 = 1;
+const HASH_MISMATCH: u8 = 2;
+
+/// Outcome of checking [`FD4BasicHashString::hash_utf16`] against hashes
+/// computed by the game.
+static HASH_CHECK: AtomicU8 = AtomicU8::new(HASH_UNCHECKED);
+
+#[derive(Error, Debug)]
+pub enum HashStringError {
+    #[error("The FD4BasicHashString vftable has not been set.")]
+    NoVftable,
+}
+
+/// Result of checking [`FD4BasicHashString::hash_utf16`] against the hashes
+/// the game computed for its own strings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HashCheck {
+    /// No strings hashed by the game have been checked yet.
+    Unchecked,
+    /// Every string hashed by the game that was checked matched.
+    Confirmed,
+    /// At least one string hashed by the game did not match.
+    Mismatch,
+}
+
 #[repr(C)]
 /// Wraps a string to make it easier to use with hashmaps. Seemingly mostly used in the resource
 /// system but has some usage elsewhere too.
@@ -9,7 +51,7 @@ use crate::dltx::DLBasicString;
 /// Source of name: RTTI
 pub struct FD4BasicHashString {
     vftable: usize,
-    allocator: usize,
+    allocator: Option<DLAllocatorRef>,
     /// The contained string we're hashing for.
     inner: DLBasicString,
     /// Hashed representation of the string field.
@@ -19,6 +61,149 @@ pub struct FD4BasicHashString {
     _pad35: [u8; 0xB],
 }
 
+impl FD4BasicHashString {
+    /// Sets the game's vftable for FD4BasicHashString, which is used by all
+    /// hash strings created afterwards. eldenring-util sets this in
+    /// `system::init`.
+    pub fn set_vftable(vftable: usize) {
+        VFTABLE.store(vftable, Ordering::Relaxed);
+    }
+
+    /// Creates a hash string owned by `allocator` using the vftable set with
+    /// [`FD4BasicHashString::set_vftable`]. Fails if no vftable has been set.
+    ///
+    /// The string is hashed with [`FD4BasicHashString::hash_utf16`] once the
+    /// hash has been confirmed against the game's, see
+    /// [`FD4BasicHashString::hash_check`]. Until then hashing is left to the
+    /// game.
+    ///
+    /// The buffer is not freed when the string is dropped, the string is meant
+    /// to be handed over to the game. Use [`OwnedFD4BasicHashString`] for
+    /// strings that stay on the Rust side.
+    pub fn new_in(value: &str, allocator: DLAllocatorRef) -> Result<Self, HashStringError> {
+        Self::from_utf16_in(&value.encode_utf16().collect::<Vec<_>>(), allocator)
+    }
+
+    /// Like [`FD4BasicHashString::new_in`] but takes UTF-16.
+    pub fn from_utf16_in(
+        units: &[u16],
+        allocator: DLAllocatorRef,
+    ) -> Result<Self, HashStringError> {
+        match VFTABLE.load(Ordering::Relaxed) {
+            0 => Err(HashStringError::NoVftable),
+            vftable => Ok(Self::with_vftable_in(units, allocator, vftable)),
+        }
+    }
+
+    /// Like [`FD4BasicHashString::from_utf16_in`] but with an explicit
+    /// vftable, ex: for strings that are never handed to the game.
+    pub fn with_vftable_in(units: &[u16], allocator: DLAllocatorRef, vftable: usize) -> Self {
+        let hash = (Self::hash_check() == HashCheck::Confirmed).then(|| Self::hash_utf16(units));
+        Self::build(units, allocator, vftable, hash)
+    }
+
+    fn build(units: &[u16], allocator: DLAllocatorRef, vftable: usize, hash: Option<u32>) -> Self {
+        Self {
+            vftable,
+            allocator: Some(allocator),
+            inner: DLBasicString::from_utf16_in(units, allocator),
+            hash: hash.unwrap_or(0),
+            needs_hashing: hash.is_none() as u8,
+            _pad35: [0; 0xB],
+        }
+    }
+
+    /// See [`FD4BasicHashString::hash_utf16`].
+    pub fn hash_str(value: &str) -> u32 {
+        Self::hash_utf16(&value.encode_utf16().collect::<Vec<_>>())
+    }
+
+    /// FNV-1a over the UTF-16 units with ASCII uppercase folded to lowercase.
+    ///
+    /// This is a best guess at the game's hash. It's checked against the
+    /// hashes the game computed for its own strings before anything relies on
+    /// it, see [`FD4BasicHashString::hash_check`].
+    pub fn hash_utf16(units: &[u16]) -> u32 {
+        units.iter().fold(FNV_OFFSET_BASIS, |hash, unit| {
+            let unit = match *unit {
+                unit @ 0x41..=0x5a => unit + 0x20,
+                unit => unit,
+            };
+
+            (hash ^ unit as u32).wrapping_mul(FNV_PRIME)
+        })
+    }
+
+    pub fn as_utf16(&self) -> &[u16] {
+        self.inner.as_utf16()
+    }
+
+    /// The hash computed by the game, if the game has hashed the string yet.
+    pub fn get_hash(&self) -> Option<u32> {
+        (self.needs_hashing == 0).then_some(self.hash)
+    }
+
+    /// Whether [`FD4BasicHashString::hash_utf16`] has been confirmed against
+    /// the game's hashes. Strings are only hashed on the Rust side and lookups
+    /// only rely on the hash once this is [`HashCheck::Confirmed`].
+    pub fn hash_check() -> HashCheck {
+        match HASH_CHECK.load(Ordering::Relaxed) {
+            HASH_CONFIRMED => HashCheck::Confirmed,
+            HASH_MISMATCH => HashCheck::Mismatch,
+            _ => HashCheck::Unchecked,
+        }
+    }
+
+    /// Records the outcome of checking [`FD4BasicHashString::hash_utf16`]
+    /// against strings hashed by the game, see
+    /// [`FD4BasicHashString::matches_hash`]. A mismatch sticks, later checks
+    /// can't confirm the hash again.
+    pub fn record_hash_check(matched: bool) {
+        if matched {
+            let _ = HASH_CHECK.compare_exchange(
+                HASH_UNCHECKED,
+                HASH_CONFIRMED,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            );
+        } else if HASH_CHECK.swap(HASH_MISMATCH, Ordering::Relaxed) != HASH_MISMATCH {
+            tracing::warn!("FD4BasicHashString::hash_utf16 does not match the game's hash");
+        }
+    }
+
+    /// Whether [`FD4BasicHashString::hash_utf16`] produces the hash the game
+    /// computed for this string. None if the game hasn't hashed it yet.
+    pub fn matches_hash(&self) -> Option<bool> {
+        self.get_hash()
+            .map(|hash| hash == Self::hash_utf16(self.as_utf16()))
+    }
+
+    /// Replaces the contents of the string. The string is rehashed like in
+    /// [`FD4BasicHashString::new_in`].
+    ///
+    /// # Panics
+    /// Panics if the string has no allocator.
+    pub fn assign(&mut self, value: &str) {
+        let allocator = self
+            .allocator
+            .expect("FD4BasicHashString without allocator cannot be assigned to");
+        let units = value.encode_utf16().collect::<Vec<_>>();
+
+        // SAFETY: any heap buffer was allocated by the string's allocator.
+        unsafe { self.inner.assign(&units, allocator) };
+        match Self::hash_check() {
+            HashCheck::Confirmed => {
+                self.hash = Self::hash_utf16(&units);
+                self.needs_hashing = 0;
+            }
+            _ => {
+                self.hash = 0;
+                self.needs_hashing = 1;
+            }
+        }
+    }
+}
+
 impl AsRef<DLBasicString> for FD4BasicHashString {
     fn as_ref(&self) -> &DLBasicString {
         &self.inner
@@ -31,12 +216,194 @@ impl Display for FD4BasicHashString {
     }
 }
 
+/// A [`FD4BasicHashString`] created on the Rust side that frees its buffer
+/// when dropped, ex: for names passed by reference to the game's lookups.
+///
+/// [`FD4BasicHashString`] itself describes the game's strings, which are freed
+/// by the game, so it doesn't free anything on drop.
+pub struct OwnedFD4BasicHashString(FD4BasicHashString);
+
+impl OwnedFD4BasicHashString {
+    /// See [`FD4BasicHashString::new_in`].
+    pub fn new_in(value: &str, allocator: DLAllocatorRef) -> Result<Self, HashStringError> {
+        FD4BasicHashString::new_in(value, allocator).map(Self)
+    }
+
+    /// See [`FD4BasicHashString::from_utf16_in`].
+    pub fn from_utf16_in(
+        units: &[u16],
+        allocator: DLAllocatorRef,
+    ) -> Result<Self, HashStringError> {
+        FD4BasicHashString::from_utf16_in(units, allocator).map(Self)
+    }
+
+    /// See [`FD4BasicHashString::with_vftable_in`].
+    pub fn with_vftable_in(units: &[u16], allocator: DLAllocatorRef, vftable: usize) -> Self {
+        Self(FD4BasicHashString::with_vftable_in(
+            units, allocator, vftable,
+        ))
+    }
+
+    /// Gives up ownership of the buffer, ex: to hand the string over to the
+    /// game.
+    pub fn into_inner(self) -> FD4BasicHashString {
+        let string = ManuallyDrop::new(self);
+        // SAFETY: the wrapper is not dropped so the buffer has a single owner.
+        unsafe { ptr::read(&string.0) }
+    }
+
+    fn allocator(&self) -> DLAllocatorRef {
+        self.0
+            .allocator
+            .expect("OwnedFD4BasicHashString is always created with an allocator")
+    }
+}
+
+impl Deref for OwnedFD4BasicHashString {
+    type Target = FD4BasicHashString;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for OwnedFD4BasicHashString {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl Display for OwnedFD4BasicHashString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Clone for OwnedFD4BasicHashString {
+    fn clone(&self) -> Self {
+        let allocator = self.allocator();
+
+        Self(FD4BasicHashString {
+            vftable: self.0.vftable,
+            allocator: Some(allocator),
+            inner: DLBasicString::from_utf16_in(self.0.as_utf16(), allocator),
+            hash: self.0.hash,
+            needs_hashing: self.0.needs_hashing,
+            _pad35: [0; 0xB],
+        })
+    }
+}
+
+impl Drop for OwnedFD4BasicHashString {
+    fn drop(&mut self) {
+        // SAFETY: any heap buffer was allocated by the string's allocator.
+        unsafe { self.0.inner.free(self.allocator()) };
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use crate::fd4::FD4BasicHashString;
+    use super::{FD4BasicHashString, OwnedFD4BasicHashString};
+    use crate::dlkr::mock::MockAllocator;
+
+    const VFTABLE: usize = 0x1_4000_1000;
+
+    fn utf16(value: &str) -> Vec<u16> {
+        value.encode_utf16().collect()
+    }
 
     #[test]
     fn proper_sizes() {
         assert_eq!(0x40, size_of::<FD4BasicHashString>());
     }
+
+    #[test]
+    fn leaves_hashing_to_the_game_until_confirmed() {
+        let mut allocator = MockAllocator::new();
+        // Built directly so the test doesn't depend on the shared hash check.
+        let mut name = OwnedFD4BasicHashString(FD4BasicHashString::build(
+            &utf16("EquipParamWeapon"),
+            allocator.handle(),
+            VFTABLE,
+            None,
+        ));
+
+        assert_eq!(name.to_string(), "EquipParamWeapon");
+        assert_eq!(name.needs_hashing, 1);
+        assert_eq!(name.get_hash(), None);
+        assert_eq!(name.matches_hash(), None);
+
+        // As if the game hashed the string.
+        name.hash = 0x1234;
+        name.needs_hashing = 0;
+        assert_eq!(name.get_hash(), Some(0x1234));
+        assert_eq!(name.matches_hash(), Some(false));
+
+        let clone = name.clone();
+        assert_eq!(clone.to_string(), "EquipParamWeapon");
+        assert_eq!(clone.get_hash(), Some(0x1234));
+
+        let hashed = OwnedFD4BasicHashString(FD4BasicHashString::build(
+            &utf16("NpcParam"),
+            allocator.handle(),
+            VFTABLE,
+            Some(FD4BasicHashString::hash_str("NpcParam")),
+        ));
+        assert_eq!(hashed.needs_hashing, 0);
+        assert_eq!(hashed.matches_hash(), Some(true));
+        assert_eq!(allocator.live_allocations(), 3);
+
+        // Assigning drops the game's hash.
+        name.assign("SpEffectParam");
+        assert_eq!(name.to_string(), "SpEffectParam");
+        assert_ne!(name.get_hash(), Some(0x1234));
+
+        drop(name);
+        drop(clone);
+        drop(hashed);
+        assert_eq!(allocator.live_allocations(), 0);
+    }
+
+    #[test]
+    fn new_requires_a_vftable() {
+        // No test sets the shared vftable.
+        let mut allocator = MockAllocator::new();
+        assert!(FD4BasicHashString::new_in("NpcParam", allocator.handle()).is_err());
+        assert!(OwnedFD4BasicHashString::new_in("NpcParam", allocator.handle()).is_err());
+        assert_eq!(allocator.live_allocations(), 0);
+    }
+
+    #[test]
+    fn hash_folds_ascii_case() {
+        assert_eq!(
+            FD4BasicHashString::hash_str("data0:/regulation.bin"),
+            FD4BasicHashString::hash_str("DATA0:/REGULATION.BIN")
+        );
+        assert_ne!(
+            FD4BasicHashString::hash_str("EquipParamWeapon"),
+            FD4BasicHashString::hash_str("EquipParamGoods")
+        );
+    }
+
+    #[test]
+    fn uses_the_game_vftable() {
+        let mut allocator = MockAllocator::new();
+        // Leaves the shared vftable alone so other tests don't depend on the
+        // order tests run in.
+        let name = OwnedFD4BasicHashString::with_vftable_in(
+            &utf16("data0:/regulation.bin"),
+            allocator.handle(),
+            VFTABLE,
+        );
+        assert_eq!(name.vftable, VFTABLE);
+        assert_eq!(name.clone().vftable, VFTABLE);
+
+        // By-value copies of the game's strings must not free its buffers.
+        let name = name.into_inner();
+        {
+            let _copy = unsafe { std::ptr::read(&name) };
+        }
+        assert_eq!(allocator.live_allocations(), 1);
+        assert_eq!(name.to_string(), "data0:/regulation.bin");
+    }
 }
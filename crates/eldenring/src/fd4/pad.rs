@@ -0,0 +1,44 @@
+use shared::OwnedPtr;
+
+use crate::dluid::DLUserInputDevice;
+use crate::Vector;
+
+/// Polls the input devices for every local user. Runs as part of
+/// `CSTaskGroupIndex::PadStep`.
+///
+/// The offset of `pad_devices` hasn't been confirmed against a game build.
+#[repr(C)]
+#[dlrf::singleton("FD4PadManager")]
+pub struct FD4PadManager {
+    vftable: usize,
+    unk8: [u8; 0x10],
+    /// One pad device per local user, the first one belongs to the player.
+    pub pad_devices: Vector<OwnedPtr<FD4PadDevice>>,
+}
+
+impl FD4PadManager {
+    pub fn pad_device(&self, index: usize) -> Option<&FD4PadDevice> {
+        self.pad_devices.items().get(index).map(|d| d.as_ref())
+    }
+}
+
+/// The offset of `user_input_device` hasn't been confirmed against a game
+/// build.
+#[repr(C)]
+pub struct FD4PadDevice {
+    vftable: usize,
+    unk8: [u8; 0x10],
+    /// Device holding the merged input of this user's physical devices.
+    pub user_input_device: OwnedPtr<DLUserInputDevice>,
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fd4::{FD4PadDevice, FD4PadManager};
+
+    #[test]
+    fn proper_sizes() {
+        assert_eq!(0x38, size_of::<FD4PadManager>());
+        assert_eq!(0x20, size_of::<FD4PadDevice>());
+    }
+}
@@ -38,6 +38,34 @@ impl FD4ParamRepository {
         // SAFETY: we shouldn't run into invalid casts because of the code gen dictating T::NAME.
         unsafe { file_header.data.get_mut(id) }
     }
+
+    /// Retrieves a row from the param with the specified resource name, ex:
+    /// "EquipParamWeapon". Unlike [`FD4ParamRepository::get`] this also works
+    /// for params that share a paramdef. Returns None if the param's paramdef
+    /// does not match T.
+    ///
+    /// See [`FD4ResCapHolder::get`](super::FD4ResCapHolder::get) for how the
+    /// param is looked up.
+    pub fn get_by_name<T: ParamDef>(&self, param_name: &str, id: u32) -> Option<&T> {
+        let file_header = self.res_rep.res_cap_holder.get(param_name)?;
+        if file_header.data.name() != T::NAME {
+            return None;
+        }
+
+        // SAFETY: paramdef of the param was checked against T above.
+        unsafe { file_header.data.get(id) }
+    }
+
+    /// Like [`FD4ParamRepository::get_by_name`] but hands out a mutable reference.
+    pub fn get_by_name_mut<T: ParamDef>(&mut self, param_name: &str, id: u32) -> Option<&mut T> {
+        let file_header = self.res_rep.res_cap_holder.get_mut(param_name)?;
+        if file_header.data.name() != T::NAME {
+            return None;
+        }
+
+        // SAFETY: paramdef of the param was checked against T above.
+        unsafe { file_header.data.get_mut(id) }
+    }
 }
 
 #[repr(C)]
@@ -1,7 +1,7 @@
 use core::ffi;
 use std::ptr::NonNull;
 
-use crate::fd4::FD4BasicHashString;
+use crate::fd4::{FD4BasicHashString, HashCheck};
 
 /// Represents a managed resource.
 /// The data it represents is immediately handed over to
@@ -102,6 +102,83 @@ impl<T> FD4ResCapHolder<T>
 where
     T: AsRef<FD4ResCap<T>>,
 {
+    /// Looks up an entry by its resource name. Names are compared
+    /// case-insensitively.
+    ///
+    /// Only the bucket the name hashes to is walked once
+    /// [`FD4BasicHashString::hash_utf16`] has been confirmed against the
+    /// game's hashes. Until then, or if it doesn't match, all buckets are
+    /// walked. A lookup made while the hash is unchecked checks it against
+    /// the holder's entries first.
+    pub fn get(&self, name: &str) -> Option<&T> {
+        let entry = self.find(name)?;
+        Some(unsafe { entry.as_ref() })
+    }
+
+    fn find(&self, name: &str) -> Option<NonNull<T>> {
+        if FD4BasicHashString::hash_check() == HashCheck::Unchecked {
+            if let Some(matched) = self.check_hashes() {
+                FD4BasicHashString::record_hash_check(matched);
+            }
+        }
+
+        let single_bucket = FD4BasicHashString::hash_check() == HashCheck::Confirmed;
+        self.find_in(name, single_bucket)
+    }
+
+    fn find_in(&self, name: &str, single_bucket: bool) -> Option<NonNull<T>> {
+        if self.bucket_count == 0 {
+            return None;
+        }
+
+        let name = name.encode_utf16().collect::<Vec<_>>();
+        if single_bucket {
+            let bucket = FD4BasicHashString::hash_utf16(&name) % self.bucket_count;
+            return self.find_in_bucket(bucket, &name);
+        }
+
+        (0..self.bucket_count).find_map(|bucket| self.find_in_bucket(bucket, &name))
+    }
+
+    /// Checks [`FD4BasicHashString::hash_utf16`] against the hashes of the
+    /// entries the game hashed and verifies that they're in the bucket their
+    /// hash points at. None if the game hasn't hashed any of the entries.
+    fn check_hashes(&self) -> Option<bool> {
+        let mut checked = false;
+
+        for bucket in 0..self.bucket_count {
+            let mut current = unsafe { *self.buckets.as_ptr().add(bucket as usize) };
+            while let Some(entry) = current {
+                let res_cap = unsafe { entry.as_ref() }.as_ref();
+                if let Some(matched) = res_cap.name.matches_hash() {
+                    if !matched || res_cap.name.hash % self.bucket_count != bucket {
+                        return Some(false);
+                    }
+                    checked = true;
+                }
+
+                current = res_cap.next_item;
+            }
+        }
+
+        checked.then_some(true)
+    }
+
+    /// Walks the chain of a bucket.
+    fn find_in_bucket(&self, bucket: u32, name: &[u16]) -> Option<NonNull<T>> {
+        let mut current = unsafe { *self.buckets.as_ptr().add(bucket as usize) };
+        while let Some(entry) = current {
+            let res_cap = unsafe { entry.as_ref() }.as_ref();
+            if eq_ignore_ascii_case(res_cap.name.as_utf16(), name) {
+                return Some(entry);
+            }
+
+            current = res_cap.next_item;
+        }
+
+        None
+    }
+
     /// Immutable iterator over entries.
     pub fn entries<'a>(&'a self) -> impl Iterator<Item = &'a T> + 'a {
         // For immutable iteration we can store the current chain pointer (if any)
@@ -160,6 +237,12 @@ impl<T> FD4ResCapHolder<T>
 where
     T: AsRef<FD4ResCap<T>> + AsMut<FD4ResCap<T>>,
 {
+    /// Like [`FD4ResCapHolder::get`] but hands out a mutable reference.
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut T> {
+        let mut entry = self.find(name)?;
+        Some(unsafe { entry.as_mut() })
+    }
+
     pub fn entries_mut<'a>(&'a mut self) -> impl Iterator<Item = &'a mut T> + 'a {
         struct IterMut<'a, T: AsMut<FD4ResCap<T>> + AsRef<FD4ResCap<T>>> {
             buckets_ptr: *const Option<NonNull<T>>,
@@ -215,8 +298,18 @@ where
     }
 }
 
+fn eq_ignore_ascii_case(a: &[u16], b: &[u16]) -> bool {
+    let lower = |unit: u16| match unit {
+        0x41..=0x5a => unit + 0x20,
+        unit => unit,
+    };
+
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| lower(*a) == lower(*b))
+}
+
 /// Represents file load state for this FD4FileCap.
 #[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum FD4FileCapState {
     Initial = 0x0,
     Queued = 0x1,
@@ -310,3 +403,188 @@ where
         &self.res_cap
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::ptr::NonNull;
+
+    use super::{FD4ResCap, FD4ResCapHolder};
+    use crate::dlkr::mock::MockAllocator;
+    use crate::fd4::FD4BasicHashString;
+
+    struct TestResCap {
+        res_cap: FD4ResCap<Self>,
+    }
+
+    impl AsRef<FD4ResCap<Self>> for TestResCap {
+        fn as_ref(&self) -> &FD4ResCap<Self> {
+            &self.res_cap
+        }
+    }
+
+    impl AsMut<FD4ResCap<Self>> for TestResCap {
+        fn as_mut(&mut self) -> &mut FD4ResCap<Self> {
+            &mut self.res_cap
+        }
+    }
+
+    /// Links the resources into the buckets their names hash to.
+    fn build_holder(
+        names: &[&str],
+        bucket_count: u32,
+        allocator: &mut MockAllocator,
+    ) -> FD4ResCapHolder<TestResCap> {
+        build_holder_by(names, bucket_count, allocator, FD4BasicHashString::hash_str)
+    }
+
+    /// Links the resources into buckets the same way the game does, new
+    /// entries become the head of their bucket.
+    fn build_holder_by(
+        names: &[&str],
+        bucket_count: u32,
+        allocator: &mut MockAllocator,
+        hash: impl Fn(&str) -> u32,
+    ) -> FD4ResCapHolder<TestResCap> {
+        let buckets: &mut [Option<NonNull<TestResCap>>] =
+            Box::leak(vec![None; bucket_count as usize].into_boxed_slice());
+
+        for name in names {
+            let hash = hash(name);
+            let bucket = (hash % bucket_count) as usize;
+            let units = name.encode_utf16().collect::<Vec<_>>();
+            let mut name = FD4BasicHashString::with_vftable_in(&units, allocator.handle(), 0);
+            name.hash = hash;
+            name.needs_hashing = 0;
+
+            let entry = Box::leak(Box::new(TestResCap {
+                res_cap: FD4ResCap {
+                    vftable: 0,
+                    name,
+                    owning_repository: None,
+                    next_item: buckets[bucket],
+                    reference_count: 1,
+                    unk5c: 0,
+                    unk60: false,
+                    unk61: [0; 7],
+                    unk68: 0,
+                    unk70: 0,
+                    unk71: [0; 7],
+                },
+            }));
+            buckets[bucket] = Some(NonNull::from(entry));
+        }
+
+        FD4ResCapHolder {
+            vftable: 0,
+            allocator: 0,
+            owning_repository: None,
+            unk18: 0,
+            bucket_count,
+            buckets: NonNull::from(&mut buckets[0]),
+        }
+    }
+
+    const NAMES: [&str; 8] = [
+        "EquipParamWeapon",
+        "EquipParamProtector",
+        "EquipParamGoods",
+        "SpEffectParam",
+        "NpcParam",
+        "Bullet",
+        "AtkParam_Pc",
+        "AtkParam_Npc",
+    ];
+
+    #[test]
+    fn get_finds_all_entries() {
+        let mut allocator = MockAllocator::new();
+        let holder = build_holder(&NAMES, 3, &mut allocator);
+
+        for name in NAMES {
+            let entry = holder.get(name).unwrap();
+            assert_eq!(entry.res_cap.name.to_string(), name);
+        }
+
+        assert_eq!(holder.entries().count(), NAMES.len());
+    }
+
+    #[test]
+    fn get_is_case_insensitive() {
+        let mut allocator = MockAllocator::new();
+        let holder = build_holder(&NAMES, 5, &mut allocator);
+
+        let entry = holder.get("equipparamweapon").unwrap();
+        assert_eq!(entry.res_cap.name.to_string(), "EquipParamWeapon");
+    }
+
+    #[test]
+    fn get_misses_unknown_names() {
+        let mut allocator = MockAllocator::new();
+        let holder = build_holder(&NAMES, 5, &mut allocator);
+
+        assert!(holder.get("EquipParamAccessory").is_none());
+        assert!(holder.get("").is_none());
+    }
+
+    #[test]
+    fn single_bucket_lookups() {
+        let mut allocator = MockAllocator::new();
+        let holder = build_holder(&NAMES, 7, &mut allocator);
+        assert_eq!(holder.check_hashes(), Some(true));
+
+        for name in NAMES {
+            let entry = unsafe { holder.find_in(name, true).unwrap().as_ref() };
+            assert_eq!(entry.res_cap.name.to_string(), name);
+        }
+        assert!(holder.find_in("EquipParamAccessory", true).is_none());
+    }
+
+    // The holders below fail the hash check, so they're not looked up
+    // through get to leave the shared hash check alone.
+
+    #[test]
+    fn lookups_walk_all_buckets_for_other_hashes() {
+        let mut allocator = MockAllocator::new();
+        let holder = build_holder_by(&NAMES, 5, &mut allocator, |name| {
+            FD4BasicHashString::hash_str(name).wrapping_add(1)
+        });
+        assert_eq!(holder.check_hashes(), Some(false));
+
+        for name in NAMES {
+            let entry = unsafe { holder.find_in(name, false).unwrap().as_ref() };
+            assert_eq!(entry.res_cap.name.to_string(), name);
+        }
+        assert!(holder.find_in("EquipParamAccessory", false).is_none());
+    }
+
+    #[test]
+    fn entries_in_other_buckets_fail_the_check() {
+        let mut allocator = MockAllocator::new();
+        let holder = build_holder(&NAMES, 7, &mut allocator);
+
+        // Keeps the hashes but moves every chain over by one bucket.
+        unsafe { std::slice::from_raw_parts_mut(holder.buckets.as_ptr(), 7) }.rotate_left(1);
+        assert_eq!(holder.check_hashes(), Some(false));
+    }
+
+    #[test]
+    fn unhashed_entries_are_not_checked() {
+        let mut allocator = MockAllocator::new();
+        let mut holder = build_holder(&NAMES, 3, &mut allocator);
+        for entry in holder.entries_mut() {
+            entry.res_cap.name.needs_hashing = 1;
+        }
+
+        assert_eq!(holder.check_hashes(), None);
+    }
+
+    #[test]
+    fn get_mut_works() {
+        let mut allocator = MockAllocator::new();
+        let mut holder = build_holder(&NAMES, 1, &mut allocator);
+
+        holder.get_mut("NpcParam").unwrap().res_cap.reference_count = 5;
+        assert_eq!(holder.get("NpcParam").unwrap().res_cap.reference_count, 5);
+        assert_eq!(holder.get("Bullet").unwrap().res_cap.reference_count, 1);
+    }
+}
@@ -6,7 +6,10 @@ use std::{
 
 use vtable_rs::VPtr;
 
-use crate::{dlrf::DLRuntimeClass, Tree, Vector};
+use crate::{
+    dlrf::{DLRuntimeClass, DLRuntimeClassHolder},
+    Tree, Vector,
+};
 
 use super::FD4Time;
 
@@ -48,6 +51,12 @@ impl FD4TaskBaseVmt for FD4TaskBase {
     }
 }
 
+impl DLRuntimeClassHolder for FD4TaskBase {
+    fn runtime_class(&self) -> &DLRuntimeClass {
+        (self.vftable.get_runtime_class)(self)
+    }
+}
+
 #[repr(C)]
 pub struct FD4TaskQueue {
     vftable: usize,
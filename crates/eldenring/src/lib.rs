@@ -1,3 +1,4 @@
+#![feature(allocator_api)]
 #![feature(once_cell_get_mut)]
 
 mod stl;
@@ -10,6 +11,7 @@ pub mod dlkr;
 pub mod dlkrd;
 pub mod dlrf;
 pub mod dltx;
+pub mod dluid;
 pub mod dlut;
 pub mod fd4;
 pub mod ffx;
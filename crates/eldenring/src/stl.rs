@@ -1,12 +1,39 @@
 use std::{
-    collections::VecDeque,
+    alloc::{handle_alloc_error, Allocator, Layout},
+    cmp::Ordering,
     marker::PhantomData,
-    ptr::{copy_nonoverlapping, NonNull},
+    ops::{Bound, RangeBounds},
+    ptr::{self, copy_nonoverlapping, NonNull},
 };
 
-use crate::dlkr::DLAllocatorBase;
+use crate::dlkr::DLAllocatorRef;
 use shared::OwnedPtr;
 
+/// Allocates room for `count` T's using the game allocator.
+///
+/// # Panics
+/// Panics if the container has no allocator.
+fn allocate<T>(allocator: Option<DLAllocatorRef>, count: usize) -> NonNull<T> {
+    let layout = Layout::array::<T>(count).expect("allocation too large");
+    allocator
+        .expect("container has no allocator")
+        .allocate(layout)
+        .unwrap_or_else(|_| handle_alloc_error(layout))
+        .cast()
+}
+
+/// Hands memory obtained through [`allocate`] back to the game allocator.
+///
+/// # Safety
+/// The allocation must have been made by the same allocator for the same
+/// amount of T's.
+unsafe fn deallocate<T>(allocator: Option<DLAllocatorRef>, allocation: NonNull<T>, count: usize) {
+    let layout = Layout::array::<T>(count).expect("allocation too large");
+    allocator
+        .expect("container has no allocator")
+        .deallocate(allocation.cast(), layout);
+}
+
 #[repr(C)]
 pub struct DoublyLinkedListNode<T> {
     pub next: NonNull<DoublyLinkedListNode<T>>,
@@ -16,7 +43,7 @@ pub struct DoublyLinkedListNode<T> {
 
 #[repr(C)]
 pub struct DoublyLinkedList<T> {
-    allocator: usize,
+    allocator: Option<DLAllocatorRef>,
     pub head: NonNull<DoublyLinkedListNode<T>>,
     pub count: u64,
 }
@@ -44,6 +71,56 @@ impl<T> DoublyLinkedList<T> {
     pub fn is_empty(&self) -> bool {
         self.len() == 0
     }
+
+    /// Appends an entry to the end of the list. The node is allocated with the
+    /// list's allocator.
+    pub fn push_back(&mut self, value: T) -> &mut T {
+        let mut node = allocate::<DoublyLinkedListNode<T>>(self.allocator, 1);
+
+        unsafe {
+            let mut last = self.head.as_ref().previous;
+            node.write(DoublyLinkedListNode {
+                next: self.head,
+                previous: last,
+                value,
+            });
+
+            last.as_mut().next = node;
+            self.head.as_mut().previous = node;
+            self.count += 1;
+
+            &mut node.as_mut().value
+        }
+    }
+
+    /// Unlinks and returns the first entry matching the predicate.
+    pub fn remove<F>(&mut self, mut predicate: F) -> Option<T>
+    where
+        F: FnMut(&T) -> bool,
+    {
+        let mut current = unsafe { self.head.as_ref().next };
+        for _ in 0..self.count {
+            let node = unsafe { current.as_ref() };
+            if !predicate(&node.value) {
+                current = node.next;
+                continue;
+            }
+
+            unsafe {
+                let mut previous = node.previous;
+                let mut next = node.next;
+                previous.as_mut().next = next;
+                next.as_mut().previous = previous;
+                self.count -= 1;
+
+                let value = ptr::read(&node.value);
+                deallocate(self.allocator, current, 1);
+                return Some(value);
+            }
+        }
+
+        None
+    }
 }
 
 #[repr(C)]
@@ -51,7 +128,7 @@ pub struct Vector<T>
 where
     T: Sized,
 {
-    allocator: NonNull<DLAllocatorBase>,
+    allocator: Option<DLAllocatorRef>,
     pub begin: Option<NonNull<T>>,
     pub end: Option<NonNull<T>>,
     pub capacity: Option<NonNull<T>>,
@@ -98,11 +175,102 @@ where
     pub fn is_empty(&self) -> bool {
         self.len() == 0
     }
+
+    /// Allocator backing the vector's storage, if the game set one.
+    pub fn allocator(&self) -> Option<DLAllocatorRef> {
+        self.allocator
+    }
+
+    /// Amount of entries the vector can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        let (Some(start), Some(capacity)) = (self.begin, self.capacity) else {
+            return 0;
+        };
+
+        (capacity.as_ptr() as usize - start.as_ptr() as usize) / size_of::<T>()
+    }
+
+    /// Ensures there's room for at least `additional` more entries, moving the
+    /// entries to a new allocation from the vector's allocator if needed.
+    pub fn reserve(&mut self, additional: usize) {
+        let len = self.len();
+        let required = len.checked_add(additional).expect("capacity overflow");
+        if required <= self.capacity() {
+            return;
+        }
+
+        let old_capacity = self.capacity();
+        let new_capacity = required.max(old_capacity * 2);
+        let allocation = allocate::<T>(self.allocator, new_capacity);
+        unsafe {
+            if let Some(start) = self.begin {
+                copy_nonoverlapping(start.as_ptr(), allocation.as_ptr(), len);
+                deallocate(self.allocator, start, old_capacity);
+            }
+
+            self.begin = Some(allocation);
+            self.end = Some(allocation.add(len));
+            self.capacity = Some(allocation.add(new_capacity));
+        }
+    }
+
+    /// Appends an entry to the back of the vector.
+    pub fn push(&mut self, value: T) {
+        self.reserve(1);
+
+        unsafe {
+            let end = self.end.unwrap();
+            end.write(value);
+            self.end = Some(end.add(1));
+        }
+    }
+
+    /// Inserts an entry at `index`, shifting all entries after it.
+    ///
+    /// # Panics
+    /// Panics if `index > len`.
+    pub fn insert(&mut self, index: usize, value: T) {
+        let len = self.len();
+        assert!(index <= len, "insertion index {index} out of bounds {len}");
+        self.reserve(1);
+
+        unsafe {
+            let slot = self.begin.unwrap().add(index);
+            ptr::copy(slot.as_ptr(), slot.as_ptr().add(1), len - index);
+            slot.write(value);
+            self.end = Some(self.end.unwrap().add(1));
+        }
+    }
+
+    /// Removes and returns the entry at `index`, shifting all entries after it.
+    ///
+    /// # Panics
+    /// Panics if `index >= len`.
+    pub fn remove(&mut self, index: usize) -> T {
+        let len = self.len();
+        assert!(index < len, "removal index {index} out of bounds {len}");
+
+        unsafe {
+            let slot = self.begin.unwrap().add(index);
+            let value = slot.read();
+            ptr::copy(slot.as_ptr().add(1), slot.as_ptr(), len - index - 1);
+            self.end = Some(self.end.unwrap().sub(1));
+            value
+        }
+    }
+
+    /// Drops all entries while keeping the allocation around.
+    pub fn clear(&mut self) {
+        let items = self.items_mut() as *mut [T];
+        self.end = self.begin;
+
+        unsafe { ptr::drop_in_place(items) };
+    }
 }
 
 #[repr(C)]
 pub struct Tree<T> {
-    allocator: usize,
+    allocator: Option<DLAllocatorRef>,
     head: NonNull<TreeNode<T>>,
     size: usize,
 }
@@ -116,33 +284,477 @@ impl<T> Tree<T> {
         self.len() == 0
     }
 
-    pub fn iter(&self) -> impl Iterator<Item = &mut T> {
-        let mut pending = VecDeque::new();
+    /// Iterates over the entries in order.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.nodes(self.first())
+            .map(|node| unsafe { &node.as_ref().value })
+    }
 
-        let start = unsafe { self.head.as_ref().parent };
-        pending.push_back(start);
+    /// Iterates over the entries in order. Changing the entries in a way that
+    /// affects their ordering will break lookups.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.nodes(self.first())
+            .map(|mut node| unsafe { &mut node.as_mut().value })
+    }
 
-        // TODO: clean up this code
-        std::iter::from_fn(move || {
-            if self.size == 0 {
-                None
-            } else if let Some(mut entry) = pending.pop_front() {
-                let entry = unsafe { entry.as_mut() };
+    /// Iterates over the entries whose key falls within the range, in order.
+    /// `key` should extract the key the game orders the entries by.
+    pub fn range<K, R, F>(&self, range: R, key: F) -> impl Iterator<Item = &T>
+    where
+        K: Ord,
+        R: RangeBounds<K>,
+        F: Fn(&T) -> K,
+    {
+        let start = self.lower_bound_by(|value| match range.start_bound() {
+            Bound::Included(start) => key(value).cmp(start),
+            Bound::Excluded(start) => match key(value).cmp(start) {
+                Ordering::Equal => Ordering::Less,
+                ordering => ordering,
+            },
+            Bound::Unbounded => Ordering::Greater,
+        });
+
+        self.nodes(start)
+            .map(|node| unsafe { &node.as_ref().value })
+            .take_while(move |value| match range.end_bound() {
+                Bound::Included(end) => key(value) <= *end,
+                Bound::Excluded(end) => key(value) < *end,
+                Bound::Unbounded => true,
+            })
+    }
+
+    /// Finds all entries for which `compare` returns [`Ordering::Equal`] by
+    /// descending the tree. `compare` should order the entries the same way the
+    /// game does, ex: `|e| e.entity_id.cmp(&entity_id)`.
+    pub fn find_by<F>(&self, compare: F) -> impl Iterator<Item = &T>
+    where
+        F: Fn(&T) -> Ordering,
+    {
+        self.nodes(self.lower_bound_by(&compare))
+            .map(|node| unsafe { &node.as_ref().value })
+            .take_while(move |value| compare(value) == Ordering::Equal)
+    }
+
+    /// Walks the nodes in order starting at `start`.
+    fn nodes(
+        &self,
+        start: Option<NonNull<TreeNode<T>>>,
+    ) -> impl Iterator<Item = NonNull<TreeNode<T>>> + '_ {
+        std::iter::successors(start, |node| self.successor(*node))
+    }
 
-                if unsafe { entry.left.as_ref() }.is_nil == 0 {
-                    pending.push_back(entry.left);
+    /// Leftmost node, the head's left points to it.
+    fn first(&self) -> Option<NonNull<TreeNode<T>>> {
+        if self.size == 0 {
+            return None;
+        }
+
+        Some(unsafe { self.head.as_ref().left })
+    }
+
+    /// Finds the first node that does not compare as [`Ordering::Less`].
+    fn lower_bound_by<F>(&self, compare: F) -> Option<NonNull<TreeNode<T>>>
+    where
+        F: Fn(&T) -> Ordering,
+    {
+        if self.size == 0 {
+            return None;
+        }
+
+        let mut result = None;
+        let mut current = unsafe { self.head.as_ref().parent };
+        loop {
+            let node = unsafe { current.as_ref() };
+            if node.is_nil != 0 {
+                break result;
+            }
+
+            if compare(&node.value) == Ordering::Less {
+                current = node.right;
+            } else {
+                result = Some(current);
+                current = node.left;
+            }
+        }
+    }
+
+    /// Returns the in-order successor of the node, or None if the node is the
+    /// last one.
+    fn successor(&self, node: NonNull<TreeNode<T>>) -> Option<NonNull<TreeNode<T>>> {
+        let mut current = unsafe { node.as_ref() }.right;
+        if unsafe { current.as_ref() }.is_nil == 0 {
+            loop {
+                let left = unsafe { current.as_ref() }.left;
+                if unsafe { left.as_ref() }.is_nil != 0 {
+                    return Some(current);
                 }
+                current = left;
+            }
+        }
+
+        let mut child = node;
+        let mut parent = unsafe { node.as_ref() }.parent;
+        while parent != self.head && unsafe { parent.as_ref() }.right == child {
+            child = parent;
+            parent = unsafe { parent.as_ref() }.parent;
+        }
+
+        (parent != self.head).then_some(parent)
+    }
+
+    /// Inserts an entry, allocating the node with the tree's allocator. `key`
+    /// should extract the key the game orders the entries by. Entries with an
+    /// equal key are kept, the new entry is placed after them.
+    pub fn insert<K, F>(&mut self, value: T, key: F) -> &mut T
+    where
+        K: Ord,
+        F: Fn(&T) -> K,
+    {
+        let head = self.head;
+        let mut node = allocate::<TreeNode<T>>(self.allocator, 1);
+        unsafe {
+            node.write(TreeNode {
+                left: head,
+                parent: head,
+                right: head,
+                black_red: RED,
+                is_nil: 0,
+                _pad1a: [0; 6],
+                value,
+            });
+        }
 
-                if unsafe { entry.right.as_ref() }.is_nil == 0 {
-                    pending.push_back(entry.right);
+        unsafe {
+            let new_key = key(&node.as_ref().value);
+
+            let mut parent = head;
+            let mut current = head.as_ref().parent;
+            let mut go_left = true;
+            while current.as_ref().is_nil == 0 {
+                parent = current;
+                go_left = new_key < key(&current.as_ref().value);
+                current = if go_left {
+                    current.as_ref().left
+                } else {
+                    current.as_ref().right
+                };
+            }
+
+            node.as_mut().parent = parent;
+            let head = head.as_ptr();
+            if parent == self.head {
+                (*head).parent = node;
+                (*head).left = node;
+                (*head).right = node;
+            } else if go_left {
+                (*parent.as_ptr()).left = node;
+                if parent == (*head).left {
+                    (*head).left = node;
                 }
+            } else {
+                (*parent.as_ptr()).right = node;
+                if parent == (*head).right {
+                    (*head).right = node;
+                }
+            }
+
+            self.size += 1;
+            self.insert_fixup(node);
 
-                Some(&mut entry.value)
+            &mut node.as_mut().value
+        }
+    }
+
+    /// Removes and returns the first entry for which `compare` returns
+    /// [`Ordering::Equal`]. The node is freed with the tree's allocator.
+    pub fn remove<F>(&mut self, compare: F) -> Option<T>
+    where
+        F: Fn(&T) -> Ordering,
+    {
+        let node = self.lower_bound_by(&compare)?;
+        if compare(unsafe { &node.as_ref().value }) != Ordering::Equal {
+            return None;
+        }
+
+        unsafe {
+            self.unlink(node);
+            self.size -= 1;
+
+            let value = ptr::read(&node.as_ref().value);
+            deallocate(self.allocator, node, 1);
+            Some(value)
+        }
+    }
+
+    /// Restores the red-black properties after linking in a red node.
+    unsafe fn insert_fixup(&mut self, mut node: NonNull<TreeNode<T>>) {
+        while color(parent(node)) == RED {
+            let mut parent_node = parent(node);
+            let mut grandparent = parent(parent_node);
+
+            if parent_node == left(grandparent) {
+                let mut uncle = right(grandparent);
+                if color(uncle) == RED {
+                    parent_node.as_mut().black_red = BLACK;
+                    uncle.as_mut().black_red = BLACK;
+                    grandparent.as_mut().black_red = RED;
+                    node = grandparent;
+                } else {
+                    if node == right(parent_node) {
+                        node = parent_node;
+                        self.rotate_left(node);
+                    }
+
+                    parent_node = parent(node);
+                    grandparent = parent(parent_node);
+                    parent_node.as_mut().black_red = BLACK;
+                    grandparent.as_mut().black_red = RED;
+                    self.rotate_right(grandparent);
+                }
             } else {
-                None
+                let mut uncle = left(grandparent);
+                if color(uncle) == RED {
+                    parent_node.as_mut().black_red = BLACK;
+                    uncle.as_mut().black_red = BLACK;
+                    grandparent.as_mut().black_red = RED;
+                    node = grandparent;
+                } else {
+                    if node == left(parent_node) {
+                        node = parent_node;
+                        self.rotate_right(node);
+                    }
+
+                    parent_node = parent(node);
+                    grandparent = parent(parent_node);
+                    parent_node.as_mut().black_red = BLACK;
+                    grandparent.as_mut().black_red = RED;
+                    self.rotate_left(grandparent);
+                }
             }
-        })
+        }
+
+        (*self.head.as_ref().parent.as_ptr()).black_red = BLACK;
     }
+
+    /// Unlinks the node from the tree and rebalances. Since the nil node is
+    /// also the head (and the head's parent is the root) the fixup tracks the
+    /// parent of the replacement node separately instead of storing it in the
+    /// nil node.
+    unsafe fn unlink(&mut self, node: NonNull<TreeNode<T>>) {
+        let head = self.head;
+        let mut removed = node;
+        let mut replacement;
+        let mut replacement_parent;
+
+        if is_nil(left(removed)) {
+            replacement = right(removed);
+        } else if is_nil(right(removed)) {
+            replacement = left(removed);
+        } else {
+            removed = right(removed);
+            while !is_nil(left(removed)) {
+                removed = left(removed);
+            }
+            replacement = right(removed);
+        }
+
+        if removed != node {
+            // Node has two children, move its successor into its place.
+            (*left(node).as_ptr()).parent = removed;
+            (*removed.as_ptr()).left = left(node);
+
+            if removed != right(node) {
+                replacement_parent = parent(removed);
+                if !is_nil(replacement) {
+                    (*replacement.as_ptr()).parent = replacement_parent;
+                }
+                (*replacement_parent.as_ptr()).left = replacement;
+                (*removed.as_ptr()).right = right(node);
+                (*right(node).as_ptr()).parent = removed;
+            } else {
+                replacement_parent = removed;
+            }
+
+            self.replace_child(node, removed);
+            (*removed.as_ptr()).parent = parent(node);
+
+            let removed_color = color(removed);
+            (*removed.as_ptr()).black_red = color(node);
+            (*node.as_ptr()).black_red = removed_color;
+        } else {
+            replacement_parent = parent(node);
+            if !is_nil(replacement) {
+                (*replacement.as_ptr()).parent = replacement_parent;
+            }
+
+            self.replace_child(node, replacement);
+
+            if head.as_ref().left == node {
+                (*head.as_ptr()).left = if is_nil(right(node)) {
+                    parent(node)
+                } else {
+                    minimum(replacement)
+                };
+            }
+
+            if head.as_ref().right == node {
+                (*head.as_ptr()).right = if is_nil(left(node)) {
+                    parent(node)
+                } else {
+                    maximum(replacement)
+                };
+            }
+        }
+
+        // After the swap above `node` holds the color of the node that was
+        // physically taken out of the tree.
+        if color(node) == RED {
+            return;
+        }
+
+        while replacement != head.as_ref().parent && color(replacement) == BLACK {
+            if replacement == left(replacement_parent) {
+                let mut sibling = right(replacement_parent);
+                if color(sibling) == RED {
+                    sibling.as_mut().black_red = BLACK;
+                    (*replacement_parent.as_ptr()).black_red = RED;
+                    self.rotate_left(replacement_parent);
+                    sibling = right(replacement_parent);
+                }
+
+                if color(left(sibling)) == BLACK && color(right(sibling)) == BLACK {
+                    sibling.as_mut().black_red = RED;
+                    replacement = replacement_parent;
+                    replacement_parent = parent(replacement_parent);
+                } else {
+                    if color(right(sibling)) == BLACK {
+                        (*left(sibling).as_ptr()).black_red = BLACK;
+                        sibling.as_mut().black_red = RED;
+                        self.rotate_right(sibling);
+                        sibling = right(replacement_parent);
+                    }
+
+                    sibling.as_mut().black_red = color(replacement_parent);
+                    (*replacement_parent.as_ptr()).black_red = BLACK;
+                    if !is_nil(right(sibling)) {
+                        (*right(sibling).as_ptr()).black_red = BLACK;
+                    }
+                    self.rotate_left(replacement_parent);
+                    break;
+                }
+            } else {
+                let mut sibling = left(replacement_parent);
+                if color(sibling) == RED {
+                    sibling.as_mut().black_red = BLACK;
+                    (*replacement_parent.as_ptr()).black_red = RED;
+                    self.rotate_right(replacement_parent);
+                    sibling = left(replacement_parent);
+                }
+
+                if color(left(sibling)) == BLACK && color(right(sibling)) == BLACK {
+                    sibling.as_mut().black_red = RED;
+                    replacement = replacement_parent;
+                    replacement_parent = parent(replacement_parent);
+                } else {
+                    if color(left(sibling)) == BLACK {
+                        (*right(sibling).as_ptr()).black_red = BLACK;
+                        sibling.as_mut().black_red = RED;
+                        self.rotate_left(sibling);
+                        sibling = left(replacement_parent);
+                    }
+
+                    sibling.as_mut().black_red = color(replacement_parent);
+                    (*replacement_parent.as_ptr()).black_red = BLACK;
+                    if !is_nil(left(sibling)) {
+                        (*left(sibling).as_ptr()).black_red = BLACK;
+                    }
+                    self.rotate_right(replacement_parent);
+                    break;
+                }
+            }
+        }
+
+        if !is_nil(replacement) {
+            (*replacement.as_ptr()).black_red = BLACK;
+        }
+    }
+
+    /// Points whatever referenced `old` as a child (or the head when `old` is
+    /// the root) at `new`.
+    unsafe fn replace_child(&mut self, old: NonNull<TreeNode<T>>, new: NonNull<TreeNode<T>>) {
+        let old_parent = parent(old);
+        if old_parent == self.head {
+            self.head.as_mut().parent = new;
+        } else if left(old_parent) == old {
+            (*old_parent.as_ptr()).left = new;
+        } else {
+            (*old_parent.as_ptr()).right = new;
+        }
+    }
+
+    unsafe fn rotate_left(&mut self, node: NonNull<TreeNode<T>>) {
+        let pivot = right(node);
+        (*node.as_ptr()).right = left(pivot);
+        if !is_nil(left(pivot)) {
+            (*left(pivot).as_ptr()).parent = node;
+        }
+
+        (*pivot.as_ptr()).parent = parent(node);
+        self.replace_child(node, pivot);
+        (*pivot.as_ptr()).left = node;
+        (*node.as_ptr()).parent = pivot;
+    }
+
+    unsafe fn rotate_right(&mut self, node: NonNull<TreeNode<T>>) {
+        let pivot = left(node);
+        (*node.as_ptr()).left = right(pivot);
+        if !is_nil(right(pivot)) {
+            (*right(pivot).as_ptr()).parent = node;
+        }
+
+        (*pivot.as_ptr()).parent = parent(node);
+        self.replace_child(node, pivot);
+        (*pivot.as_ptr()).right = node;
+        (*node.as_ptr()).parent = pivot;
+    }
+}
+
+/// Node colors as used by MSVC's tree implementation.
+const RED: u8 = 0;
+const BLACK: u8 = 1;
+
+unsafe fn left<T>(node: NonNull<TreeNode<T>>) -> NonNull<TreeNode<T>> {
+    node.as_ref().left
+}
+
+unsafe fn right<T>(node: NonNull<TreeNode<T>>) -> NonNull<TreeNode<T>> {
+    node.as_ref().right
+}
+
+unsafe fn parent<T>(node: NonNull<TreeNode<T>>) -> NonNull<TreeNode<T>> {
+    node.as_ref().parent
+}
+
+unsafe fn color<T>(node: NonNull<TreeNode<T>>) -> u8 {
+    node.as_ref().black_red
+}
+
+unsafe fn is_nil<T>(node: NonNull<TreeNode<T>>) -> bool {
+    node.as_ref().is_nil != 0
+}
+
+unsafe fn minimum<T>(mut node: NonNull<TreeNode<T>>) -> NonNull<TreeNode<T>> {
+    while !is_nil(left(node)) {
+        node = left(node);
+    }
+    node
+}
+
+unsafe fn maximum<T>(mut node: NonNull<TreeNode<T>>) -> NonNull<TreeNode<T>> {
+    while !is_nil(right(node)) {
+        node = right(node);
+    }
+    node
 }
 
 #[repr(C)]
@@ -195,3 +807,377 @@ pub struct CSFixedListEntry<T> {
     pub previous: Option<NonNull<CSFixedListEntry<T>>>,
     index: usize,
 }
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+    use std::ptr::NonNull;
+
+    use super::{DoublyLinkedList, DoublyLinkedListNode, Tree, TreeNode, Vector, BLACK};
+    use crate::dlkr::mock::MockAllocator;
+
+    /// Builds a balanced tree out of sorted values the same way MSVC lays
+    /// it out: the head doubles as the nil node, its parent is the root and
+    /// its left and right point to the smallest and largest node.
+    fn build_tree(values: &[u32]) -> Tree<u32> {
+        fn node(value: u32, nil: NonNull<TreeNode<u32>>) -> NonNull<TreeNode<u32>> {
+            NonNull::from(Box::leak(Box::new(TreeNode {
+                left: nil,
+                parent: nil,
+                right: nil,
+                black_red: 0,
+                is_nil: 0,
+                _pad1a: [0; 6],
+                value,
+            })))
+        }
+
+        fn build(
+            values: &[u32],
+            parent: NonNull<TreeNode<u32>>,
+            nil: NonNull<TreeNode<u32>>,
+        ) -> NonNull<TreeNode<u32>> {
+            if values.is_empty() {
+                return nil;
+            }
+
+            let middle = values.len() / 2;
+            let mut current = node(values[middle], nil);
+            unsafe {
+                current.as_mut().parent = parent;
+                current.as_mut().left = build(&values[..middle], current, nil);
+                current.as_mut().right = build(&values[middle + 1..], current, nil);
+            }
+            current
+        }
+
+        let mut head = node(0, NonNull::dangling());
+        unsafe {
+            head.as_mut().is_nil = 1;
+            head.as_mut().black_red = BLACK;
+            head.as_mut().left = head;
+            head.as_mut().parent = head;
+            head.as_mut().right = head;
+        }
+
+        let root = build(values, head, head);
+        unsafe {
+            head.as_mut().parent = root;
+            if !values.is_empty() {
+                let mut leftmost = root;
+                while leftmost.as_ref().left != head {
+                    leftmost = leftmost.as_ref().left;
+                }
+
+                let mut rightmost = root;
+                while rightmost.as_ref().right != head {
+                    rightmost = rightmost.as_ref().right;
+                }
+
+                head.as_mut().left = leftmost;
+                head.as_mut().right = rightmost;
+            }
+        }
+
+        Tree {
+            allocator: None,
+            head,
+            size: values.len(),
+        }
+    }
+
+    fn empty_tree(allocator: &mut MockAllocator) -> Tree<u32> {
+        let mut tree = build_tree(&[]);
+        tree.allocator = Some(allocator.handle());
+        tree
+    }
+
+    /// Checks the red-black invariants and the MSVC head layout.
+    fn validate_tree(tree: &Tree<u32>) {
+        unsafe fn validate(node: NonNull<TreeNode<u32>>) -> usize {
+            let node = node.as_ref();
+            if node.is_nil != 0 {
+                return 1;
+            }
+
+            for child in [node.left, node.right] {
+                if child.as_ref().is_nil == 0 {
+                    assert_eq!(child.as_ref().parent, NonNull::from(node));
+                    assert!(
+                        node.black_red == BLACK || child.as_ref().black_red == BLACK,
+                        "red node with red child"
+                    );
+                }
+            }
+
+            let left = validate(node.left);
+            let right = validate(node.right);
+            assert_eq!(left, right, "unequal black height");
+
+            left + (node.black_red == BLACK) as usize
+        }
+
+        unsafe {
+            let head = tree.head.as_ref();
+            assert_eq!(head.is_nil, 1);
+            if tree.size == 0 {
+                assert_eq!(head.parent, tree.head);
+                assert_eq!(head.left, tree.head);
+                assert_eq!(head.right, tree.head);
+                return;
+            }
+
+            let root = head.parent;
+            assert_eq!(root.as_ref().parent, tree.head);
+            assert_eq!(root.as_ref().black_red, BLACK);
+            assert_eq!(head.left, super::minimum(root));
+            assert_eq!(head.right, super::maximum(root));
+            validate(root);
+        }
+
+        assert_eq!(tree.iter().count(), tree.len());
+        assert!(tree.iter().is_sorted());
+    }
+
+    #[test]
+    fn tree_iter_is_in_order() {
+        let values = (0..37).map(|v| v * 3).collect::<Vec<_>>();
+        let tree = build_tree(&values);
+
+        assert_eq!(tree.iter().copied().collect::<Vec<_>>(), values);
+    }
+
+    #[test]
+    fn tree_iter_empty() {
+        let tree = build_tree(&[]);
+
+        assert_eq!(tree.iter().count(), 0);
+        assert_eq!(tree.find_by(|v| v.cmp(&0)).count(), 0);
+    }
+
+    #[test]
+    fn tree_iter_mut_works() {
+        let mut tree = build_tree(&[1, 2, 3, 4, 5]);
+        tree.iter_mut().for_each(|v| *v *= 10);
+
+        assert_eq!(
+            tree.iter().copied().collect::<Vec<_>>(),
+            vec![10, 20, 30, 40, 50]
+        );
+    }
+
+    #[test]
+    fn tree_find_by_works() {
+        let tree = build_tree(&[1, 3, 5, 5, 5, 7, 9, 11]);
+
+        assert_eq!(tree.find_by(|v| v.cmp(&7)).collect::<Vec<_>>(), vec![&7]);
+        assert_eq!(tree.find_by(|v| v.cmp(&5)).count(), 3);
+        assert_eq!(tree.find_by(|v| v.cmp(&4)).count(), 0);
+        assert_eq!(tree.find_by(|v| v.cmp(&12)).count(), 0);
+    }
+
+    #[test]
+    fn tree_range_works() {
+        let tree = build_tree(&(0..20).collect::<Vec<_>>());
+
+        assert_eq!(
+            tree.range(5..8, |v| *v).copied().collect::<Vec<_>>(),
+            vec![5, 6, 7]
+        );
+        assert_eq!(
+            tree.range(17.., |v| *v).copied().collect::<Vec<_>>(),
+            vec![17, 18, 19]
+        );
+        assert_eq!(
+            tree.range(..=2, |v| *v).copied().collect::<Vec<_>>(),
+            vec![0, 1, 2]
+        );
+        assert_eq!(
+            tree.range(
+                (std::ops::Bound::Excluded(3), std::ops::Bound::Included(5)),
+                |v| *v
+            )
+            .copied()
+            .collect::<Vec<_>>(),
+            vec![4, 5]
+        );
+    }
+
+    #[test]
+    fn vector_push_insert_remove_works() {
+        let mut allocator = MockAllocator::new();
+        let mut vector = Vector::<u32> {
+            allocator: Some(allocator.handle()),
+            begin: None,
+            end: None,
+            capacity: None,
+        };
+
+        (0..10).for_each(|v| vector.push(v));
+        assert_eq!(vector.items(), &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        assert!(vector.capacity() >= 10);
+
+        vector.insert(0, 100);
+        vector.insert(5, 200);
+        vector.insert(12, 300);
+        assert_eq!(
+            vector.items(),
+            &[100, 0, 1, 2, 3, 200, 4, 5, 6, 7, 8, 9, 300]
+        );
+
+        assert_eq!(vector.remove(5), 200);
+        assert_eq!(vector.remove(0), 100);
+        assert_eq!(vector.remove(10), 300);
+        assert_eq!(vector.items(), &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+
+        // Old allocations are handed back when growing.
+        assert_eq!(allocator.live_allocations(), 1);
+
+        vector.reserve(100);
+        assert!(vector.capacity() >= 110);
+        assert_eq!(vector.items(), &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        assert_eq!(allocator.live_allocations(), 1);
+
+        vector.clear();
+        assert!(vector.is_empty());
+        assert!(vector.capacity() >= 110);
+    }
+
+    #[test]
+    #[should_panic(expected = "container has no allocator")]
+    fn vector_without_allocator() {
+        let mut vector = Vector::<u32> {
+            allocator: None,
+            begin: None,
+            end: None,
+            capacity: None,
+        };
+
+        assert!(vector.allocator().is_none());
+        assert!(vector.is_empty());
+        vector.reserve(0);
+        vector.push(1);
+    }
+
+    #[test]
+    fn vector_clear_drops_entries() {
+        struct Dropped<'a>(&'a Cell<usize>);
+
+        impl Drop for Dropped<'_> {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+
+        let drops = Cell::new(0);
+        let mut allocator = MockAllocator::new();
+        let mut vector = Vector {
+            allocator: Some(allocator.handle()),
+            begin: None,
+            end: None,
+            capacity: None,
+        };
+
+        (0..5).for_each(|_| vector.push(Dropped(&drops)));
+        drop(vector.remove(2));
+        assert_eq!(drops.get(), 1);
+
+        vector.clear();
+        assert_eq!(drops.get(), 5);
+    }
+
+    #[test]
+    fn doubly_linked_list_push_back_remove_works() {
+        let mut allocator = MockAllocator::new();
+
+        let mut head = NonNull::from(Box::leak(Box::new(DoublyLinkedListNode {
+            next: NonNull::dangling(),
+            previous: NonNull::dangling(),
+            value: 0u32,
+        })));
+        unsafe {
+            head.as_mut().next = head;
+            head.as_mut().previous = head;
+        }
+
+        let mut list = DoublyLinkedList {
+            allocator: Some(allocator.handle()),
+            head,
+            count: 0,
+        };
+
+        (1..=5).for_each(|v| {
+            list.push_back(v);
+        });
+        assert_eq!(
+            list.iter().copied().collect::<Vec<_>>(),
+            vec![1, 2, 3, 4, 5]
+        );
+        assert_eq!(allocator.live_allocations(), 5);
+
+        assert_eq!(list.remove(|v| *v == 3), Some(3));
+        assert_eq!(list.remove(|v| *v == 1), Some(1));
+        assert_eq!(list.remove(|v| *v == 5), Some(5));
+        assert_eq!(list.remove(|v| *v == 42), None);
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![2, 4]);
+        assert_eq!(allocator.live_allocations(), 2);
+
+        list.push_back(6);
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![2, 4, 6]);
+        assert_eq!(list.len(), 3);
+    }
+
+    #[test]
+    fn tree_insert_remove_works() {
+        let mut allocator = MockAllocator::new();
+        let mut tree = empty_tree(&mut allocator);
+
+        // Visit the values in a scrambled but deterministic order.
+        let values = (0..101).map(|i| (i * 37) % 101).collect::<Vec<u32>>();
+        for value in values.iter() {
+            tree.insert(*value, |v| *v);
+            validate_tree(&tree);
+        }
+
+        assert_eq!(
+            tree.iter().copied().collect::<Vec<_>>(),
+            (0..101).collect::<Vec<_>>()
+        );
+        assert_eq!(allocator.live_allocations(), 101);
+
+        for value in values.iter().filter(|v| *v % 2 == 0) {
+            assert_eq!(tree.remove(|v| v.cmp(value)), Some(*value));
+            validate_tree(&tree);
+        }
+
+        assert_eq!(tree.remove(|v| v.cmp(&2)), None);
+        assert_eq!(
+            tree.iter().copied().collect::<Vec<_>>(),
+            (0..101).filter(|v| v % 2 == 1).collect::<Vec<_>>()
+        );
+
+        for value in values.iter().rev().filter(|v| *v % 2 == 1) {
+            assert_eq!(tree.remove(|v| v.cmp(value)), Some(*value));
+            validate_tree(&tree);
+        }
+
+        assert!(tree.is_empty());
+        assert_eq!(allocator.live_allocations(), 0);
+    }
+
+    #[test]
+    fn tree_insert_keeps_duplicates() {
+        let mut allocator = MockAllocator::new();
+        let mut tree = empty_tree(&mut allocator);
+
+        [3, 1, 3, 2, 3].into_iter().for_each(|v| {
+            tree.insert(v, |v| *v);
+        });
+        validate_tree(&tree);
+
+        assert_eq!(tree.find_by(|v| v.cmp(&3)).count(), 3);
+        assert_eq!(tree.remove(|v| v.cmp(&3)), Some(3));
+        assert_eq!(tree.find_by(|v| v.cmp(&3)).count(), 2);
+        validate_tree(&tree);
+    }
+}
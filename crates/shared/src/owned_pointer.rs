@@ -15,6 +15,13 @@ use std::{
 pub struct OwnedPtr<T>(NonNull<T>);
 
 impl<T> OwnedPtr<T> {
+    /// # Safety
+    ///
+    /// The pointee must be owned by whoever ends up holding the pointer, see [`OwnedPtr`].
+    pub unsafe fn from_raw(ptr: NonNull<T>) -> Self {
+        Self(ptr)
+    }
+
     pub fn as_ptr(&self) -> *mut T {
         self.0.as_ptr()
     }
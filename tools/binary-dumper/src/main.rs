@@ -0,0 +1,45 @@
+use std::{error::Error, fs::File, path::PathBuf};
+
+use clap::{Parser, Subcommand};
+use eldenring_util::program::Program;
+use memmap::MmapOptions;
+use pelite::pe64::PeFile;
+
+mod rtti;
+mod singletons;
+
+/// Dump information from a binary without running it.
+#[derive(Parser)]
+struct Args {
+    #[arg(long, env("DUMPER_GAME_EXE"))]
+    exe: PathBuf,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Dump the class hierarchies recovered from the RTTI.
+    Rtti(rtti::RttiArgs),
+    /// Dump the names of the DLRF singletons and the RVAs of their statics.
+    Singletons(singletons::SingletonArgs),
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args = Args::parse();
+
+    let exe_file = File::open(&args.exe)
+        .map_err(|e| format!("Could not open game binary {}: {e}", args.exe.display()))?;
+    let exe_mmap = unsafe { MmapOptions::new().map(&exe_file) }
+        .map_err(|e| format!("Could not mmap game binary: {e}"))?;
+    let program = Program::File(
+        PeFile::from_bytes(&exe_mmap[0..])
+            .map_err(|e| format!("Could not create PE view for game binary: {e}"))?,
+    );
+
+    match args.command {
+        Command::Rtti(rtti_args) => rtti::dump(&program, rtti_args),
+        Command::Singletons(singleton_args) => singletons::dump(&program, singleton_args),
+    }
+}
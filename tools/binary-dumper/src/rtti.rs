@@ -0,0 +1,136 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    error::Error,
+};
+
+use clap::{Args, ValueEnum};
+use eldenring_util::{program::Program, rtti::find_rtti_classes};
+use pelite::pe64::Pe;
+use serde::Serialize;
+
+#[derive(ValueEnum, Clone)]
+pub enum RttiOutputFormat {
+    Json,
+    Graphviz,
+}
+
+#[derive(Args)]
+pub struct RttiArgs {
+    #[arg(long, env("DUMPER_OUTPUT_FORMAT"))]
+    output: RttiOutputFormat,
+
+    /// Only keep classes whose name contains this string (and their bases).
+    #[arg(long)]
+    filter: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct DumpedClass {
+    name: String,
+    vftable_rva: u32,
+    vftable_len: usize,
+    /// Offset of the vftable within the complete object.
+    vftable_offset: u32,
+    /// Attributes of the class hierarchy descriptor.
+    attributes: u32,
+    /// All base classes in the order the class hierarchy descriptor lists them.
+    bases: Vec<DumpedBaseClass>,
+    /// Names of the classes this class directly inherits from.
+    direct_bases: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct DumpedBaseClass {
+    name: String,
+    contained_bases: u32,
+    member_displacement: i32,
+    vbtable_displacement: i32,
+    vbtable_entry_displacement: i32,
+    attributes: u32,
+}
+
+pub fn dump(program: &Program, args: RttiArgs) -> Result<(), Box<dyn Error>> {
+    // Class discovery and vftable lengths depend on both sections being present.
+    for section in [".text", ".rdata"] {
+        if program.section_headers().by_name(section).is_none() {
+            return Err(format!("Binary has no {section} section").into());
+        }
+    }
+
+    let mut classes = find_rtti_classes(program)
+        .filter_map(|class| {
+            let hierarchy = class.hierarchy()?;
+
+            Some(DumpedClass {
+                vftable_rva: class.vftable,
+                vftable_len: class.vftable_len()?,
+                vftable_offset: class.vftable_offset()?,
+                attributes: hierarchy.attributes,
+                direct_bases: hierarchy
+                    .direct_bases()
+                    .map(|base| base.name.clone())
+                    .collect(),
+                bases: hierarchy
+                    .base_classes
+                    .iter()
+                    .skip(1)
+                    .map(|base| DumpedBaseClass {
+                        name: base.name.clone(),
+                        contained_bases: base.contained_bases,
+                        member_displacement: base.member_displacement,
+                        vbtable_displacement: base.vbtable_displacement,
+                        vbtable_entry_displacement: base.vbtable_entry_displacement,
+                        attributes: base.attributes,
+                    })
+                    .collect(),
+                name: class.name,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    if let Some(filter) = args.filter.as_ref() {
+        let keep = classes
+            .iter()
+            .filter(|c| c.name.contains(filter.as_str()))
+            .flat_map(|c| {
+                std::iter::once(c.name.clone()).chain(c.bases.iter().map(|b| b.name.clone()))
+            })
+            .collect::<BTreeSet<_>>();
+
+        classes.retain(|c| keep.contains(&c.name));
+    }
+
+    classes.sort_by(|a, b| a.name.cmp(&b.name).then(a.vftable_rva.cmp(&b.vftable_rva)));
+
+    match args.output {
+        RttiOutputFormat::Json => println!("{}", serde_json::to_string_pretty(&classes)?),
+        RttiOutputFormat::Graphviz => println!("{}", graphviz(&classes)),
+    }
+
+    Ok(())
+}
+
+/// Renders the inheritance graph as a dot file. Classes with multiple vftables
+/// end up as a single node.
+fn graphviz(classes: &[DumpedClass]) -> String {
+    let edges = classes
+        .iter()
+        .map(|c| (c.name.as_str(), c.direct_bases.iter().map(String::as_str)))
+        .fold(
+            BTreeMap::<&str, BTreeSet<&str>>::new(),
+            |mut acc, (name, bases)| {
+                acc.entry(name).or_default().extend(bases);
+                acc
+            },
+        );
+
+    let mut output = String::from("digraph rtti {\n    rankdir=LR;\n    node [shape=box];\n");
+    for (class, bases) in edges.iter() {
+        output.push_str(&format!("    \"{class}\";\n"));
+        for base in bases {
+            output.push_str(&format!("    \"{class}\" -> \"{base}\";\n"));
+        }
+    }
+    output.push('}');
+    output
+}
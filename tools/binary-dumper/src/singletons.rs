@@ -0,0 +1,51 @@
+use std::{collections::BTreeMap, error::Error};
+
+use clap::{Args, ValueEnum};
+use eldenring_util::{program::Program, singleton::build_singleton_table_static};
+
+#[derive(ValueEnum, Clone)]
+pub enum SingletonOutputFormat {
+    Json,
+    Rust,
+}
+
+#[derive(Args)]
+pub struct SingletonArgs {
+    #[arg(long, env("DUMPER_OUTPUT_FORMAT"))]
+    output: SingletonOutputFormat,
+}
+
+pub fn dump(program: &Program, args: SingletonArgs) -> Result<(), Box<dyn Error>> {
+    let table = build_singleton_table_static(program)
+        .map_err(|e| format!("Could not build singleton table: {e}"))?;
+    if !table.skipped.is_empty() {
+        let skipped = table
+            .skipped
+            .iter()
+            .map(|rva| format!("{rva:#x}"))
+            .collect::<Vec<_>>();
+        eprintln!(
+            "Warning: could not resolve the names of {} singleton statics, the table is incomplete: {}",
+            skipped.len(),
+            skipped.join(", ")
+        );
+    }
+
+    let singletons = table.singletons.into_iter().collect::<BTreeMap<_, _>>();
+
+    match args.output {
+        SingletonOutputFormat::Json => println!("{}", serde_json::to_string_pretty(&singletons)?),
+        SingletonOutputFormat::Rust => {
+            let lines = singletons
+                .iter()
+                .map(|(name, rva)| format!("    (\"{name}\", {rva:#x}),"))
+                .collect::<Vec<_>>();
+            println!(
+                "pub const SINGLETON_STATICS: &[(&str, u32)] = &[\n{}\n];",
+                lines.join("\n")
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -9,7 +9,7 @@ use eldenring::cs::CSWindowImp;
 use eldenring::cs::CSWorldSceneDrawParamManager;
 use eldenring::cs::FieldArea;
 use eldenring_util::program::Program;
-use eldenring_util::system::wait_for_system_init;
+use eldenring_util::system::{self, wait_for_system_init};
 use hudhook::eject;
 use hudhook::hooks::dx12::ImguiDx12Hooks;
 use hudhook::imgui::Condition;
@@ -52,6 +52,7 @@ pub unsafe extern "C" fn DllMain(hmodule: HINSTANCE, reason: u32) -> bool {
         std::thread::spawn(move || {
             wait_for_system_init(&Program::current(), Duration::MAX)
                 .expect("Timeout waiting for system init");
+            system::init(&Program::current()).expect("Could not initialize");
 
             if let Err(e) = Hudhook::builder()
                 .with::<ImguiDx12Hooks>(EldenRingDebugGui::new())
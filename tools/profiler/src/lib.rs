@@ -1,7 +1,8 @@
 use std::{
     collections::HashMap,
     mem::transmute,
-    sync::{LazyLock, RwLock},
+    sync::{LazyLock, OnceLock, RwLock},
+    time::Duration,
 };
 
 use eldenring::{
@@ -9,14 +10,17 @@ use eldenring::{
     fd4::{FD4TaskBase, FD4TaskData, FD4TaskRequestEntry},
 };
 use eldenring_util::{
-    program::Program, rtti::vftable_classname, singleton::get_instance, task::CSTaskImpExt,
+    hook::{Hook, HookTarget},
+    program::Program,
+    rtti::vftable_classname,
+    singleton::wait_for_instance,
+    system::wait_for_system_init,
+    task::CSTaskImpExt,
 };
-use pelite::pe64::Pe;
-use retour::static_detour;
 
-static_detour! {
-    static FD4_EXECUTE_TASK_DETOUR: extern "C" fn(usize, *const FD4TaskRequestEntry, u32, u32);
-}
+type FD4ExecuteTaskFn = extern "C" fn(usize, *const FD4TaskRequestEntry, u32, u32);
+
+static FD4_EXECUTE_TASK_HOOK: OnceLock<Hook<FD4ExecuteTaskFn>> = OnceLock::new();
 
 const FD4_EXECUTE_TASK_RVA: u32 = 0x26d54a0;
 
@@ -27,61 +31,61 @@ const FD4_EXECUTE_TASK_RVA: u32 = 0x26d54a0;
 pub unsafe extern "C" fn DllMain(_base: usize, reason: u32) -> bool {
     if reason == 1 {
         let tracy = tracy_client::Client::start();
-        let f4_execute_task_va = Program::current().rva_to_va(FD4_EXECUTE_TASK_RVA).unwrap();
-
-        FD4_EXECUTE_TASK_DETOUR
-            .initialize(
-                std::mem::transmute::<
-                    u64,
-                    extern "C" fn(usize, *const FD4TaskRequestEntry, u32, u32),
-                >(f4_execute_task_va),
-                move |task_group, request_entry, task_group_index, task_runner_index| {
-                    let task = unsafe { request_entry.as_ref() }
-                        .map(|r| r.task.as_ref())
-                        .and_then(label_task)
-                        .unwrap_or(String::from("Unknown Task Type"));
-
-                    let task_group_label: CSTaskGroupIndex =
-                        unsafe { transmute(task_group_index - 0x90000000) };
-                    let span_label = format!("{task_group_label:?} {task}");
-                    let _span = tracy_client::Client::running().map(|c| {
-                        c.span_alloc(
-                            Some(span_label.as_str()),
-                            "FD4TaskExecute",
-                            "profiler.rs",
-                            0,
-                            0,
-                        )
-                    });
-
-                    FD4_EXECUTE_TASK_DETOUR.call(
-                        task_group,
-                        request_entry,
-                        task_group_index,
-                        task_runner_index,
-                    );
-                },
-            )
-            .unwrap()
-            .enable()
-            .unwrap();
+
+        let hook = Hook::new(
+            HookTarget::Rva(FD4_EXECUTE_TASK_RVA),
+            fd4_execute_task as FD4ExecuteTaskFn,
+        )
+        .unwrap();
+        FD4_EXECUTE_TASK_HOOK.get_or_init(|| hook).enable();
 
         std::thread::spawn(move || {
-            // Wait for CSTask to become a thing
-            // TODO: write optimized wait for with thread parking?
-            std::thread::sleep(std::time::Duration::from_secs(5));
+            wait_for_system_init(&Program::current(), Duration::MAX)
+                .expect("Could not await system init.");
 
-            let task = get_instance::<CSTaskImp>().unwrap().unwrap();
-            std::mem::forget(task.run_recurring(
+            let task =
+                wait_for_instance::<CSTaskImp>(Duration::MAX).expect("Could not await CSTaskImp.");
+            task.run_recurring(
                 move |_: &FD4TaskData| tracy.frame_mark(),
                 CSTaskGroupIndex::FrameEnd,
-            ));
+            );
         });
     }
 
     true
 }
 
+extern "C" fn fd4_execute_task(
+    task_group: usize,
+    request_entry: *const FD4TaskRequestEntry,
+    task_group_index: u32,
+    task_runner_index: u32,
+) {
+    let task = unsafe { request_entry.as_ref() }
+        .map(|r| unsafe { r.task.as_ref() })
+        .and_then(label_task)
+        .unwrap_or(String::from("Unknown Task Type"));
+
+    let task_group_label: CSTaskGroupIndex = unsafe { transmute(task_group_index - 0x90000000) };
+    let span_label = format!("{task_group_label:?} {task}");
+    let _span = tracy_client::Client::running().map(|c| {
+        c.span_alloc(
+            Some(span_label.as_str()),
+            "FD4TaskExecute",
+            "profiler.rs",
+            0,
+            0,
+        )
+    });
+
+    FD4_EXECUTE_TASK_HOOK.get().unwrap().original()(
+        task_group,
+        request_entry,
+        task_group_index,
+        task_runner_index,
+    );
+}
+
 /// Determines the label for a given FD4TaskBase instance
 fn label_task(task: &FD4TaskBase) -> Option<String> {
     let mut name = lookup_rtti_classname(*task.vftable as *const _ as usize)?;